@@ -14,7 +14,11 @@ cargo doc --no-deps -p tsukuyomi-server --all-features
 
 cargo doc --no-deps -p tsukuyomi-askama
 cargo doc --no-deps -p tsukuyomi-cors
-cargo doc --no-deps -p tsukuyomi-juniper
+cargo doc --no-deps -p tsukuyomi-csrf --all-features
+cargo doc --no-deps -p tsukuyomi-juniper --all-features
+cargo doc --no-deps -p tsukuyomi-jwt
+cargo doc --no-deps -p tsukuyomi-prometheus
+cargo doc --no-deps -p tsukuyomi-ratelimit --all-features
 cargo doc --no-deps -p tsukuyomi-session --all-features
 cargo doc --no-deps -p tsukuyomi-tungstenite
 
@@ -14,6 +14,7 @@ if cargo clippy --version >/dev/null 2>&1; then
 
     cargo clippy -p tsukuyomi --all-features --all-targets
     cargo clippy -p tsukuyomi-session --all-features --all-targets
+    cargo clippy -p tsukuyomi-juniper --all-features --all-targets
 fi
 
 cargo test --all
@@ -23,3 +24,7 @@ cargo test -p tsukuyomi --no-default-features
 
 cargo test -p tsukuyomi-session --all-features
 cargo test -p tsukuyomi-session --no-default-features
+
+cargo test -p tsukuyomi-juniper --all-features
+
+cargo test -p tsukuyomi-server --features use-rustls,tsukuyomi
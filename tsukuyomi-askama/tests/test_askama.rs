@@ -60,3 +60,66 @@ fn test_template_with_modifier() -> tsukuyomi_server::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_template_with_flash_messages() -> tsukuyomi_server::Result<()> {
+    use {
+        http::Request,
+        tsukuyomi::output::redirect,
+        tsukuyomi_session::{
+            backend::CookieBackend,
+            flash::{FlashMessage, Level},
+            session, Session,
+        },
+    };
+
+    #[derive(Template, IntoResponse)]
+    #[template(
+        source = "{% for message in flashes %}<p class=\"{{ message.level() }}\">{{ message }}</p>{% endfor %}",
+        ext = "html"
+    )]
+    #[response(preset = "tsukuyomi_askama::Askama")]
+    struct Index {
+        flashes: Vec<FlashMessage>,
+    }
+
+    let session = std::sync::Arc::new(session(CookieBackend::plain()));
+
+    let app = App::create(
+        path!("/") //
+            .to(chain![
+                endpoint::get() //
+                    .extract(session.clone())
+                    .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                        let flashes = session.take_flashes()?;
+                        Ok(session.finish(Index { flashes }))
+                    }),
+                endpoint::post() //
+                    .extract(session)
+                    .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                        session.flash(Level::Success, "Saved.")?;
+                        session.flash(Level::Error, "<failed>")?;
+                        Ok(session.finish(redirect::see_other("/")))
+                    }),
+            ]),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    let response = client.perform(Request::post("/"))?;
+    assert_eq!(response.status(), 303);
+
+    let response = client.perform("/")?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("content-type")?, "text/html");
+    assert_eq!(
+        response.body().to_utf8()?,
+        "<p class=\"success\">Saved.</p><p class=\"error\">&lt;failed&gt;</p>"
+    );
+
+    // the flash messages are rendered only once.
+    let response = client.perform("/")?;
+    assert_eq!(response.body().to_utf8()?, "");
+
+    Ok(())
+}
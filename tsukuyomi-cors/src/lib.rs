@@ -22,25 +22,33 @@ use {
             ACCESS_CONTROL_ALLOW_HEADERS,
             ACCESS_CONTROL_ALLOW_METHODS,
             ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS,
             ACCESS_CONTROL_MAX_AGE,
             ACCESS_CONTROL_REQUEST_HEADERS,
             ACCESS_CONTROL_REQUEST_METHOD,
             ORIGIN,
+            VARY,
         },
         HttpTryFrom, Method, Request, Response, StatusCode, Uri,
     },
-    std::{collections::HashSet, sync::Arc, time::Duration},
-    tsukuyomi::{HttpError, Input},
+    std::{collections::HashSet, fmt, sync::Arc, time::Duration},
+    tsukuyomi::{error::Error, handler::AllowedMethods, HttpError, Input},
 };
 
+const ACCESS_CONTROL_ALLOW_PRIVATE_NETWORK: &str = "access-control-allow-private-network";
+const ACCESS_CONTROL_REQUEST_PRIVATE_NETWORK: &str = "access-control-request-private-network";
+
 /// A builder of `CORS`.
 #[derive(Debug, Default)]
 pub struct Builder {
-    origins: Option<HashSet<Uri>>,
+    origins: Option<Origins>,
     methods: Option<HashSet<Method>>,
     headers: Option<HashSet<HeaderName>>,
+    expose_headers: Option<HashSet<HeaderName>>,
     max_age: Option<Duration>,
     allow_credentials: bool,
+    allow_private_network: bool,
+    on_error: Option<ErrorHandler>,
 }
 
 impl Builder {
@@ -57,6 +65,7 @@ impl Builder {
         let origin = Uri::try_from(origin).map_err(Into::into)?;
         self.origins
             .get_or_insert_with(Default::default)
+            .exact
             .insert(origin);
         Ok(self)
     }
@@ -73,10 +82,37 @@ impl Builder {
             .map_err(Into::into)?;
         self.origins
             .get_or_insert_with(Default::default)
+            .exact
             .extend(origins);
         Ok(self)
     }
 
+    /// Allows the origins matching the specified pattern.
+    ///
+    /// The pattern has the form of an origin, whose host may start with the
+    /// wildcard label `*.` (e.g. `"https://*.example.com"`).  The wildcard matches
+    /// one or more labels of subdomains, but not the parent domain itself.
+    pub fn allow_origin_pattern(mut self, pattern: &str) -> http::Result<Self> {
+        let pattern = OriginPattern::parse(pattern)?;
+        self.origins
+            .get_or_insert_with(Default::default)
+            .patterns
+            .push(pattern);
+        Ok(self)
+    }
+
+    /// Allows the origins for which the specified predicate returns `true`.
+    pub fn allow_origin_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Uri) -> bool + Send + Sync + 'static,
+    {
+        self.origins
+            .get_or_insert_with(Default::default)
+            .predicates
+            .push(OriginPredicate(Box::new(f)));
+        self
+    }
+
     #[allow(missing_docs)]
     pub fn allow_method<M>(mut self, method: M) -> http::Result<Self>
     where
@@ -133,6 +169,34 @@ impl Builder {
         Ok(self)
     }
 
+    /// Adds a header name that is exposed to the client by `Access-Control-Expose-Headers`.
+    pub fn expose_header<H>(mut self, header: H) -> http::Result<Self>
+    where
+        HeaderName: HttpTryFrom<H>,
+    {
+        let header = HeaderName::try_from(header).map_err(Into::into)?;
+        self.expose_headers
+            .get_or_insert_with(Default::default)
+            .insert(header);
+        Ok(self)
+    }
+
+    /// Adds the header names that are exposed to the client by `Access-Control-Expose-Headers`.
+    pub fn expose_headers<H>(mut self, headers: impl IntoIterator<Item = H>) -> http::Result<Self>
+    where
+        HeaderName: HttpTryFrom<H>,
+    {
+        let headers = headers
+            .into_iter()
+            .map(HeaderName::try_from)
+            .collect::<Result<Vec<HeaderName>, _>>()
+            .map_err(Into::into)?;
+        self.expose_headers
+            .get_or_insert_with(Default::default)
+            .extend(headers);
+        Ok(self)
+    }
+
     #[allow(missing_docs)]
     pub fn allow_credentials(self, enabled: bool) -> Self {
         Self {
@@ -149,60 +213,227 @@ impl Builder {
         }
     }
 
+    /// Sets whether to allow the requests from public networks to private networks.
+    ///
+    /// If enabled, the preflight requests with `Access-Control-Request-Private-Network: true`
+    /// are responded with `Access-Control-Allow-Private-Network: true`.
+    pub fn allow_private_network(self, enabled: bool) -> Self {
+        Self {
+            allow_private_network: enabled,
+            ..self
+        }
+    }
+
+    /// Sets the function that converts the rejected CORS requests into errors.
+    ///
+    /// By default, the rejected requests are responded with `403 Forbidden`
+    /// and the description of `CORSErrorKind`.
+    pub fn on_error<F, E>(self, f: F) -> Self
+    where
+        F: Fn(CORSError) -> E + Send + Sync + 'static,
+        E: Into<Error>,
+    {
+        Self {
+            on_error: Some(ErrorHandler(Box::new(move |err| f(err).into()))),
+            ..self
+        }
+    }
+
     #[allow(missing_docs)]
     pub fn build(self) -> CORS {
-        let methods = self.methods.unwrap_or_else(|| {
+        let default_methods = Arc::new(Methods::new(self.methods.clone().unwrap_or_else(|| {
             vec![Method::GET, Method::POST, Method::OPTIONS]
                 .into_iter()
                 .collect()
-        });
+        })));
 
-        let methods_value = HeaderValue::from_shared(
-            methods
-                .iter()
-                .enumerate()
-                .fold(String::new(), |mut acc, (i, m)| {
-                    if i > 0 {
-                        acc += ",";
-                    }
-                    acc += m.as_str();
-                    acc
-                })
-                .into(),
-        )
-        .expect("should be a valid header value");
-
-        let headers_value = self.headers.as_ref().map(|hdrs| {
-            HeaderValue::from_shared(
-                hdrs.iter()
-                    .enumerate()
-                    .fold(String::new(), |mut acc, (i, hdr)| {
-                        if i > 0 {
-                            acc += ",";
-                        }
-                        acc += hdr.as_str();
-                        acc
-                    })
-                    .into(),
-            )
-            .expect("should be a valid header value")
-        });
+        let headers_value = self
+            .headers
+            .as_ref()
+            .map(|hdrs| join_header_values(hdrs.iter().map(HeaderName::as_str)));
+        let expose_headers_value = self
+            .expose_headers
+            .as_ref()
+            .map(|hdrs| join_header_values(hdrs.iter().map(HeaderName::as_str)));
 
         CORS {
             inner: Arc::new(Inner {
                 origins: self.origins,
-                methods,
-                methods_value,
+                methods: self.methods,
+                default_methods,
                 headers: self.headers,
                 headers_value,
+                expose_headers_value,
                 max_age: self.max_age,
                 allow_credentials: self.allow_credentials,
+                allow_private_network: self.allow_private_network,
+                on_error: self.on_error,
             }),
         }
     }
 }
 
+fn join_header_values<'a>(values: impl Iterator<Item = &'a str>) -> HeaderValue {
+    let joined = values.enumerate().fold(String::new(), |mut acc, (i, v)| {
+        if i > 0 {
+            acc += ",";
+        }
+        acc += v;
+        acc
+    });
+    HeaderValue::from_shared(joined.into()).expect("should be a valid header value")
+}
+
+/// The set of allowed methods, along with the value of `Access-Control-Allow-Methods`.
+#[derive(Debug)]
+struct Methods {
+    set: HashSet<Method>,
+    value: HeaderValue,
+}
+
+impl Methods {
+    fn new(set: HashSet<Method>) -> Self {
+        let value = join_header_values(set.iter().map(Method::as_str));
+        Self { set, value }
+    }
+}
+
+/// The set of allowed origins.
+#[derive(Debug, Default)]
+struct Origins {
+    exact: HashSet<Uri>,
+    patterns: Vec<OriginPattern>,
+    predicates: Vec<OriginPredicate>,
+}
+
+impl Origins {
+    fn contains(&self, origin: &Uri) -> bool {
+        self.exact.contains(origin)
+            || self.patterns.iter().any(|pattern| pattern.matches(origin))
+            || self
+                .predicates
+                .iter()
+                .any(|predicate| (predicate.0)(origin))
+    }
+}
+
+/// An origin whose host may contain the wildcard label, e.g. `https://*.example.com`.
+#[derive(Debug)]
+struct OriginPattern {
+    scheme: Option<String>,
+    host: String,
+    port: String,
+    wildcard: bool,
+}
+
+impl OriginPattern {
+    fn parse(pattern: &str) -> http::Result<Self> {
+        let (wildcard, origin) = match pattern.find("://*.") {
+            Some(pos) => (
+                true,
+                format!("{}://{}", &pattern[..pos], &pattern[pos + "://*.".len()..]),
+            ),
+            None => (false, pattern.to_owned()),
+        };
+        let origin: Uri = origin.parse()?;
+        Ok(Self {
+            scheme: origin
+                .scheme_part()
+                .map(|scheme| scheme.as_str().to_owned()),
+            host: origin.host().unwrap_or("").to_ascii_lowercase(),
+            port: port_suffix(&origin).to_owned(),
+            wildcard,
+        })
+    }
+
+    fn matches(&self, origin: &Uri) -> bool {
+        let host = match origin.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        if origin.scheme_part().map(|scheme| scheme.as_str()) != self.scheme.as_ref().map(|s| &**s)
+            || port_suffix(origin) != self.port
+        {
+            return false;
+        }
+        if self.wildcard {
+            host.len() > self.host.len() + 1
+                && host.ends_with(&*self.host)
+                && host[..host.len() - self.host.len()].ends_with('.')
+        } else {
+            host == self.host
+        }
+    }
+}
+
+/// Returns the port part of the authority including the leading colon, e.g. `":8080"`.
+fn port_suffix(uri: &Uri) -> &str {
+    let authority = uri
+        .authority_part()
+        .map_or("", |authority| authority.as_str());
+    match authority.rfind(':') {
+        Some(pos) if !authority[pos..].contains(']') => &authority[pos..],
+        _ => "",
+    }
+}
+
+struct OriginPredicate(Box<dyn Fn(&Uri) -> bool + Send + Sync + 'static>);
+
+#[cfg_attr(tarpaulin, skip)]
+impl fmt::Debug for OriginPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OriginPredicate").finish()
+    }
+}
+
+struct ErrorHandler(Box<dyn Fn(CORSError) -> Error + Send + Sync + 'static>);
+
+#[cfg_attr(tarpaulin, skip)]
+impl fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ErrorHandler").finish()
+    }
+}
+
 /// The main type for providing the CORS filtering.
+///
+/// A `CORS` can be used in two ways:
+///
+/// * As a `ModifyHandler` applied to routes or scopes.  The preflight requests
+///   to each route are answered with the methods actually accepted by the route,
+///   limited to the ones specified by `Builder::allow_methods` if any.
+/// * As an `Endpoint` that only answers the preflight requests, e.g. `OPTIONS *`.
+///   Since the target route is unknown, the methods specified in the builder
+///   (or `GET`, `POST` and `OPTIONS` by default) are used.
+///
+/// Different policies can be used for different routes by applying separate
+/// `CORS` values to them:
+///
+/// ```
+/// # use tsukuyomi::{config::prelude::*, App};
+/// # use tsukuyomi_cors::CORS;
+/// # fn main() -> tsukuyomi_server::Result<()> {
+/// let public = CORS::new();
+/// let private = CORS::builder()
+///     .allow_origin("https://app.example.com")?
+///     .allow_credentials(true)
+///     .build();
+///
+/// let app = App::create(chain![
+///     path!("/public/items")
+///         .to(endpoint::get().call(|| "items"))
+///         .modify(public),
+///     path!("/private/items")
+///         .to(endpoint::allow_only("GET, POST")?.call(|| "private items"))
+///         .modify(private),
+/// ])?;
+/// # drop(app);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Note that the `CORS`es should not be nested, since the outer one answers the
+/// preflight requests before the inner one is reached.
 #[derive(Debug, Clone)]
 pub struct CORS {
     inner: Arc<Inner>,
@@ -267,15 +498,14 @@ mod impl_endpoint_for_cors {
         type Error = Error;
 
         fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
-            match self.cors.inner.validate_origin(input.request) {
-                Ok(Some(origin)) => self
-                    .cors
-                    .inner
-                    .process_preflight_request(input.request, origin)
+            let inner = &self.cors.inner;
+            match inner.validate_origin(input.request) {
+                Ok(Some(origin)) => inner
+                    .process_preflight_request(input.request, origin, &inner.default_methods)
                     .map(Into::into)
-                    .map_err(Into::into),
+                    .map_err(|err| inner.reject(err)),
                 Ok(None) => Err(StatusCode::NOT_FOUND.into()),
-                Err(err) => Err(err.into()),
+                Err(err) => Err(inner.reject(err)),
             }
         }
     }
@@ -283,9 +513,10 @@ mod impl_endpoint_for_cors {
 
 mod impl_modify_handler_for_cors {
     use {
-        super::CORS,
+        super::{Methods, CORS},
         either::Either,
         http::{Method, Response},
+        std::sync::Arc,
         tsukuyomi::{
             error::Error,
             future::{Async, Poll, TryFuture},
@@ -312,9 +543,12 @@ mod impl_modify_handler_for_cors {
                 methods
             });
 
+            let methods = self.inner.route_methods(allowed_methods.as_ref());
+
             CORSHandler {
                 handler,
                 allowed_methods,
+                methods,
                 cors: self.clone(),
             }
         }
@@ -324,6 +558,7 @@ mod impl_modify_handler_for_cors {
     pub struct CORSHandler<H> {
         handler: H,
         allowed_methods: Option<AllowedMethods>,
+        methods: Arc<Methods>,
         cors: CORS,
     }
 
@@ -340,7 +575,7 @@ mod impl_modify_handler_for_cors {
         #[inline]
         fn handle(&self) -> Self::Handle {
             CORSHandle {
-                cors: Some(self.cors.clone()),
+                cors: Some((self.cors.clone(), self.methods.clone())),
                 handle: self.handler.handle(),
             }
         }
@@ -348,7 +583,7 @@ mod impl_modify_handler_for_cors {
 
     #[derive(Debug)]
     pub struct CORSHandle<H: TryFuture> {
-        cors: Option<CORS>,
+        cors: Option<(CORS, Arc<Methods>)>,
         handle: H,
     }
 
@@ -357,8 +592,12 @@ mod impl_modify_handler_for_cors {
         type Error = Error;
 
         fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
-            if let Some(cors) = self.cors.take() {
-                if let Some(output) = cors.inner.process_request(input)? {
+            if let Some((cors, methods)) = self.cors.take() {
+                let output = cors
+                    .inner
+                    .process_request(input, &methods)
+                    .map_err(|err| cors.inner.reject(err))?;
+                if let Some(output) = output {
                     return Ok(Async::Ready(Either::Left(output)));
                 }
             }
@@ -372,16 +611,46 @@ mod impl_modify_handler_for_cors {
 
 #[derive(Debug)]
 struct Inner {
-    origins: Option<HashSet<Uri>>,
-    methods: HashSet<Method>,
-    methods_value: HeaderValue,
+    origins: Option<Origins>,
+    methods: Option<HashSet<Method>>,
+    default_methods: Arc<Methods>,
     headers: Option<HashSet<HeaderName>>,
     headers_value: Option<HeaderValue>,
+    expose_headers_value: Option<HeaderValue>,
     max_age: Option<Duration>,
     allow_credentials: bool,
+    allow_private_network: bool,
+    on_error: Option<ErrorHandler>,
 }
 
 impl Inner {
+    /// Returns whether the value of `Access-Control-Allow-Origin` depends on
+    /// the request's origin, rather than the fixed `*`.
+    fn varies_by_origin(&self) -> bool {
+        self.origins.is_some() || self.allow_credentials
+    }
+
+    /// Returns the methods allowed for the route accepting the specified methods.
+    fn route_methods(&self, allowed_methods: Option<&AllowedMethods>) -> Arc<Methods> {
+        match allowed_methods {
+            Some(allowed_methods) => Arc::new(Methods::new(
+                allowed_methods
+                    .into_iter()
+                    .filter(|method| self.methods.as_ref().map_or(true, |m| m.contains(*method)))
+                    .cloned()
+                    .collect(),
+            )),
+            None => self.default_methods.clone(),
+        }
+    }
+
+    fn reject(&self, err: CORSError) -> Error {
+        match self.on_error {
+            Some(ref on_error) => (on_error.0)(err),
+            None => err.into(),
+        }
+    }
+
     fn validate_origin<T>(&self, request: &Request<T>) -> Result<Option<AllowedOrigin>, CORSError> {
         let origin = match request.headers().get(ORIGIN) {
             Some(origin) => origin,
@@ -420,6 +689,7 @@ impl Inner {
     fn validate_request_method<T>(
         &self,
         request: &Request<T>,
+        methods: &Methods,
     ) -> Result<Option<HeaderValue>, CORSError> {
         match request.headers().get(ACCESS_CONTROL_REQUEST_METHOD) {
             Some(h) => {
@@ -428,8 +698,8 @@ impl Inner {
                     .map_err(|_| CORSErrorKind::InvalidRequestMethod)?
                     .parse()
                     .map_err(|_| CORSErrorKind::InvalidRequestMethod)?;
-                if self.methods.contains(&method) {
-                    Ok(Some(self.methods_value.clone()))
+                if methods.set.contains(&method) {
+                    Ok(Some(methods.value.clone()))
                 } else {
                     Err(CORSErrorKind::DisallowedRequestMethod.into())
                 }
@@ -472,8 +742,9 @@ impl Inner {
         &self,
         request: &Request<T>,
         origin: AllowedOrigin,
+        methods: &Methods,
     ) -> Result<Response<()>, CORSError> {
-        let allow_methods = self.validate_request_method(request)?;
+        let allow_methods = self.validate_request_method(request, methods)?;
         let allow_headers = self.validate_request_headers(request)?;
 
         let mut response = Response::default();
@@ -494,12 +765,40 @@ impl Inner {
                 .insert(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
         }
 
+        if self.allow_credentials {
+            response.headers_mut().insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+
         if let Some(max_age) = self.max_age {
             response
                 .headers_mut()
                 .insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
         }
 
+        if self.allow_private_network
+            && request
+                .headers()
+                .get(ACCESS_CONTROL_REQUEST_PRIVATE_NETWORK)
+                .map_or(false, |h| h == "true")
+        {
+            response.headers_mut().insert(
+                ACCESS_CONTROL_ALLOW_PRIVATE_NETWORK,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        // The preflight response is computed from the request headers and
+        // must not be reused for the other values by the caches.
+        response.headers_mut().insert(
+            VARY,
+            HeaderValue::from_static(
+                "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
+            ),
+        );
+
         Ok(response)
     }
 
@@ -507,9 +806,10 @@ impl Inner {
         &self,
         request: &Request<T>,
         origin: AllowedOrigin,
+        methods: &Methods,
         hdrs: &mut HeaderMap,
     ) -> Result<(), CORSError> {
-        if !self.methods.contains(request.method()) {
+        if !methods.set.contains(request.method()) {
             return Err(CORSErrorKind::DisallowedRequestMethod.into());
         }
 
@@ -522,21 +822,38 @@ impl Inner {
             );
         }
 
+        if let Some(ref expose_headers) = self.expose_headers_value {
+            hdrs.append(ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers.clone());
+        }
+
         Ok(())
     }
 
-    fn process_request(&self, input: &mut Input<'_>) -> Result<Option<Response<()>>, CORSError> {
+    fn process_request(
+        &self,
+        input: &mut Input<'_>,
+        methods: &Methods,
+    ) -> Result<Option<Response<()>>, CORSError> {
+        if self.varies_by_origin() && input.request.method() != Method::OPTIONS {
+            // The response depends on the value of `Origin`, including the case
+            // where the header is missing.
+            input
+                .response_headers
+                .get_or_insert_with(Default::default)
+                .append(VARY, HeaderValue::from_static("Origin"));
+        }
+
         let origin = match self.validate_origin(input.request)? {
             Some(origin) => origin,
             None => return Ok(None), // do nothing
         };
         if input.request.method() == Method::OPTIONS {
-            self.process_preflight_request(input.request, origin)
+            self.process_preflight_request(input.request, origin, methods)
                 .map(Some)
                 .map_err(Into::into)
         } else {
             let response_headers = input.response_headers.get_or_insert_with(Default::default);
-            self.process_simple_request(input.request, origin, response_headers)
+            self.process_simple_request(input.request, origin, methods, response_headers)
                 .map(|_| None)
                 .map_err(Into::into)
         }
@@ -590,7 +907,7 @@ impl HttpError for CORSError {
 }
 
 #[allow(missing_docs)]
-#[derive(Debug, Fail)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
 pub enum CORSErrorKind {
     #[fail(display = "the provided Origin is not a valid value.")]
     InvalidOrigin,
@@ -5,20 +5,22 @@ use {
             ACCESS_CONTROL_ALLOW_HEADERS,
             ACCESS_CONTROL_ALLOW_METHODS,
             ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS,
             ACCESS_CONTROL_MAX_AGE,
             ACCESS_CONTROL_REQUEST_HEADERS,
             ACCESS_CONTROL_REQUEST_METHOD,
             COOKIE,
             HOST,
             ORIGIN,
+            VARY,
         },
-        Method, Request,
+        Method, Request, StatusCode,
     },
     tsukuyomi::{
         config::prelude::*, //
         App,
     },
-    tsukuyomi_cors::CORS,
+    tsukuyomi_cors::{CORSErrorKind, CORS},
     tsukuyomi_server::test::ResponseExt,
 };
 
@@ -160,6 +162,137 @@ fn simple_request_with_allow_credentials() -> tsukuyomi_server::Result<()> {
     Ok(())
 }
 
+#[test]
+fn simple_request_with_origin_pattern_and_predicate() -> tsukuyomi_server::Result<()> {
+    let cors = CORS::builder()
+        .allow_origin_pattern("https://*.example.com")?
+        .allow_origin_fn(|origin| origin.host() == Some("localhost"))
+        .build();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get() //
+                .call(|| "hello"))
+            .modify(cors),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    for origin in &[
+        "https://api.example.com",
+        "https://a.b.example.com",
+        "http://localhost:8080",
+    ] {
+        let response = server.perform(
+            Request::get("/")
+                .header(HOST, "localhost")
+                .header(ORIGIN, *origin),
+        )?;
+        assert_eq!(response.status(), 200, "{}", origin);
+        assert_eq!(response.header(ACCESS_CONTROL_ALLOW_ORIGIN)?, *origin);
+    }
+
+    for origin in &[
+        "https://example.com",
+        "http://api.example.com",
+        "https://api.example.com:8443",
+        "https://evil-example.com",
+    ] {
+        let response = server.perform(
+            Request::get("/")
+                .header(HOST, "localhost")
+                .header(ORIGIN, *origin),
+        )?;
+        assert_eq!(response.status(), 403, "{}", origin);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn simple_request_with_expose_headers_and_vary() -> tsukuyomi_server::Result<()> {
+    let cors = CORS::builder()
+        .allow_origin("http://example.com")?
+        .expose_header("x-request-id")?
+        .build();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get() //
+                .call(|| "hello"))
+            .modify(cors),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(
+        Request::get("/")
+            .header(HOST, "localhost")
+            .header(ORIGIN, "http://example.com"),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.header(ACCESS_CONTROL_EXPOSE_HEADERS)?,
+        "x-request-id"
+    );
+    assert_eq!(response.header(VARY)?, "Origin");
+
+    // the response without Origin may also be cached differently.
+    let response = server.perform(
+        Request::get("/") //
+            .header(HOST, "localhost"),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    assert_eq!(response.header(VARY)?, "Origin");
+
+    let response = server.perform(
+        Request::get("/")
+            .header(HOST, "localhost")
+            .header(ORIGIN, "http://example.org"),
+    )?;
+    assert_eq!(response.status(), 403);
+    assert_eq!(response.header(VARY)?, "Origin");
+
+    Ok(())
+}
+
+#[test]
+fn custom_rejection() -> tsukuyomi_server::Result<()> {
+    let cors = CORS::builder()
+        .allow_origin("http://example.com")?
+        .on_error(|err| match err.kind() {
+            CORSErrorKind::DisallowedOrigin => {
+                tsukuyomi::error::custom(StatusCode::NOT_FOUND, "not found")
+            }
+            _ => err.into(),
+        })
+        .build();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get() //
+                .call(|| "hello"))
+            .modify(cors),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(
+        Request::get("/")
+            .header(HOST, "localhost")
+            .header(ORIGIN, "http://example.org"),
+    )?;
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.body().to_utf8()?, "not found");
+
+    let response = server.perform(
+        Request::get("/")
+            .header(HOST, "localhost")
+            .header(ORIGIN, "invalid origin"),
+    )?;
+    assert_eq!(response.status(), 403);
+
+    Ok(())
+}
+
 macro_rules! assert_methods {
     ($h:expr, [$($METHOD:ident),*]) => {{
         let h_str = $h.to_str()?;
@@ -353,6 +486,39 @@ fn preflight_max_age() -> tsukuyomi_server::Result<()> {
     Ok(())
 }
 
+#[test]
+fn preflight_private_network() -> tsukuyomi_server::Result<()> {
+    const ALLOW_PRIVATE_NETWORK: &str = "access-control-allow-private-network";
+    const REQUEST_PRIVATE_NETWORK: &str = "access-control-request-private-network";
+
+    let app = App::create(chain![
+        path!("/public").to(CORS::new()),
+        path!("/private").to(CORS::builder().allow_private_network(true).build()),
+    ])?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(
+        Request::options("/private")
+            .header(ORIGIN, "http://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(REQUEST_PRIVATE_NETWORK, "true"),
+    )?;
+    assert_eq!(response.status(), 204);
+    assert_eq!(response.header(ALLOW_PRIVATE_NETWORK)?, "true");
+    assert!(response.header(VARY)?.to_str()?.contains("Origin"));
+
+    let response = server.perform(
+        Request::options("/public")
+            .header(ORIGIN, "http://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(REQUEST_PRIVATE_NETWORK, "true"),
+    )?;
+    assert_eq!(response.status(), 204);
+    assert!(!response.headers().contains_key(ALLOW_PRIVATE_NETWORK));
+
+    Ok(())
+}
+
 #[test]
 fn as_route_modifier() -> tsukuyomi_server::Result<()> {
     let cors = CORS::new();
@@ -400,6 +566,74 @@ fn as_route_modifier() -> tsukuyomi_server::Result<()> {
     Ok(())
 }
 
+#[test]
+fn per_route_policies() -> tsukuyomi_server::Result<()> {
+    let public = CORS::new();
+    let private = CORS::builder()
+        .allow_origin("http://example.com")?
+        .allow_credentials(true)
+        .build();
+
+    let app = App::create(chain![
+        path!("/public") //
+            .to(endpoint::get().call(|| "public"))
+            .modify(public),
+        path!("/private") //
+            .to(endpoint::allow_only("GET, PUT")?.call(|| "private"))
+            .modify(private),
+    ])?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(
+        Request::get("/public") //
+            .header(ORIGIN, "http://example.org"),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header(ACCESS_CONTROL_ALLOW_ORIGIN)?, "*");
+    assert!(!response
+        .headers()
+        .contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+    let response = server.perform(
+        Request::get("/private") //
+            .header(ORIGIN, "http://example.org"),
+    )?;
+    assert_eq!(response.status(), 403);
+
+    let response = server.perform(
+        Request::put("/private") //
+            .header(ORIGIN, "http://example.com"),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_ORIGIN)?,
+        "http://example.com"
+    );
+    assert_eq!(response.header(ACCESS_CONTROL_ALLOW_CREDENTIALS)?, "true");
+
+    // the preflight responses are computed from the methods of the target route.
+    let response = server.perform(
+        Request::options("/private")
+            .header(ORIGIN, "http://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT"),
+    )?;
+    assert_eq!(response.status(), 204);
+    assert_methods!(
+        response.header(ACCESS_CONTROL_ALLOW_METHODS)?,
+        [GET, PUT, OPTIONS]
+    );
+    assert_eq!(response.header(ACCESS_CONTROL_ALLOW_CREDENTIALS)?, "true");
+
+    let response = server.perform(
+        Request::options("/public")
+            .header(ORIGIN, "http://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT"),
+    )?;
+    assert_eq!(response.status(), 403);
+
+    Ok(())
+}
+
 #[test]
 fn as_scope_modifier() -> tsukuyomi_server::Result<()> {
     let cors = CORS::new();
@@ -0,0 +1,77 @@
+//! Minimal parsers for finding the token field in the form data.
+
+use url::form_urlencoded;
+
+/// Finds the value of the specified field in an `application/x-www-form-urlencoded` body.
+pub(crate) fn urlencoded(body: &[u8], name: &str) -> Option<String> {
+    form_urlencoded::parse(body)
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Finds the value of the specified field in a `multipart/form-data` body.
+///
+/// Only the parts without `filename` are considered, and the value must be a valid UTF-8.
+pub(crate) fn multipart(body: &[u8], boundary: &str, name: &str) -> Option<String> {
+    let delimiter = format!("\r\n--{}", boundary);
+
+    // The first delimiter is not necessarily preceded by CRLF.
+    let mut rest = &body[find(body, &delimiter.as_bytes()[2..])? + delimiter.len() - 2..];
+    loop {
+        // the closing delimiter is followed by "--".
+        if rest.starts_with(b"--") {
+            return None;
+        }
+        let end = find(rest, delimiter.as_bytes())?;
+        let (part, next) = (&rest[..end], &rest[end + delimiter.len()..]);
+        rest = next;
+
+        if let Some(value) = part_value(part, name) {
+            return Some(value);
+        }
+    }
+}
+
+fn part_value(part: &[u8], name: &str) -> Option<String> {
+    if !part.starts_with(b"\r\n") {
+        return None;
+    }
+    let part = &part[2..];
+    let pos = find(part, b"\r\n\r\n")?;
+    let (headers, content) = (&part[..pos], &part[pos + 4..]);
+
+    let headers = std::str::from_utf8(headers).ok()?;
+    let disposition = headers.split("\r\n").find(|line| {
+        line.get(..20)
+            .map_or(false, |s| s.eq_ignore_ascii_case("content-disposition:"))
+    })?;
+    if header_param(disposition, "name")? != name || header_param(disposition, "filename").is_some()
+    {
+        return None;
+    }
+
+    std::str::from_utf8(content).ok().map(ToOwned::to_owned)
+}
+
+/// Extracts the boundary from the value of `Content-Type`.
+pub(crate) fn boundary(content_type: &str) -> Option<&str> {
+    header_param(content_type, "boundary")
+}
+
+fn header_param<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value.split(';').skip(1).find_map(|param| {
+        let mut kv = param.trim().splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(key), Some(value)) if key.eq_ignore_ascii_case(name) => {
+                Some(value.trim_matches('"'))
+            }
+            _ => None,
+        }
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,446 @@
+//! CSRF protection for Tsukuyomi.
+//!
+//! The modifier `Csrf` issues a token for each client and stores it in a `Store`.
+//! The requests with unsafe methods (i.e. other than `GET`, `HEAD`, `OPTIONS`
+//! and `TRACE`) must submit the same token through the header field `X-CSRF-Token`
+//! or the form field `csrf_token`, otherwise they are rejected with `403 Forbidden`.
+//!
+//! ```ignore
+//! use tsukuyomi_csrf::{store::SessionStore, Csrf, CsrfToken};
+//!
+//! let csrf = Csrf::new(SessionStore::new(session_backend.clone()));
+//!
+//! let app = App::create(
+//!     path!("/form")
+//!         .to(chain![
+//!             endpoint::get()
+//!                 .extract(tsukuyomi_csrf::token())
+//!                 .call(|token: CsrfToken| FormTemplate { csrf_token: token }),
+//!             endpoint::post()
+//!                 .extract(extractor::body::urlencoded())
+//!                 .call(|form: Form| ...),
+//!         ])
+//!         .modify(csrf),
+//! )?;
+//! ```
+//!
+//! Since the token is written to the form data, the value of `CsrfToken` should
+//! be rendered in the template as a hidden field:
+//!
+//! ```html
+//! <input type="hidden" name="csrf_token" value="{{ csrf_token }}">
+//! ```
+
+#![doc(html_root_url = "https://docs.rs/tsukuyomi-csrf/0.1.0")]
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    rust_2018_compatibility,
+    unused
+)]
+#![forbid(clippy::unimplemented)]
+
+mod form;
+pub mod store;
+
+use {
+    crate::store::Store,
+    futures::{stream, Async, Stream},
+    http::{
+        header::{HeaderName, CONTENT_TYPE},
+        Method, StatusCode,
+    },
+    rand::Rng,
+    std::{borrow::Cow, fmt, sync::Arc},
+    tsukuyomi::{
+        error::Error,
+        extractor::Extractor,
+        future::{Poll, TryFuture},
+        handler::{AllowedMethods, Handler, ModifyHandler},
+        input::{
+            body::RequestBody,
+            localmap::{local_key, LocalData},
+            Input,
+        },
+    },
+};
+
+/// Generates a new random token.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill(&mut bytes);
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// The CSRF token issued for the current client.
+///
+/// The value is available after the modifier `Csrf` is applied.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// Returns the string representation of this token.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CsrfToken").field(&"[REDACTED]").finish()
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl LocalData for CsrfToken {
+    local_key! {
+        /// The local key to manage the CSRF token of the current client.
+        const KEY: Self;
+    }
+}
+
+/// Creates an `Extractor` that returns the CSRF token of the current client.
+///
+/// The extraction fails with `500 Internal Server Error` if the modifier `Csrf`
+/// is not applied to the handler.
+pub fn token() -> impl Extractor<
+    Output = (CsrfToken,), //
+    Error = Error,
+    Extract = impl TryFuture<Ok = (CsrfToken,), Error = Error> + Send + 'static,
+> {
+    tsukuyomi::extractor::local::clone(&CsrfToken::KEY)
+}
+
+/// A `ModifyHandler` that protects the handlers from CSRF attacks.
+#[derive(Debug)]
+pub struct Csrf<S> {
+    inner: Arc<Config<S>>,
+}
+
+#[derive(Debug)]
+struct Config<S> {
+    store: S,
+    header_name: HeaderName,
+    field_name: Cow<'static, str>,
+    body_limit: usize,
+}
+
+impl<S> Clone for Csrf<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> Csrf<S>
+where
+    S: Store,
+{
+    /// Creates a `Csrf` that stores the tokens into the specified `Store`.
+    pub fn new(store: S) -> Self {
+        Self {
+            inner: Arc::new(Config {
+                store,
+                header_name: HeaderName::from_static("x-csrf-token"),
+                field_name: "csrf_token".into(),
+                body_limit: 1024 * 1024,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Config<S> {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the name of header field used for submitting the token.
+    ///
+    /// The default value is `X-CSRF-Token`.
+    pub fn header_name(mut self, name: HeaderName) -> Self {
+        self.inner_mut().header_name = name;
+        self
+    }
+
+    /// Sets the name of form field used for submitting the token.
+    ///
+    /// The default value is `"csrf_token"`.
+    pub fn field_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.inner_mut().field_name = name.into();
+        self
+    }
+
+    /// Sets the maximum number of bytes of the request body buffered for finding the form field.
+    ///
+    /// The `application/x-www-form-urlencoded` bodies larger than this limit are
+    /// rejected with `413 Payload Too Large`.  The `multipart/form-data` bodies are
+    /// read only until the token field is found, so the token field must appear
+    /// within this limit (i.e. it should be placed at the beginning of the form).
+    ///
+    /// The default value is 1 MiB.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.inner_mut().body_limit = limit;
+        self
+    }
+}
+
+impl<S, H> ModifyHandler<H> for Csrf<S>
+where
+    S: Store,
+    H: Handler,
+{
+    type Output = H::Output;
+    type Handler = CsrfHandler<S, H>; // private
+
+    fn modify(&self, inner: H) -> Self::Handler {
+        CsrfHandler {
+            inner,
+            config: self.inner.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct CsrfHandler<S, H> {
+    inner: H,
+    config: Arc<Config<S>>,
+}
+
+impl<S, H> Handler for CsrfHandler<S, H>
+where
+    S: Store,
+    H: Handler,
+{
+    type Output = H::Output;
+    type Error = Error;
+    type Handle = HandleCsrf<S, H::Handle>;
+
+    fn handle(&self) -> Self::Handle {
+        HandleCsrf {
+            state: State::Token(self.config.store.token()),
+            inner: self.inner.handle(),
+            config: self.config.clone(),
+        }
+    }
+
+    fn allowed_methods(&self) -> Option<&AllowedMethods> {
+        self.inner.allowed_methods()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HandleCsrf<S: Store, H: TryFuture> {
+    state: State<S::Token, S::Finish, H::Ok>,
+    inner: H,
+    config: Arc<Config<S>>,
+}
+
+enum State<T, F, O> {
+    Token(T),
+    ReadBody(ReadBody, String),
+    Handle,
+    Finish(F, Option<O>),
+}
+
+struct ReadBody {
+    body: RequestBody,
+    buf: Vec<u8>,
+    form: Form,
+}
+
+#[derive(Clone, Copy)]
+enum Form {
+    UrlEncoded,
+    Multipart,
+}
+
+impl<S, H> TryFuture for HandleCsrf<S, H>
+where
+    S: Store,
+    H: TryFuture,
+{
+    type Ok = H::Ok;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        loop {
+            self.state = match self.state {
+                State::Token(ref mut token) => {
+                    let token = match token.poll_ready(input) {
+                        Ok(Async::Ready(token)) => token,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(err) => return Err(err.into()),
+                    };
+                    input
+                        .locals
+                        .insert(&CsrfToken::KEY, CsrfToken(token.clone()));
+
+                    if is_safe(input.request.method()) {
+                        State::Handle
+                    } else if let Some(submitted) =
+                        input.request.headers().get(&self.config.header_name)
+                    {
+                        verify(&token, submitted.as_bytes())?;
+                        State::Handle
+                    } else {
+                        let form = match form_kind(input) {
+                            Some(form) => form,
+                            None => return Err(forbidden()),
+                        };
+                        let body = RequestBody::take_from(input.locals).ok_or_else(|| {
+                            tsukuyomi::error::internal_server_error(
+                                "the request body has already been stolen by someone",
+                            )
+                        })?;
+                        State::ReadBody(
+                            ReadBody {
+                                body,
+                                buf: vec![],
+                                form,
+                            },
+                            token,
+                        )
+                    }
+                }
+
+                State::ReadBody(ref mut read_body, ref token) => {
+                    let submitted = match read_body.poll_field(input, &self.config)? {
+                        Async::Ready(submitted) => submitted,
+                        Async::NotReady => return Ok(Async::NotReady),
+                    };
+                    verify(token, submitted.as_ref().map_or(&b""[..], |s| s.as_bytes()))?;
+                    State::Handle
+                }
+
+                State::Handle => {
+                    let output = match self.inner.poll_ready(input) {
+                        Ok(Async::Ready(output)) => output,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(err) => return Err(err.into()),
+                    };
+                    State::Finish(self.config.store.finish(), Some(output))
+                }
+
+                State::Finish(ref mut finish, ref mut output) => {
+                    match finish.poll_ready(input) {
+                        Ok(Async::Ready(())) => {}
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(err) => return Err(err.into()),
+                    }
+                    let output = output.take().expect("the future has already been polled");
+                    return Ok(Async::Ready(output));
+                }
+            };
+        }
+    }
+}
+
+impl ReadBody {
+    /// Reads the request body until the token field is found, and restores it for
+    /// the subsequent extractors.
+    fn poll_field<S>(
+        &mut self,
+        input: &mut Input<'_>,
+        config: &Config<S>,
+    ) -> Poll<Option<String>, Error> {
+        loop {
+            let chunk = match self.body.poll() {
+                Ok(Async::Ready(chunk)) => chunk,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => return Err(tsukuyomi::error::bad_request(err)),
+            };
+
+            let chunk = match chunk {
+                Some(chunk) => chunk,
+                None => {
+                    let submitted = self.find_field(input, config);
+                    let buf = std::mem::replace(&mut self.buf, vec![]);
+                    input
+                        .locals
+                        .insert(&RequestBody::KEY, RequestBody::from(hyper::Body::from(buf)));
+                    return Ok(Async::Ready(submitted));
+                }
+            };
+            self.buf.extend_from_slice(&chunk);
+
+            if let Form::Multipart = self.form {
+                if let Some(submitted) = self.find_field(input, config) {
+                    // the rest of body is left to the subsequent extractors without reading.
+                    let buf = std::mem::replace(&mut self.buf, vec![]);
+                    let body = std::mem::replace(&mut self.body, hyper::Body::empty().into());
+                    let restored = stream::once(Ok(hyper::Chunk::from(buf))).chain(body);
+                    input.locals.insert(
+                        &RequestBody::KEY,
+                        RequestBody::from(hyper::Body::wrap_stream(restored)),
+                    );
+                    return Ok(Async::Ready(Some(submitted)));
+                }
+            }
+
+            if self.buf.len() > config.body_limit {
+                return Err(tsukuyomi::error::custom(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "the request body is too large to find the CSRF token",
+                ));
+            }
+        }
+    }
+
+    fn find_field<S>(&self, input: &Input<'_>, config: &Config<S>) -> Option<String> {
+        match self.form {
+            Form::UrlEncoded => form::urlencoded(&self.buf, &config.field_name),
+            Form::Multipart => input
+                .request
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(form::boundary)
+                .and_then(|boundary| form::multipart(&self.buf, boundary, &config.field_name)),
+        }
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE => true,
+        _ => false,
+    }
+}
+
+fn form_kind(input: &Input<'_>) -> Option<Form> {
+    let content_type = input.request.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let essence = content_type.split(';').next()?.trim();
+    if essence.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+        Some(Form::UrlEncoded)
+    } else if essence.eq_ignore_ascii_case("multipart/form-data") {
+        Some(Form::Multipart)
+    } else {
+        None
+    }
+}
+
+/// Compares the submitted token with the expected one in constant time.
+fn verify(expected: &str, submitted: &[u8]) -> tsukuyomi::Result<()> {
+    let expected = expected.as_bytes();
+    let matched = expected.len() == submitted.len()
+        && expected
+            .iter()
+            .zip(submitted)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if matched {
+        Ok(())
+    } else {
+        Err(forbidden())
+    }
+}
+
+fn forbidden() -> Error {
+    tsukuyomi::error::custom(StatusCode::FORBIDDEN, "invalid CSRF token")
+}
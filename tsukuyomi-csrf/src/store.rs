@@ -0,0 +1,336 @@
+//! The storages of CSRF tokens.
+
+use {
+    std::borrow::Cow,
+    tsukuyomi::{
+        error::Error,
+        future::{Async, Poll, TryFuture},
+        input::Input,
+    },
+    tsukuyomi_session::{Backend, RawSession},
+};
+
+#[cfg(feature = "secure")]
+pub use self::signed::CookieStore;
+
+/// A trait representing the storage of CSRF tokens associated with each client.
+pub trait Store {
+    /// The type of errors which will occur when polling `Token`.
+    type Error: Into<Error>;
+    /// The type of `TryFuture` that will return the token.
+    type Token: TryFuture<Ok = String, Error = Self::Error>;
+    /// The type of `TryFuture` that will complete the modification of the storage.
+    type Finish: TryFuture<Ok = (), Error = Self::Error>;
+
+    /// Creates a `TryFuture` that returns the token associated with the current client.
+    ///
+    /// If the client does not have any token, a new token is generated by
+    /// `generate_token` and stored before returning it.
+    fn token(&self) -> Self::Token;
+
+    /// Creates a `TryFuture` that completes the modification of the storage,
+    /// called after the inner handler has been completed successfully.
+    fn finish(&self) -> Self::Finish;
+}
+
+impl<S> Store for Box<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Token = S::Token;
+    type Finish = S::Finish;
+
+    #[inline]
+    fn token(&self) -> Self::Token {
+        (**self).token()
+    }
+
+    #[inline]
+    fn finish(&self) -> Self::Finish {
+        (**self).finish()
+    }
+}
+
+impl<S> Store for std::rc::Rc<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Token = S::Token;
+    type Finish = S::Finish;
+
+    #[inline]
+    fn token(&self) -> Self::Token {
+        (**self).token()
+    }
+
+    #[inline]
+    fn finish(&self) -> Self::Finish {
+        (**self).finish()
+    }
+}
+
+impl<S> Store for std::sync::Arc<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Token = S::Token;
+    type Finish = S::Finish;
+
+    #[inline]
+    fn token(&self) -> Self::Token {
+        (**self).token()
+    }
+
+    #[inline]
+    fn finish(&self) -> Self::Finish {
+        (**self).finish()
+    }
+}
+
+/// A `Store` that saves the token in the session.
+///
+/// The session read by this store is shared with the extractor `session` of
+/// the inner handler, so the handler can read and modify the same session as
+/// usual.  If the handler does not extract the session, it is written back
+/// after the handler has been completed.
+#[derive(Debug, Clone)]
+pub struct SessionStore<B> {
+    backend: B,
+    key: Cow<'static, str>,
+}
+
+impl<B> SessionStore<B>
+where
+    B: Backend,
+{
+    /// Creates a `SessionStore` using the specified session backend.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            key: "csrf-token".into(),
+        }
+    }
+
+    /// Sets the field name of the session where the token is stored.
+    ///
+    /// The default value is `"csrf-token"`.
+    pub fn key(self, key: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            key: key.into(),
+            ..self
+        }
+    }
+}
+
+impl<B> Store for SessionStore<B>
+where
+    B: Backend,
+    B::Session: Send + 'static,
+{
+    type Error = Error;
+    type Token = SessionToken<B>;
+    type Finish = SessionFinish<B>;
+
+    fn token(&self) -> Self::Token {
+        SessionToken {
+            read: self.backend.read(),
+            key: self.key.clone(),
+        }
+    }
+
+    fn finish(&self) -> Self::Finish {
+        SessionFinish { write: None }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SessionToken<B: Backend> {
+    read: B::ReadSession,
+    key: Cow<'static, str>,
+}
+
+impl<B> TryFuture for SessionToken<B>
+where
+    B: Backend,
+    B::Session: Send + 'static,
+{
+    type Ok = String;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        let mut session = match tsukuyomi_session::take_shared::<B::Session>(input) {
+            Some(session) => session,
+            None => match self.read.poll_ready(input) {
+                Ok(Async::Ready(session)) => session,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => return Err(err.into()),
+            },
+        };
+
+        let stored = session
+            .get(&*self.key)
+            .and_then(|value| serde_json::from_str::<String>(value).ok());
+        let token = match stored {
+            Some(token) => token,
+            None => {
+                let token = crate::generate_token();
+                let value = serde_json::to_string(&token)
+                    .map_err(tsukuyomi::error::internal_server_error)?;
+                session.set(&*self.key, value);
+                token
+            }
+        };
+
+        // the session is written by the handler or `SessionFinish`.
+        tsukuyomi_session::share(input, session);
+        Ok(Async::Ready(token))
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SessionFinish<B: Backend> {
+    write: Option<<B::Session as RawSession>::WriteSession>,
+}
+
+impl<B> TryFuture for SessionFinish<B>
+where
+    B: Backend,
+    B::Session: Send + 'static,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        if self.write.is_none() {
+            match tsukuyomi_session::take_shared::<B::Session>(input) {
+                Some(session) => self.write = Some(session.write()),
+                // the session has been taken by the handler.
+                None => return Ok(Async::Ready(())),
+            }
+        }
+        let write = self
+            .write
+            .as_mut()
+            .expect("the future has already been polled");
+        write.poll_ready(input).map_err(Into::into)
+    }
+}
+
+#[cfg(feature = "secure")]
+mod signed {
+    use {
+        super::Store,
+        cookie::{Cookie, Key, SameSite},
+        futures::future::{self, FutureResult},
+        std::{borrow::Cow, sync::Arc},
+        tsukuyomi::{
+            error::Error,
+            future::{Async, Compat01, Poll, TryFuture},
+            input::Input,
+        },
+    };
+
+    /// A `Store` that saves the token in a signed Cookie entry.
+    #[derive(Debug, Clone)]
+    pub struct CookieStore {
+        inner: Arc<CookieStoreInner>,
+    }
+
+    struct CookieStoreInner {
+        key: Key,
+        cookie_name: Cow<'static, str>,
+        secure: bool,
+    }
+
+    #[cfg_attr(tarpaulin, skip)]
+    impl std::fmt::Debug for CookieStoreInner {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CookieStoreInner")
+                .field("key", &"<secret key>")
+                .field("cookie_name", &self.cookie_name)
+                .field("secure", &self.secure)
+                .finish()
+        }
+    }
+
+    impl CookieStore {
+        /// Creates a `CookieStore` that signs the Cookie entry with the specified `Key`.
+        pub fn signed(key: Key) -> Self {
+            Self {
+                inner: Arc::new(CookieStoreInner {
+                    key,
+                    cookie_name: "csrf-token".into(),
+                    secure: true,
+                }),
+            }
+        }
+
+        fn inner_mut(&mut self) -> &mut CookieStoreInner {
+            Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+        }
+
+        /// Sets the name of Cookie entry for storing the token.
+        ///
+        /// The default value is `"csrf-token"`.
+        pub fn cookie_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+            self.inner_mut().cookie_name = name.into();
+            self
+        }
+
+        /// Sets whether to add the `Secure` attribute to the Cookie entry.
+        ///
+        /// The default value is `true`.
+        pub fn secure(mut self, secure: bool) -> Self {
+            self.inner_mut().secure = secure;
+            self
+        }
+    }
+
+    impl Store for CookieStore {
+        type Error = Error;
+        type Token = CookieToken;
+        type Finish = Compat01<FutureResult<(), Error>>;
+
+        fn token(&self) -> Self::Token {
+            CookieToken {
+                inner: self.inner.clone(),
+            }
+        }
+
+        fn finish(&self) -> Self::Finish {
+            // the Cookie entry has already been added by `CookieToken`.
+            future::ok(()).into()
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct CookieToken {
+        inner: Arc<CookieStoreInner>,
+    }
+
+    impl TryFuture for CookieToken {
+        type Ok = String;
+        type Error = Error;
+
+        fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+            let mut jar = input.cookies.signed_jar(&self.inner.key)?;
+            if let Some(cookie) = jar.get(&*self.inner.cookie_name) {
+                return Ok(Async::Ready(cookie.value().to_owned()));
+            }
+
+            let token = crate::generate_token();
+            jar.add(
+                Cookie::build(self.inner.cookie_name.clone(), token.clone())
+                    .path("/")
+                    .http_only(true)
+                    .same_site(SameSite::Strict)
+                    .secure(self.inner.secure)
+                    .finish(),
+            );
+            Ok(Async::Ready(token))
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use {
+    http::Request,
+    tsukuyomi::{config::prelude::*, extractor, App},
+    tsukuyomi_csrf::{
+        store::{CookieStore, SessionStore},
+        Csrf, CsrfToken,
+    },
+    tsukuyomi_session::{
+        backend::{CookieBackend, MemoryBackend},
+        session, Session,
+    },
+};
+
+#[test]
+fn test_version_sync() {
+    version_sync::assert_html_root_url_updated!("src/lib.rs");
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Form {
+    message: String,
+}
+
+#[test]
+fn test_session_store() -> tsukuyomi_server::Result<()> {
+    let backend = CookieBackend::plain().cookie_name("session");
+
+    let app = App::create(
+        path!("/") //
+            .to(chain![
+                endpoint::get()
+                    .extract(tsukuyomi_csrf::token())
+                    .call(|token: CsrfToken| token.to_string()),
+                endpoint::post()
+                    .extract(extractor::body::urlencoded())
+                    .call(|form: Form| form.message),
+                endpoint::put().extract(session(backend.clone())).call(
+                    |mut session: Session<_>| {
+                        session.set("visited", true).unwrap();
+                        session.finish("updated")
+                    }
+                ),
+            ])
+            .modify(Csrf::new(SessionStore::new(backend))),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    let response = client.perform("/")?;
+    assert_eq!(response.status(), 200);
+    let token = response.body().to_utf8()?.into_owned();
+
+    // the token is not changed during the session.
+    let response = client.perform("/")?;
+    assert_eq!(response.body().to_utf8()?, token);
+
+    let response = client.perform(
+        Request::post("/")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(&b"message=hello"[..]),
+    )?;
+    assert_eq!(response.status(), 403);
+
+    let response = client.perform(
+        Request::post("/")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(format!("message=hello&csrf_token={}", token).into_bytes()),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "hello");
+
+    // the token stored by the modifier is kept after the handler modifies the session.
+    let response = client.perform(Request::put("/").header("x-csrf-token", &*token))?;
+    assert_eq!(response.status(), 200);
+    let response = client.perform("/")?;
+    assert_eq!(response.body().to_utf8()?, token);
+
+    Ok(())
+}
+
+#[test]
+fn test_session_store_shares_session() -> tsukuyomi_server::Result<()> {
+    let backend = MemoryBackend::new();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get()
+                .extract(tsukuyomi_csrf::token())
+                .extract(session(backend.clone()))
+                .call(|token: CsrfToken, mut session: Session<_>| {
+                    let visits = session.get::<u32>("visits").unwrap().unwrap_or(0) + 1;
+                    session.set("visits", visits).unwrap();
+                    session.finish(format!("{}:{}", token, visits))
+                }))
+            .modify(Csrf::new(SessionStore::new(backend.clone()))),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    // the first visit starts only one session, shared by the modifier and the handler.
+    let response = client.perform("/")?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers().get_all("set-cookie").iter().count(), 1);
+    assert_eq!(backend.stats().sessions, 1);
+    let body = response.body().to_utf8()?.into_owned();
+    let token = body.trim_end_matches(":1");
+    assert_ne!(token, body);
+
+    let response = client.perform("/")?;
+    assert_eq!(response.body().to_utf8()?, format!("{}:2", token));
+    assert_eq!(backend.stats().sessions, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_body_limit() -> tsukuyomi_server::Result<()> {
+    let store = CookieStore::signed(cookie::Key::generate()).secure(false);
+
+    let app = App::create(
+        path!("/") //
+            .to(chain![
+                endpoint::get()
+                    .extract(tsukuyomi_csrf::token())
+                    .call(|token: CsrfToken| token.to_string()),
+                endpoint::post()
+                    .extract(extractor::body::read_all())
+                    .call(|body: bytes::Bytes| body.len().to_string()),
+            ])
+            .modify(Csrf::new(store).body_limit(128)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    let response = client.perform("/")?;
+    let token = response.body().to_utf8()?.into_owned();
+    let padding = "x".repeat(256);
+
+    let response = client.perform(
+        Request::post("/")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(format!("csrf_token={}&message={}", token, padding).into_bytes()),
+    )?;
+    assert_eq!(response.status(), 413);
+
+    // the multipart body is read only until the token field.
+    let body = format!(
+        "--boundary\r\n\
+         Content-Disposition: form-data; name=\"csrf_token\"\r\n\
+         \r\n\
+         {}\r\n\
+         --boundary\r\n\
+         Content-Disposition: form-data; name=\"message\"\r\n\
+         \r\n\
+         {}\r\n\
+         --boundary--\r\n",
+        token, padding
+    );
+    let response = client.perform(
+        Request::post("/")
+            .header("content-type", "multipart/form-data; boundary=boundary")
+            .body(body.clone().into_bytes()),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, body.len().to_string());
+
+    Ok(())
+}
+
+#[test]
+fn test_cookie_store() -> tsukuyomi_server::Result<()> {
+    let store = CookieStore::signed(cookie::Key::generate()).secure(false);
+
+    let app = App::create(
+        path!("/") //
+            .to(chain![
+                endpoint::get()
+                    .extract(tsukuyomi_csrf::token())
+                    .call(|token: CsrfToken| token.to_string()),
+                endpoint::post()
+                    .extract(extractor::body::read_all())
+                    .call(|body: bytes::Bytes| body.len().to_string()),
+            ])
+            .modify(Csrf::new(store)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    let response = client.perform("/")?;
+    assert_eq!(response.status(), 200);
+    assert!(client.cookie("csrf-token").is_some());
+    let token = response.body().to_utf8()?.into_owned();
+
+    let body = format!(
+        "--boundary\r\n\
+         Content-Disposition: form-data; name=\"message\"\r\n\
+         \r\n\
+         hello\r\n\
+         --boundary\r\n\
+         Content-Disposition: form-data; name=\"csrf_token\"\r\n\
+         \r\n\
+         {}\r\n\
+         --boundary--\r\n",
+        token
+    );
+    let response = client.perform(
+        Request::post("/")
+            .header("content-type", "multipart/form-data; boundary=boundary")
+            .body(body.clone().into_bytes()),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, body.len().to_string());
+
+    let response = client.perform(Request::post("/").header("x-csrf-token", "invalid"))?;
+    assert_eq!(response.status(), 403);
+
+    // the token is bound to the client.
+    let response = server.perform(Request::post("/").header("x-csrf-token", &*token))?;
+    assert_eq!(response.status(), 403);
+
+    Ok(())
+}
@@ -0,0 +1,462 @@
+//! A lightweight analysis of GraphQL documents for limiting the cost of queries.
+//!
+//! The analysis is performed on the token stream without building the AST,
+//! and the documents which cannot be analyzed are rejected before the execution.
+
+use {
+    crate::lexer::{tokenize, Token},
+    std::collections::HashMap,
+};
+
+/// The metrics of an operation in a GraphQL document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Metrics {
+    /// The maximum nesting level of fields, e.g. `{ a { b } }` has the depth 2.
+    pub(crate) depth: usize,
+    /// The number of fields to be resolved, with the fragment spreads expanded.
+    pub(crate) complexity: usize,
+}
+
+/// The maximum number of fragment definitions in a document.
+const MAX_FRAGMENTS: usize = 256;
+
+/// The maximum length of a chain of fragment spreads, e.g. `{ ...A }` with
+/// `fragment A on Query { ...B }` has a chain of length 2.
+const MAX_FRAGMENT_NESTING: usize = 32;
+
+/// Calculates the metrics of the operation to be executed.
+///
+/// If `operation_name` is not specified, the maximum values of all operations
+/// in the document are returned.  The return value is `None` if the document
+/// is not well-formed, or it contains more than `MAX_FRAGMENTS` fragments or
+/// a chain of fragment spreads longer than `MAX_FRAGMENT_NESTING`.
+///
+/// The selection sets whose fields are nested deeper than `max_depth` are skipped
+/// without being analyzed.  In that case, the reported depth exceeds `max_depth`
+/// but the complexity does not include the skipped fields.
+pub(crate) fn analyze(
+    document: &str,
+    operation_name: Option<&str>,
+    max_depth: usize,
+) -> Option<Metrics> {
+    let tokens = tokenize(document)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        max_depth,
+    };
+
+    let mut operations = vec![];
+    let mut fragments = HashMap::new();
+    while let Some(token) = parser.peek() {
+        match token {
+            Token::Punct(b'{') => {
+                parser.bump();
+                let mut definition = Definition::default();
+                parser.selection_set(0, &mut definition)?;
+                operations.push((None, definition));
+            }
+            Token::Name("query") | Token::Name("mutation") | Token::Name("subscription") => {
+                parser.bump();
+                let name = match parser.peek() {
+                    Some(Token::Name(name)) => {
+                        parser.bump();
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                parser.skip_until_selection_set()?;
+                let mut definition = Definition::default();
+                parser.selection_set(0, &mut definition)?;
+                operations.push((name, definition));
+            }
+            Token::Name("fragment") => {
+                parser.bump();
+                let name = parser.name()?;
+                parser.skip_until_selection_set()?;
+                let mut definition = Definition::default();
+                parser.selection_set(0, &mut definition)?;
+                fragments.insert(name, definition);
+                if fragments.len() > MAX_FRAGMENTS {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    let mut resolver = Resolver {
+        fragments: &fragments,
+        resolved: HashMap::new(),
+    };
+    let mut metrics = Metrics::default();
+    for &(name, ref definition) in &operations {
+        if operation_name.map_or(true, |n| name == Some(n)) {
+            let resolved = resolver.resolve(definition)?;
+            metrics.depth = metrics.depth.max(resolved.depth);
+            metrics.complexity = metrics.complexity.max(resolved.complexity);
+        }
+    }
+    Some(metrics)
+}
+
+#[derive(Debug, Default)]
+struct Definition<'a> {
+    depth: usize,
+    complexity: usize,
+    // the fragment spreads in this definition, with the number of enclosing fields.
+    spreads: Vec<(usize, &'a str)>,
+}
+
+struct Parser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    max_depth: usize,
+}
+
+impl<'t, 'a> Parser<'t, 'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        match self.peek()? {
+            Token::Name(name) => {
+                self.bump();
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Skips the tokens until the opening brace of the selection set, and consumes it.
+    fn skip_until_selection_set(&mut self) -> Option<()> {
+        loop {
+            match self.peek()? {
+                Token::Punct(b'{') => {
+                    self.bump();
+                    return Some(());
+                }
+                Token::Punct(b'(') => self.skip_parens()?,
+                _ => self.bump(),
+            }
+        }
+    }
+
+    fn skip_parens(&mut self) -> Option<()> {
+        let mut level = 0usize;
+        loop {
+            match self.peek()? {
+                Token::Punct(b'(') => level += 1,
+                Token::Punct(b')') => {
+                    level -= 1;
+                    if level == 0 {
+                        self.bump();
+                        return Some(());
+                    }
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn skip_directives(&mut self) -> Option<()> {
+        while let Some(Token::Punct(b'@')) = self.peek() {
+            self.bump();
+            self.name()?;
+            if let Some(Token::Punct(b'(')) = self.peek() {
+                self.skip_parens()?;
+            }
+        }
+        Some(())
+    }
+
+    /// Skips the tokens until the end of the selection set, after its opening brace.
+    fn skip_selection_set(&mut self) -> Option<()> {
+        let mut level = 1usize;
+        loop {
+            match self.peek()? {
+                Token::Punct(b'{') => level += 1,
+                Token::Punct(b'}') => {
+                    level -= 1;
+                    if level == 0 {
+                        self.bump();
+                        return Some(());
+                    }
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    /// Parses the selection set after the opening brace, enclosed by `level` fields.
+    ///
+    /// The nested selection sets are tracked by a stack instead of the recursion, and the
+    /// ones whose fields exceed the maximum depth are skipped without being analyzed,
+    /// so that the deeply nested documents do not overflow the stack.
+    fn selection_set(&mut self, level: usize, definition: &mut Definition<'a>) -> Option<()> {
+        // the levels of the enclosing selection sets, including the inline fragments.
+        let mut levels = vec![level];
+        while let Some(&level) = levels.last() {
+            match self.peek()? {
+                Token::Punct(b'}') => {
+                    self.bump();
+                    levels.pop();
+                }
+                Token::Spread => {
+                    self.bump();
+                    match self.peek()? {
+                        Token::Name(name) if name != "on" => {
+                            self.bump();
+                            definition.spreads.push((level, name));
+                            self.skip_directives()?;
+                        }
+                        _ => {
+                            // inline fragment
+                            if let Some(Token::Name("on")) = self.peek() {
+                                self.bump();
+                                self.name()?;
+                            }
+                            self.skip_directives()?;
+                            match self.peek()? {
+                                Token::Punct(b'{') => self.bump(),
+                                _ => return None,
+                            }
+                            levels.push(level);
+                        }
+                    }
+                }
+                Token::Name(..) => {
+                    self.bump();
+                    definition.complexity = definition.complexity.saturating_add(1);
+                    definition.depth = definition.depth.max(level + 1);
+                    if let Some(Token::Punct(b':')) = self.peek() {
+                        self.bump();
+                        self.name()?;
+                    }
+                    if let Some(Token::Punct(b'(')) = self.peek() {
+                        self.skip_parens()?;
+                    }
+                    self.skip_directives()?;
+                    if let Some(Token::Punct(b'{')) = self.peek() {
+                        self.bump();
+                        if level + 1 >= self.max_depth {
+                            definition.depth = definition.depth.max(level + 2);
+                            self.skip_selection_set()?;
+                        } else {
+                            levels.push(level + 1);
+                        }
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+}
+
+struct Resolver<'d, 'a> {
+    fragments: &'d HashMap<&'a str, Definition<'a>>,
+    // memoized to avoid the exponential blowup by nested fragment spreads.
+    resolved: HashMap<&'a str, Metrics>,
+}
+
+/// A definition being resolved, which is the operation or a spread fragment.
+struct Frame<'r, 'a> {
+    name: Option<&'a str>,
+    // the number of fields enclosing the spread of this fragment.
+    level: usize,
+    definition: &'r Definition<'a>,
+    metrics: Metrics,
+    // the index of the next spread to be resolved.
+    next: usize,
+}
+
+impl<'r, 'a> Frame<'r, 'a> {
+    fn new(name: Option<&'a str>, level: usize, definition: &'r Definition<'a>) -> Self {
+        Frame {
+            name,
+            level,
+            definition,
+            metrics: Metrics {
+                depth: definition.depth,
+                complexity: definition.complexity,
+            },
+            next: 0,
+        }
+    }
+
+    fn add(&mut self, level: usize, fragment_metrics: Metrics) {
+        self.metrics.depth = self.metrics.depth.max(level + fragment_metrics.depth);
+        self.metrics.complexity = self
+            .metrics
+            .complexity
+            .saturating_add(fragment_metrics.complexity);
+    }
+}
+
+impl<'d, 'a> Resolver<'d, 'a> {
+    /// Calculates the metrics of the definition with the fragment spreads expanded.
+    ///
+    /// The spreads are resolved by a stack instead of the recursion, and the return
+    /// value is `None` if the chain of spreads is longer than `MAX_FRAGMENT_NESTING`.
+    fn resolve<'r>(&mut self, definition: &'r Definition<'a>) -> Option<Metrics>
+    where
+        'd: 'r,
+    {
+        let fragments: &'r HashMap<&'a str, Definition<'a>> = self.fragments;
+        let mut stack = vec![Frame::new(None, 0, definition)];
+        loop {
+            let spread = {
+                let frame = stack.last_mut().expect("the stack should not be empty");
+                let spread = frame.definition.spreads.get(frame.next).cloned();
+                frame.next += 1;
+                spread
+            };
+
+            let (level, name) = match spread {
+                Some(spread) => spread,
+                None => {
+                    let frame = stack.pop().expect("the stack should not be empty");
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            if let Some(name) = frame.name {
+                                self.resolved.insert(name, frame.metrics);
+                            }
+                            parent.add(frame.level, frame.metrics);
+                            continue;
+                        }
+                        None => return Some(frame.metrics),
+                    }
+                }
+            };
+
+            // the unknown or cyclic fragments are reported by the validation of Juniper.
+            let fragment = match fragments.get(name) {
+                Some(fragment) if stack.iter().all(|frame| frame.name != Some(name)) => fragment,
+                _ => continue,
+            };
+            if let Some(&resolved) = self.resolved.get(name) {
+                stack
+                    .last_mut()
+                    .expect("the stack should not be empty")
+                    .add(level, resolved);
+                continue;
+            }
+            if stack.len() > MAX_FRAGMENT_NESTING {
+                return None;
+            }
+            stack.push(Frame::new(Some(name), level, fragment));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, Metrics};
+
+    fn metrics(depth: usize, complexity: usize) -> Option<Metrics> {
+        Some(Metrics { depth, complexity })
+    }
+
+    #[test]
+    fn test_simple_query() {
+        assert_eq!(
+            analyze("{ hero { name } }", None, usize::max_value()),
+            metrics(2, 2)
+        );
+        assert_eq!(
+            analyze(
+                r#"query Hero($id: String = "}") {
+                    hero(id: $id, filter: { a: [1, 2] }) @include(if: true) {
+                        name
+                        friends { n: name }
+                    }
+                }"#,
+                None,
+                usize::max_value()
+            ),
+            metrics(3, 4)
+        );
+    }
+
+    #[test]
+    fn test_fragments() {
+        let document = r#"
+            query A { hero { ...F ... on Droid { primaryFunction } } }
+            query B { hero { name } }
+            fragment F on Character { name friends { ...G } }
+            fragment G on Character { name friends { name } }
+        "#;
+        assert_eq!(analyze(document, None, usize::max_value()), metrics(4, 7));
+        assert_eq!(
+            analyze(document, Some("B"), usize::max_value()),
+            metrics(2, 2)
+        );
+    }
+
+    #[test]
+    fn test_cyclic_fragments() {
+        let document = r#"
+            { ...A }
+            fragment A on Query { a { ...B } }
+            fragment B on Query { b { ...A } }
+        "#;
+        assert_eq!(analyze(document, None, usize::max_value()), metrics(2, 2));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let document = r#"
+            { a { b { ...F } } c { ... on Query { d { e } } } }
+            fragment F on Query { f { g } }
+        "#;
+        assert_eq!(analyze(document, None, 4), metrics(4, 7));
+        assert_eq!(analyze(document, None, 2), metrics(3, 4));
+        assert_eq!(analyze(document, None, 0), metrics(2, 2));
+
+        // the deeply nested selection sets are skipped without the recursion.
+        let n = 100_000;
+        let document = format!("{}{}", "{ a ".repeat(n), "}".repeat(n));
+        assert_eq!(analyze(&document, None, 10), metrics(11, 10));
+        let document = format!("{{ {}a{} }}", "... { ".repeat(n), " }".repeat(n));
+        assert_eq!(analyze(&document, None, 10), metrics(1, 1));
+        assert_eq!(analyze(&document[..document.len() - 1], None, 10), None);
+    }
+
+    #[test]
+    fn test_fragment_limits() {
+        fn chain(n: usize) -> String {
+            let mut document = "{ ...F0 }".to_owned();
+            for i in 0..n {
+                document += &format!(" fragment F{} on Query {{ a ...F{} }}", i, i + 1);
+            }
+            document += &format!(" fragment F{} on Query {{ a }}", n);
+            document
+        }
+
+        let max = usize::max_value();
+        assert_eq!(analyze(&chain(30), None, max), metrics(1, 31));
+        assert_eq!(analyze(&chain(31), None, max), metrics(1, 32));
+        assert_eq!(analyze(&chain(32), None, max), None);
+
+        // the long chains are rejected without overflowing the stack.
+        assert_eq!(analyze(&chain(200), None, max), None);
+        assert_eq!(analyze(&chain(100_000), None, max), None);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(analyze("{ hero { name }", None, usize::max_value()), None);
+        assert_eq!(
+            analyze("{ hero(id: \"1) { name } }", None, usize::max_value()),
+            None
+        );
+    }
+}
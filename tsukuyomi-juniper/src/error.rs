@@ -56,6 +56,129 @@ impl HttpError for GraphQLParseError {
     }
 }
 
+/// An error that rejects a GraphQL request before its execution.
+///
+/// The error is reported to the client as a GraphQL error with the status code
+/// `400 Bad Request`, and the code of each variant is set to `extensions.code`.
+#[derive(Debug)]
+pub enum GraphQLRequestError {
+    /// The request contains a persisted query, but the automatic persisted queries
+    /// are not enabled or its version is not supported (`PERSISTED_QUERY_NOT_SUPPORTED`).
+    PersistedQueryNotSupported,
+
+    /// The hash of the persisted query is not registered in the store
+    /// (`PERSISTED_QUERY_NOT_FOUND`).
+    ///
+    /// The client is expected to retry the request with the query.
+    PersistedQueryNotFound,
+
+    /// The hash of the persisted query does not match the query sent with it
+    /// (`PERSISTED_QUERY_HASH_MISMATCH`).
+    PersistedQueryHashMismatch,
+
+    /// The number of requests in a batch exceeds the limit (`BATCH_TOO_LARGE`).
+    BatchTooLarge {
+        /// The number of requests in the batch.
+        size: usize,
+        /// The maximum number of requests allowed.
+        max: usize,
+    },
+
+    /// The query document cannot be analyzed for checking its depth or complexity
+    /// (`GRAPHQL_PARSE_FAILED`).
+    ///
+    /// This error is also returned if the document contains more than 256 fragments,
+    /// or a chain of fragment spreads longer than 32.
+    MalformedQuery,
+
+    /// The nesting level of fields exceeds the limit (`QUERY_TOO_DEEP`).
+    TooDeep {
+        /// The depth of the query.
+        ///
+        /// If the query is nested much deeper than the limit, the analysis stops
+        /// at the limit and this value may be less than the actual depth.
+        depth: usize,
+        /// The maximum depth allowed.
+        max: usize,
+    },
+
+    /// The number of fields to be resolved exceeds the limit (`QUERY_TOO_COMPLEX`).
+    TooComplex {
+        /// The complexity of the query.
+        complexity: usize,
+        /// The maximum complexity allowed.
+        max: usize,
+    },
+}
+
+impl GraphQLRequestError {
+    fn code(&self) -> &'static str {
+        match self {
+            GraphQLRequestError::PersistedQueryNotSupported => "PERSISTED_QUERY_NOT_SUPPORTED",
+            GraphQLRequestError::PersistedQueryNotFound => "PERSISTED_QUERY_NOT_FOUND",
+            GraphQLRequestError::PersistedQueryHashMismatch => "PERSISTED_QUERY_HASH_MISMATCH",
+            GraphQLRequestError::BatchTooLarge { .. } => "BATCH_TOO_LARGE",
+            GraphQLRequestError::MalformedQuery => "GRAPHQL_PARSE_FAILED",
+            GraphQLRequestError::TooDeep { .. } => "QUERY_TOO_DEEP",
+            GraphQLRequestError::TooComplex { .. } => "QUERY_TOO_COMPLEX",
+        }
+    }
+}
+
+impl fmt::Display for GraphQLRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // the messages are compared by the clients of automatic persisted queries.
+            GraphQLRequestError::PersistedQueryNotSupported => {
+                f.write_str("PersistedQueryNotSupported")
+            }
+            GraphQLRequestError::PersistedQueryNotFound => f.write_str("PersistedQueryNotFound"),
+            GraphQLRequestError::PersistedQueryHashMismatch => {
+                f.write_str("provided sha256Hash does not match the query")
+            }
+            GraphQLRequestError::BatchTooLarge { size, max } => write!(
+                f,
+                "the batch size {} exceeds the maximum allowed size {}",
+                size, max
+            ),
+            GraphQLRequestError::MalformedQuery => f.write_str("the query document is malformed"),
+            GraphQLRequestError::TooDeep { depth, max } => write!(
+                f,
+                "the query depth {} exceeds the maximum allowed depth {}",
+                depth, max
+            ),
+            GraphQLRequestError::TooComplex { complexity, max } => write!(
+                f,
+                "the query complexity {} exceeds the maximum allowed complexity {}",
+                complexity, max
+            ),
+        }
+    }
+}
+
+impl HttpError for GraphQLRequestError {
+    type Body = String;
+
+    fn into_response(self, _: &Request<()>) -> Response<Self::Body> {
+        let body = json!({
+            "errors": [
+                {
+                    "message": self.to_string(),
+                    "extensions": {
+                        "code": self.code(),
+                    },
+                }
+            ],
+        })
+        .to_string();
+        Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("content-type", "application/json")
+            .body(body)
+            .expect("should be a valid response")
+    }
+}
+
 #[derive(Debug)]
 pub struct GraphQLError(Error);
 
@@ -150,7 +273,10 @@ where
     fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
         self.inner.poll_ready(input).map_err(|err| {
             let err = err.into();
-            if err.is::<GraphQLParseError>() || err.is::<GraphQLError>() {
+            if err.is::<GraphQLParseError>()
+                || err.is::<GraphQLRequestError>()
+                || err.is::<GraphQLError>()
+            {
                 err
             } else {
                 GraphQLError(err).into()
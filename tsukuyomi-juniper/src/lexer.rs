@@ -0,0 +1,99 @@
+//! A minimal lexer of GraphQL documents.
+//!
+//! The lexer is used for inspecting the documents without building the AST,
+//! as Juniper does not expose its parser.
+
+/// A token in a GraphQL document.
+///
+/// The values, such as strings and numbers, are not distinguished from each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Token<'a> {
+    Name(&'a str),
+    Punct(u8),
+    Spread,
+    Value,
+}
+
+/// Splits the document into tokens, skipping the whitespaces, commas and comments.
+///
+/// The return value is `None` if the document contains an invalid token.
+pub(crate) fn tokenize(document: &str) -> Option<Vec<Token<'_>>> {
+    let bytes = document.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                    i += 1;
+                }
+                continue;
+            }
+            b' ' | b'\t' | b'\n' | b'\r' | b',' => {}
+            b'"' if bytes[i..].starts_with(b"\"\"\"") => {
+                i += 3;
+                loop {
+                    if i >= bytes.len() {
+                        return None;
+                    }
+                    if bytes[i..].starts_with(b"\\\"\"\"") {
+                        i += 4;
+                    } else if bytes[i..].starts_with(b"\"\"\"") {
+                        i += 3;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                tokens.push(Token::Value);
+                continue;
+            }
+            b'"' => {
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        Some(b'"') => break,
+                        Some(b'\\') => i += 2,
+                        Some(b'\n') | Some(b'\r') | None => return None,
+                        Some(..) => i += 1,
+                    }
+                }
+                tokens.push(Token::Value);
+            }
+            b'.' => {
+                if !bytes[i..].starts_with(b"...") {
+                    return None;
+                }
+                tokens.push(Token::Spread);
+                i += 3;
+                continue;
+            }
+            b'-' | b'0'..=b'9' => {
+                i += 1;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || b"._+-".contains(&bytes[i]))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Value);
+                continue;
+            }
+            b if b == b'_' || b.is_ascii_alphabetic() => {
+                let start = i;
+                while i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push(Token::Name(&document[start..i]));
+                continue;
+            }
+            b if b"!$():=@[]{}|&".contains(&b) => tokens.push(Token::Punct(b)),
+            _ if document[i..].starts_with('\u{feff}') => {
+                i += '\u{feff}'.len_utf8();
+                continue;
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(tokens)
+}
@@ -11,16 +11,25 @@
 )]
 #![forbid(clippy::unimplemented)]
 
+mod analysis;
 mod error;
 mod graphiql;
+mod lexer;
+mod persisted_query;
 mod request;
+#[cfg(feature = "subscriptions")]
+mod subscription;
 
 pub use crate::{
-    error::{capture_errors, CaptureErrors},
+    error::{capture_errors, CaptureErrors, GraphQLRequestError},
     graphiql::graphiql_source,
-    request::{request, GraphQLRequest, GraphQLResponse},
+    persisted_query::{MemoryQueryStore, PersistedQueryStore},
+    request::{request, GraphQLRequest, GraphQLResponse, RequestConfig},
 };
 
+#[cfg(feature = "subscriptions")]
+pub use crate::subscription::{subscriptions, Operation, SubscriptionHandler, Subscriptions};
+
 use {
     juniper::{DefaultScalarValue, GraphQLType, RootNode, ScalarRefValue, ScalarValue},
     std::sync::Arc,
@@ -0,0 +1,124 @@
+use {
+    sha2::{Digest, Sha256},
+    std::{collections::HashMap, fmt::Write, sync::Mutex},
+};
+
+/// A trait representing the storage of persisted queries, keyed by their SHA-256 hashes.
+///
+/// The store is accessed on the blocking section of the executor, so the
+/// implementors may perform the blocking I/O operations.
+pub trait PersistedQueryStore: Send + Sync + 'static {
+    /// Returns the query associated with the specified hash, if any.
+    fn get(&self, hash: &str) -> Option<String>;
+
+    /// Registers the query with the specified hash.
+    ///
+    /// The hash has already been verified against the query.
+    fn insert(&self, hash: &str, query: &str);
+}
+
+/// A `PersistedQueryStore` that stores the queries in memory.
+///
+/// Since any client can register the queries, the number of stored queries is
+/// limited by the capacity, and the least recently used query is evicted when
+/// a new query is inserted into the full store.
+#[derive(Debug)]
+pub struct MemoryQueryStore {
+    inner: Mutex<Inner>,
+    capacity: usize,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    // the queries with the tick of their last access.
+    queries: HashMap<String, (String, u64)>,
+    tick: u64,
+}
+
+impl Default for MemoryQueryStore {
+    fn default() -> Self {
+        Self::with_capacity(1024)
+    }
+}
+
+impl MemoryQueryStore {
+    /// Creates an empty `MemoryQueryStore` with the default capacity (1024 queries).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty `MemoryQueryStore` which stores up to `capacity` queries.
+    ///
+    /// Finding the query to be evicted takes the time linear to the capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity,
+        }
+    }
+}
+
+impl PersistedQueryStore for MemoryQueryStore {
+    fn get(&self, hash: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+        let &mut (ref query, ref mut accessed) = inner.queries.get_mut(hash)?;
+        *accessed = tick;
+        Some(query.clone())
+    }
+
+    fn insert(&self, hash: &str, query: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+        if !inner.queries.contains_key(hash) && inner.queries.len() >= self.capacity {
+            let oldest = inner
+                .queries
+                .iter()
+                .min_by_key(|&(_, &(_, accessed))| accessed)
+                .map(|(hash, _)| hash.clone());
+            if let Some(oldest) = oldest {
+                inner.queries.remove(&oldest);
+            }
+        }
+        inner
+            .queries
+            .insert(hash.to_owned(), (query.to_owned(), tick));
+    }
+}
+
+/// Returns the SHA-256 hash of the query, in the lowercase hexadecimal form.
+pub(crate) fn sha256_hex(query: &str) -> String {
+    let digest = Sha256::digest(query.as_bytes());
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for b in digest.iter() {
+        let _ = write!(hex, "{:02x}", b);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryQueryStore, PersistedQueryStore};
+
+    #[test]
+    fn test_memory_store_evicts_least_recently_used() {
+        let store = MemoryQueryStore::with_capacity(2);
+        store.insert("a", "{ a }");
+        store.insert("b", "{ b }");
+        assert_eq!(store.get("a"), Some("{ a }".into()));
+
+        store.insert("c", "{ c }");
+        assert_eq!(store.get("a"), Some("{ a }".into()));
+        assert_eq!(store.get("b"), None);
+        assert_eq!(store.get("c"), Some("{ c }".into()));
+
+        // re-inserting the stored query does not evict others.
+        store.insert("a", "{ a }");
+        assert_eq!(store.get("c"), Some("{ c }".into()));
+    }
+}
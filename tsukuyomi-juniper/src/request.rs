@@ -1,10 +1,16 @@
 use {
-    crate::{error::GraphQLParseError, Schema},
+    crate::{
+        analysis,
+        error::{GraphQLParseError, GraphQLRequestError},
+        persisted_query::{self, PersistedQueryStore},
+        Schema,
+    },
     futures::{stream::Concat2, Future, Stream},
     http::{Method, Response, StatusCode},
     juniper::{DefaultScalarValue, InputValue, ScalarRefValue, ScalarValue},
     percent_encoding::percent_decode,
     serde::Deserialize,
+    std::{fmt, sync::Arc},
     tsukuyomi::{
         error::Error,
         extractor::Extractor,
@@ -78,7 +84,12 @@ where
                         RequestKind::GraphQL => {
                             return String::from_utf8(data.to_vec())
                                 .map(|query| {
-                                    Async::Ready((GraphQLRequest::single(query, None, None),))
+                                    Async::Ready((GraphQLRequest::single(
+                                        Some(query),
+                                        None,
+                                        None,
+                                        None,
+                                    ),))
                                 })
                                 .map_err(|e| GraphQLParseError::DecodeUtf8(e.utf8_error()).into())
                         }
@@ -109,17 +120,26 @@ where
 {
     #[derive(Debug, serde::Deserialize)]
     struct ParsedQuery {
-        query: String,
+        query: Option<String>,
         operation_name: Option<String>,
         variables: Option<String>,
+        extensions: Option<String>,
     }
     let parsed: ParsedQuery =
         serde_urlencoded::from_str(s).map_err(GraphQLParseError::ParseQuery)?;
 
-    let query = percent_decode(parsed.query.as_ref())
-        .decode_utf8()
-        .map_err(GraphQLParseError::DecodeUtf8)?
-        .into_owned();
+    // the query may be omitted if the hash of persisted query is provided.
+    if parsed.query.is_none() && parsed.extensions.is_none() {
+        return Err(GraphQLParseError::MissingQuery);
+    }
+
+    let query = parsed.query.map_or(Ok(None), |s| {
+        percent_decode(s.as_ref())
+            .decode_utf8()
+            .map_err(GraphQLParseError::DecodeUtf8)
+            .map(|s| s.into_owned())
+            .map(Some)
+    })?;
 
     let operation_name = parsed.operation_name.map_or(Ok(None), |s| {
         percent_decode(s.as_ref())
@@ -141,7 +161,24 @@ where
             Ok(variables)
         })?;
 
-    Ok(GraphQLRequest::single(query, operation_name, variables))
+    let extensions = parsed
+        .extensions
+        .map_or(Ok(None), |s| -> Result<_, GraphQLParseError> {
+            let decoded = percent_decode(s.as_ref())
+                .decode_utf8()
+                .map_err(GraphQLParseError::DecodeUtf8)?;
+            let extensions = serde_json::from_str(&*decoded)
+                .map(Some)
+                .map_err(GraphQLParseError::ParseJson)?;
+            Ok(extensions)
+        })?;
+
+    Ok(GraphQLRequest::single(
+        query,
+        operation_name,
+        variables,
+        extensions,
+    ))
 }
 
 /// The type representing a GraphQL request from the client.
@@ -152,8 +189,34 @@ pub struct GraphQLRequest<S: ScalarValue = DefaultScalarValue>(GraphQLRequestKin
 #[derive(Debug, Deserialize)]
 #[serde(untagged, bound = "InputValue<S>: Deserialize<'de>")]
 enum GraphQLRequestKind<S: ScalarValue> {
-    Single(juniper::http::GraphQLRequest<S>),
-    Batch(Vec<juniper::http::GraphQLRequest<S>>),
+    Batch(Vec<RawRequest<S>>),
+    Single(RawRequest<S>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(bound = "InputValue<S>: Deserialize<'de>")]
+struct RawRequest<S: ScalarValue> {
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(rename = "operationName", default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<InputValue<S>>,
+    #[serde(default)]
+    extensions: Option<Extensions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Extensions {
+    #[serde(rename = "persistedQuery", default)]
+    persisted_query: Option<PersistedQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersistedQuery {
+    version: u32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
 }
 
 impl<S> GraphQLRequest<S>
@@ -162,13 +225,17 @@ where
     for<'a> &'a S: ScalarRefValue<'a>,
 {
     fn single(
-        query: String,
+        query: Option<String>,
         operation_name: Option<String>,
         variables: Option<InputValue<S>>,
+        extensions: Option<Extensions>,
     ) -> Self {
-        GraphQLRequest(GraphQLRequestKind::Single(
-            juniper::http::GraphQLRequest::new(query, operation_name, variables),
-        ))
+        GraphQLRequest(GraphQLRequestKind::Single(RawRequest {
+            query,
+            operation_name,
+            variables,
+            extensions,
+        }))
     }
 
     /// Creates a `Responder` that executes this request using the specified schema and context.
@@ -182,6 +249,170 @@ where
             request: self,
             schema,
             context,
+            config: RequestConfig::default(),
+        }
+    }
+}
+
+/// The configuration for protecting the execution of GraphQL requests.
+///
+/// The violations are reported as GraphQL errors before executing the requests.
+#[derive(Clone, Default)]
+pub struct RequestConfig {
+    persisted_queries: Option<Arc<dyn PersistedQueryStore>>,
+    max_batch_size: Option<usize>,
+    max_depth: Option<usize>,
+    max_complexity: Option<usize>,
+}
+
+impl fmt::Debug for RequestConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestConfig")
+            .field("persisted_queries", &self.persisted_queries.is_some())
+            .field("max_batch_size", &self.max_batch_size)
+            .field("max_depth", &self.max_depth)
+            .field("max_complexity", &self.max_complexity)
+            .finish()
+    }
+}
+
+impl RequestConfig {
+    /// Creates a `RequestConfig` without any limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables the automatic persisted queries, using the specified store.
+    ///
+    /// The client may send the SHA-256 hash of the query in `extensions.persistedQuery`
+    /// instead of the query itself.  If the hash is unknown, the request is rejected
+    /// with `PersistedQueryNotFound` and the client is expected to retry with both the
+    /// query and the hash, in order to register the query to the store.
+    pub fn persisted_queries(self, store: impl PersistedQueryStore) -> Self {
+        Self {
+            persisted_queries: Some(Arc::new(store)),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of requests in a batch.
+    pub fn max_batch_size(self, max: usize) -> Self {
+        Self {
+            max_batch_size: Some(max),
+            ..self
+        }
+    }
+
+    /// Sets the maximum nesting level of fields in a query.
+    ///
+    /// The fields in the fragments are counted at the position of their spreads,
+    /// e.g. the depth of `{ hero { friends { name } } }` is 3.
+    ///
+    /// When the depth or complexity is limited, the documents which cannot be
+    /// analyzed are rejected with `MalformedQuery` before the execution.
+    pub fn max_depth(self, max: usize) -> Self {
+        Self {
+            max_depth: Some(max),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of fields to be resolved in a query.
+    ///
+    /// The fields in the fragments are counted at each of their spreads.
+    /// As with `max_depth`, the malformed documents are rejected before the execution.
+    pub fn max_complexity(self, max: usize) -> Self {
+        Self {
+            max_complexity: Some(max),
+            ..self
+        }
+    }
+
+    fn check_batch_size(&self, size: usize) -> Result<(), GraphQLRequestError> {
+        match self.max_batch_size {
+            Some(max) if size > max => Err(GraphQLRequestError::BatchTooLarge { size, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolves the persisted query and checks the limits of the request.
+    fn prepare<S>(
+        &self,
+        request: RawRequest<S>,
+    ) -> tsukuyomi::Result<juniper::http::GraphQLRequest<S>>
+    where
+        S: ScalarValue,
+        for<'a> &'a S: ScalarRefValue<'a>,
+    {
+        let RawRequest {
+            query,
+            operation_name,
+            variables,
+            extensions,
+        } = request;
+
+        let query = match extensions.and_then(|extensions| extensions.persisted_query) {
+            Some(persisted_query) => self.resolve_persisted_query(persisted_query, query)?,
+            None => query.ok_or_else(|| GraphQLParseError::MissingQuery)?,
+        };
+
+        if self.max_depth.is_some() || self.max_complexity.is_some() {
+            let metrics = analysis::analyze(
+                &query,
+                operation_name.as_ref().map(|s| &**s),
+                self.max_depth.unwrap_or_else(usize::max_value),
+            )
+            .ok_or_else(|| GraphQLRequestError::MalformedQuery)?;
+            match self.max_depth {
+                Some(max) if metrics.depth > max => {
+                    return Err(GraphQLRequestError::TooDeep {
+                        depth: metrics.depth,
+                        max,
+                    }
+                    .into());
+                }
+                _ => {}
+            }
+            match self.max_complexity {
+                Some(max) if metrics.complexity > max => {
+                    return Err(GraphQLRequestError::TooComplex {
+                        complexity: metrics.complexity,
+                        max,
+                    }
+                    .into());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(juniper::http::GraphQLRequest::new(
+            query,
+            operation_name,
+            variables,
+        ))
+    }
+
+    fn resolve_persisted_query(
+        &self,
+        persisted_query: PersistedQuery,
+        query: Option<String>,
+    ) -> Result<String, GraphQLRequestError> {
+        let store = match self.persisted_queries {
+            Some(ref store) if persisted_query.version == 1 => store,
+            _ => return Err(GraphQLRequestError::PersistedQueryNotSupported),
+        };
+        let hash = persisted_query.sha256_hash.to_ascii_lowercase();
+        match query {
+            Some(query) => {
+                if persisted_query::sha256_hex(&query) != hash {
+                    return Err(GraphQLRequestError::PersistedQueryHashMismatch);
+                }
+                store.insert(&hash, &query);
+                Ok(query)
+            }
+            None => store
+                .get(&hash)
+                .ok_or(GraphQLRequestError::PersistedQueryNotFound),
         }
     }
 }
@@ -192,6 +423,14 @@ pub struct GraphQLResponse<T, CtxT, S: ScalarValue = DefaultScalarValue> {
     request: GraphQLRequest<S>,
     schema: T,
     context: CtxT,
+    config: RequestConfig,
+}
+
+impl<T, CtxT, S: ScalarValue> GraphQLResponse<T, CtxT, S> {
+    /// Sets the configuration used for checking the request before the execution.
+    pub fn config(self, config: RequestConfig) -> Self {
+        Self { config, ..self }
+    }
 }
 
 impl<T, CtxT, S> Responder for GraphQLResponse<T, CtxT, S>
@@ -210,11 +449,13 @@ where
             request,
             schema,
             context,
+            config,
         } = self;
         let handle = tsukuyomi_server::rt::spawn_fn(move || -> tsukuyomi::Result<_> {
             use self::GraphQLRequestKind::*;
             match request.0 {
                 Single(request) => {
+                    let request = config.prepare(request)?;
                     let response = request.execute(schema.as_root_node(), context.as_ref());
                     let status = if response.is_ok() {
                         StatusCode::OK
@@ -230,6 +471,11 @@ where
                         .expect("should be a valid response"))
                 }
                 Batch(requests) => {
+                    config.check_batch_size(requests.len())?;
+                    let requests = requests
+                        .into_iter()
+                        .map(|request| config.prepare(request))
+                        .collect::<tsukuyomi::Result<Vec<_>>>()?;
                     let responses: Vec<_> = requests
                         .iter()
                         .map(|request| request.execute(schema.as_root_node(), context.as_ref()))
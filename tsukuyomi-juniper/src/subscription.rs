@@ -0,0 +1,678 @@
+//! GraphQL subscriptions over WebSocket, using the `graphql-ws` protocol.
+//!
+//! Juniper does not support the subscription operations natively.  Instead,
+//! a subscription is implemented as **query re-execution**: the operation type
+//! of the subscription is replaced with `query`, and the operation is executed
+//! against the query root of the same schema each time an event occurs.
+//!
+//! The stream of events is created by `SubscriptionHandler::subscribe` for
+//! each subscription.  Each event is passed to the resolvers through the context
+//! created by `SubscriptionHandler::execution_context`, and the result of each
+//! execution is sent to the client as a `data` message.  Hence the fields of
+//! a subscription must be defined on the query root, and they typically return
+//! the payload of the event found in the context.
+
+use {
+    crate::{
+        lexer::{tokenize, Token},
+        Schema,
+    },
+    futures::{
+        future::{self, Either},
+        stream::SplitStream,
+        sync::{mpsc, oneshot},
+        Async, Future, Poll, Sink, Stream,
+    },
+    http::Response,
+    juniper::{
+        http::GraphQLRequest as Request, DefaultScalarValue, InputValue, ScalarRefValue,
+        ScalarValue,
+    },
+    serde::Deserialize,
+    serde_json::{json, Value},
+    std::{
+        collections::HashMap,
+        fmt,
+        marker::PhantomData,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tokio_timer::Interval,
+    tsukuyomi::{
+        error::Error,
+        future::{Poll as TryPoll, TryFuture},
+        input::Input,
+        responder::Responder,
+    },
+    tsukuyomi_tungstenite::{Message, WebSocketStream, Ws},
+};
+
+/// The name of WebSocket subprotocol.
+const PROTOCOL: &str = "graphql-ws";
+
+/// A trait for customizing the behavior of subscription connections.
+pub trait SubscriptionHandler<S = DefaultScalarValue>: Send + Sync + 'static
+where
+    S: ScalarValue,
+    for<'a> &'a S: ScalarRefValue<'a>,
+{
+    /// The type of context shared by the operations in a connection.
+    type Context: Send + Sync + 'static;
+
+    /// The type of events which trigger the re-execution of subscriptions.
+    type Event: Send + 'static;
+
+    /// The type of stream that notifies the events of a subscription.
+    type Events: Stream<Item = Self::Event, Error = Self::Error> + Send + 'static;
+
+    /// The type of context passed to the resolvers, i.e. `Schema::Context`.
+    type ExecutionContext: Send + 'static;
+
+    /// The error type returned to the client.
+    type Error: fmt::Display + Send + 'static;
+
+    /// Creates the context of a connection from the payload of `connection_init`.
+    ///
+    /// This method is typically used to authenticate the client, and an error
+    /// returned from this method rejects the connection.
+    fn init(&self, payload: Option<&Value>) -> Result<Self::Context, Self::Error>;
+
+    /// Creates the stream of events which trigger the execution of the specified subscription.
+    ///
+    /// The subscription is completed when the returned stream ends.
+    fn subscribe(
+        &self,
+        operation: &Operation<'_, S>,
+        context: &Self::Context,
+    ) -> Result<Self::Events, Self::Error>;
+
+    /// Creates the context passed to the resolvers for an execution of an operation.
+    ///
+    /// The value of `event` is the event which triggered the execution of a subscription,
+    /// or `None` if the operation is a query or mutation.
+    fn execution_context(
+        &self,
+        context: &Self::Context,
+        event: Option<Self::Event>,
+    ) -> Self::ExecutionContext;
+}
+
+/// A subscription operation requested by the client.
+#[derive(Debug)]
+pub struct Operation<'a, S: ScalarValue = DefaultScalarValue> {
+    id: &'a str,
+    payload: &'a OperationPayload<S>,
+}
+
+impl<'a, S: ScalarValue> Operation<'a, S> {
+    /// Returns the ID of this operation specified by the client.
+    pub fn id(&self) -> &str {
+        self.id
+    }
+
+    /// Returns the query document of this operation.
+    pub fn query(&self) -> &str {
+        &self.payload.query
+    }
+
+    /// Returns the name of operation to be executed, if specified.
+    pub fn operation_name(&self) -> Option<&str> {
+        self.payload.operation_name.as_ref().map(|s| &**s)
+    }
+
+    /// Returns the variables passed to this operation, if specified.
+    pub fn variables(&self) -> Option<&InputValue<S>> {
+        self.payload.variables.as_ref()
+    }
+}
+
+/// Creates a `Responder` that serves the GraphQL subscriptions over WebSocket.
+///
+/// This function is available only if the feature `subscriptions` is enabled.
+pub fn subscriptions<T, H, S>(schema: T, handler: H) -> Subscriptions<T, H, S>
+where
+    T: Schema<S, Context = H::ExecutionContext> + Send + Sync + 'static,
+    H: SubscriptionHandler<S>,
+    S: ScalarValue + Send + Sync + 'static,
+    for<'a> &'a S: ScalarRefValue<'a>,
+{
+    Subscriptions {
+        shared: Arc::new(Shared { schema, handler }),
+        keep_alive: None,
+        buffer_size: 64,
+        _marker: PhantomData,
+    }
+}
+
+/// A `Responder` that serves the GraphQL subscriptions over WebSocket.
+#[derive(Debug)]
+pub struct Subscriptions<T, H, S = DefaultScalarValue> {
+    shared: Arc<Shared<T, H>>,
+    keep_alive: Option<Duration>,
+    buffer_size: usize,
+    _marker: PhantomData<fn() -> S>,
+}
+
+impl<T, H, S> Clone for Subscriptions<T, H, S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            keep_alive: self.keep_alive,
+            buffer_size: self.buffer_size,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, H, S> Subscriptions<T, H, S> {
+    /// Sets the interval of keep-alive messages (`ka`) sent to the client.
+    ///
+    /// By default, the keep-alive messages are not sent.
+    pub fn keep_alive(self, interval: Duration) -> Self {
+        Self {
+            keep_alive: Some(interval),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of outgoing messages buffered for a connection.
+    ///
+    /// If the client does not read the messages fast enough and the buffer
+    /// overflows, the connection is closed.
+    ///
+    /// The default value is `64`.
+    pub fn buffer_size(self, buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared<T, H> {
+    schema: T,
+    handler: H,
+}
+
+impl<T, H, S> Responder for Subscriptions<T, H, S>
+where
+    T: Schema<S, Context = H::ExecutionContext> + Send + Sync + 'static,
+    H: SubscriptionHandler<S>,
+    S: ScalarValue + Send + Sync + 'static,
+    for<'a> &'a S: ScalarRefValue<'a>,
+    InputValue<S>: for<'de> Deserialize<'de>,
+{
+    type Response = Response<()>;
+    type Error = Error;
+    type Respond = SubscriptionsRespond;
+
+    fn respond(self) -> Self::Respond {
+        let Self {
+            shared,
+            keep_alive,
+            buffer_size,
+            ..
+        } = self;
+        let ws = Ws::new(move |stream| {
+            serve::<T, H, S>(shared.clone(), keep_alive, buffer_size, stream)
+        })
+        .protocols(Some(PROTOCOL));
+        SubscriptionsRespond(Box::new(ws.respond()))
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct SubscriptionsRespond(Box<dyn TryFuture<Ok = Response<()>, Error = Error> + Send>);
+
+impl TryFuture for SubscriptionsRespond {
+    type Ok = Response<()>;
+    type Error = Error;
+
+    #[inline]
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> TryPoll<Self::Ok, Self::Error> {
+        self.0.poll_ready(input)
+    }
+}
+
+// ==== protocol ====
+
+#[derive(Debug, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    bound = "InputValue<S>: Deserialize<'de>"
+)]
+enum ClientMessage<S: ScalarValue> {
+    ConnectionInit {
+        #[serde(default)]
+        payload: Option<Value>,
+    },
+    Start {
+        id: String,
+        payload: OperationPayload<S>,
+    },
+    Stop {
+        id: String,
+    },
+    ConnectionTerminate,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(bound = "InputValue<S>: Deserialize<'de>")]
+struct OperationPayload<S: ScalarValue> {
+    query: String,
+    #[serde(rename = "operationName", default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<InputValue<S>>,
+}
+
+fn server_message(ty: &str, id: Option<&str>, payload: Option<Value>) -> String {
+    let mut message = json!({ "type": ty });
+    if let Some(id) = id {
+        message["id"] = id.into();
+    }
+    if let Some(payload) = payload {
+        message["payload"] = payload;
+    }
+    message.to_string()
+}
+
+fn error_payload(message: impl fmt::Display) -> Value {
+    json!({ "message": message.to_string() })
+}
+
+/// Rewrites the operation type of the subscription to be executed into `query`,
+/// and returns `None` if the operation is not a subscription.
+///
+/// The keyword is padded with spaces so that the locations in the error
+/// messages are unchanged.
+fn rewrite_subscription(document: &str, operation_name: Option<&str>) -> Option<String> {
+    const KEYWORD: &str = "subscription";
+
+    // the malformed documents are reported by the execution.
+    let subscriptions = subscription_operations(document)?;
+    let offset = subscriptions
+        .into_iter()
+        .find(|&(_, name)| operation_name.map_or(true, |n| name == Some(n)))
+        .map(|(offset, _)| offset)?;
+
+    let mut rewritten = document.to_owned();
+    rewritten.replace_range(offset..offset + KEYWORD.len(), "query       ");
+    Some(rewritten)
+}
+
+/// Returns the byte offsets and the names of the subscription operations in the document.
+///
+/// The return value is `None` if the document is not well-formed.
+fn subscription_operations(document: &str) -> Option<Vec<(usize, Option<&str>)>> {
+    let tokens = tokenize(document)?;
+    let mut subscriptions = vec![];
+    let mut depth = 0usize;
+    // whether the next token at the top level begins a definition.
+    let mut at_definition = true;
+    let mut iter = tokens.iter().peekable();
+    while let Some(&token) = iter.next() {
+        match token {
+            Token::Punct(b'{') | Token::Punct(b'(') | Token::Punct(b'[') => depth += 1,
+            Token::Punct(b'}') | Token::Punct(b')') | Token::Punct(b']') => {
+                depth = depth.checked_sub(1)?;
+                at_definition = depth == 0 && token == Token::Punct(b'}');
+                continue;
+            }
+            Token::Name(keyword) if keyword == "subscription" && depth == 0 && at_definition => {
+                let offset = keyword.as_ptr() as usize - document.as_ptr() as usize;
+                let name = match iter.peek() {
+                    Some(&&Token::Name(name)) => Some(name),
+                    _ => None,
+                };
+                subscriptions.push((offset, name));
+            }
+            _ => {}
+        }
+        at_definition = false;
+    }
+    Some(subscriptions)
+}
+
+// ==== connection ====
+
+fn serve<T, H, S>(
+    shared: Arc<Shared<T, H>>,
+    keep_alive: Option<Duration>,
+    buffer_size: usize,
+    stream: WebSocketStream,
+) -> impl Future<Item = (), Error = ()> + Send + 'static
+where
+    T: Schema<S, Context = H::ExecutionContext> + Send + Sync + 'static,
+    H: SubscriptionHandler<S>,
+    S: ScalarValue + Send + Sync + 'static,
+    for<'a> &'a S: ScalarRefValue<'a>,
+    InputValue<S>: for<'de> Deserialize<'de>,
+{
+    let (sink, incoming) = stream.split();
+    let (tx, rx) = mpsc::channel(buffer_size);
+    let (overflow_tx, overflow_rx) = oneshot::channel();
+
+    // the outgoing messages are sent through the channel, and the writer
+    // completes after all senders, including the ones owned by the running
+    // operations, are dropped.
+    let writer = rx
+        .map(Message::Text)
+        .forward(sink.sink_map_err(|_| ()))
+        .map(|_| ());
+
+    let connection = Connection::<T, H, S> {
+        shared,
+        incoming,
+        outgoing: Outgoing {
+            tx,
+            overflow: Arc::new(Mutex::new(Some(overflow_tx))),
+        },
+        keep_alive,
+        context: None,
+        operations: HashMap::new(),
+        closed: None,
+    };
+
+    // the whole connection is aborted when the buffer of outgoing messages overflows.
+    // The notifier dropped without firing means that the connection has been completed.
+    let overflow = overflow_rx.or_else(|_| future::empty::<(), ()>());
+
+    connection
+        .join(writer)
+        .map(|_| ())
+        .select2(overflow)
+        .then(|_| Ok(()))
+}
+
+/// The sender of outgoing messages.
+#[derive(Clone)]
+struct Outgoing {
+    tx: mpsc::Sender<String>,
+    overflow: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+}
+
+impl Outgoing {
+    fn send(&mut self, message: String) {
+        if let Err(err) = self.tx.try_send(message) {
+            if err.is_full() {
+                let mut overflow = self.overflow.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(overflow) = overflow.take() {
+                    let _ = overflow.send(());
+                }
+            }
+            // otherwise, the writer has been stopped and the error can be ignored.
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+struct Connection<T, H, S>
+where
+    H: SubscriptionHandler<S>,
+    S: ScalarValue,
+    for<'a> &'a S: ScalarRefValue<'a>,
+{
+    shared: Arc<Shared<T, H>>,
+    incoming: SplitStream<WebSocketStream>,
+    outgoing: Outgoing,
+    keep_alive: Option<Duration>,
+    context: Option<Arc<H::Context>>,
+    // the handles for cancelling the running operations.
+    operations: HashMap<String, oneshot::Sender<()>>,
+    // notifies the keep-alive task that the connection is closed, by dropping it.
+    closed: Option<oneshot::Sender<()>>,
+}
+
+impl<T, H, S> Connection<T, H, S>
+where
+    T: Schema<S, Context = H::ExecutionContext> + Send + Sync + 'static,
+    H: SubscriptionHandler<S>,
+    S: ScalarValue + Send + Sync + 'static,
+    for<'a> &'a S: ScalarRefValue<'a>,
+    InputValue<S>: for<'de> Deserialize<'de>,
+{
+    fn send(&mut self, message: String) {
+        self.outgoing.send(message);
+    }
+
+    /// Handles a message from the client, and returns `false` if the connection should be closed.
+    fn handle_message(&mut self, text: &str) -> bool {
+        let message: ClientMessage<S> = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(err) => {
+                self.send(server_message(
+                    "connection_error",
+                    None,
+                    Some(error_payload(err)),
+                ));
+                return false;
+            }
+        };
+
+        match message {
+            ClientMessage::ConnectionInit { payload } => {
+                match self.shared.handler.init(payload.as_ref()) {
+                    Ok(context) => {
+                        self.context = Some(Arc::new(context));
+                        self.send(server_message("connection_ack", None, None));
+                        self.start_keep_alive();
+                        true
+                    }
+                    Err(err) => {
+                        self.send(server_message(
+                            "connection_error",
+                            None,
+                            Some(error_payload(err)),
+                        ));
+                        false
+                    }
+                }
+            }
+            ClientMessage::Start { id, payload } => {
+                self.start(id, payload);
+                true
+            }
+            ClientMessage::Stop { id } => {
+                // the terminal message `complete` is sent by the operation itself.
+                if let Some(cancel) = self.operations.remove(&id) {
+                    let _ = cancel.send(());
+                }
+                true
+            }
+            ClientMessage::ConnectionTerminate => false,
+        }
+    }
+
+    fn start_keep_alive(&mut self) {
+        let interval = match self.keep_alive {
+            Some(interval) if self.closed.is_none() => interval,
+            _ => return,
+        };
+        let (closed_tx, closed_rx) = oneshot::channel();
+        self.closed = Some(closed_tx);
+
+        self.send(server_message("ka", None, None));
+        let mut outgoing = self.outgoing.clone();
+        let task = Interval::new(Instant::now() + interval, interval)
+            .map_err(|_| ())
+            .for_each(move |_| {
+                outgoing.send(server_message("ka", None, None));
+                Ok(())
+            })
+            .select2(closed_rx)
+            .then(|_| Ok(()));
+        tsukuyomi_server::rt::spawn(task);
+    }
+
+    fn start(&mut self, id: String, payload: OperationPayload<S>) {
+        let context = match self.context {
+            Some(ref context) => context.clone(),
+            None => {
+                self.send(server_message(
+                    "error",
+                    Some(&id),
+                    Some(error_payload("the connection has not been initialized")),
+                ));
+                return;
+            }
+        };
+
+        self.operations.retain(|_, cancel| !cancel.is_canceled());
+        if self.operations.contains_key(&id) {
+            self.send(server_message(
+                "error",
+                Some(&id),
+                Some(error_payload(format!(
+                    "the operation ID `{}' is already in use",
+                    id
+                ))),
+            ));
+            return;
+        }
+
+        let rewritten = rewrite_subscription(
+            &payload.query,
+            payload.operation_name.as_ref().map(|s| &**s),
+        );
+        let (query, events) = match rewritten {
+            Some(query) => {
+                let events = self.shared.handler.subscribe(
+                    &Operation {
+                        id: &id,
+                        payload: &payload,
+                    },
+                    &*context,
+                );
+                match events {
+                    Ok(events) => (query, Some(events)),
+                    Err(err) => {
+                        self.send(server_message("error", Some(&id), Some(error_payload(err))));
+                        return;
+                    }
+                }
+            }
+            None => (payload.query, None),
+        };
+        let request = Request::new(query, payload.operation_name, payload.variables);
+
+        let execute = {
+            let shared = self.shared.clone();
+            let request = Arc::new(request);
+            let outgoing = self.outgoing.clone();
+            let id = id.clone();
+            move |event: Option<H::Event>| {
+                let shared = shared.clone();
+                let request = request.clone();
+                let execution_context = shared.handler.execution_context(&*context, event);
+                let mut outgoing = outgoing.clone();
+                let id = id.clone();
+                tsukuyomi_server::rt::blocking(move || {
+                    let response =
+                        request.execute(shared.schema.as_root_node(), &execution_context);
+                    serde_json::to_value(&response).expect("should be success")
+                })
+                .map(move |payload| {
+                    outgoing.send(server_message("data", Some(&id), Some(payload)));
+                })
+                .map_err(|err| err.to_string())
+            }
+        };
+
+        let task = match events {
+            Some(events) => Either::A(
+                events
+                    .map_err(|err| err.to_string())
+                    .for_each(move |event| execute(Some(event))),
+            ),
+            None => Either::B(execute(None)),
+        };
+
+        // The operation sends exactly one terminal message: `complete` when the task is
+        // completed or stopped by the client, or `error` when the task is failed.
+        // The task is cancelled silently if the connection is closed.
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let mut outgoing = self.outgoing.clone();
+        let task = task.select2(cancel_rx).then(move |result| {
+            let message = match result {
+                Ok(..) => server_message("complete", Some(&id), None),
+                Err(Either::A((err, _))) => {
+                    server_message("error", Some(&id), Some(error_payload(err)))
+                }
+                Err(Either::B((oneshot::Canceled, _))) => return Ok(()),
+            };
+            outgoing.send(message);
+            Ok(())
+        });
+        tsukuyomi_server::rt::spawn(task);
+
+        self.operations.insert(id, cancel_tx);
+    }
+}
+
+impl<T, H, S> Future for Connection<T, H, S>
+where
+    T: Schema<S, Context = H::ExecutionContext> + Send + Sync + 'static,
+    H: SubscriptionHandler<S>,
+    S: ScalarValue + Send + Sync + 'static,
+    for<'a> &'a S: ScalarRefValue<'a>,
+    InputValue<S>: for<'de> Deserialize<'de>,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let message = match futures::try_ready!(self.incoming.poll().map_err(|_| ())) {
+                Some(message) => message,
+                None => return Ok(Async::Ready(())),
+            };
+            let keep_open = match message {
+                Message::Text(ref text) => self.handle_message(text),
+                Message::Binary(ref data) => match std::str::from_utf8(data) {
+                    Ok(text) => self.handle_message(text),
+                    Err(err) => {
+                        self.send(server_message(
+                            "connection_error",
+                            None,
+                            Some(error_payload(err)),
+                        ));
+                        false
+                    }
+                },
+                // the pong frames are sent by tungstenite automatically.
+                Message::Ping(..) | Message::Pong(..) => true,
+            };
+            if !keep_open {
+                // the running operations are cancelled by dropping the handles.
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::subscription_operations;
+
+    #[test]
+    fn test_subscription_operations() {
+        assert_eq!(subscription_operations("{ hero { name } }"), Some(vec![]));
+        assert_eq!(
+            subscription_operations("query subscription { subscription }"),
+            Some(vec![])
+        );
+        assert_eq!(
+            subscription_operations("subscription OnHero($id: String = \"}\") { hero { name } }"),
+            Some(vec![(0, Some("OnHero"))])
+        );
+        assert_eq!(
+            subscription_operations("# subscription\nquery A { a } subscription { b }"),
+            Some(vec![(29, None)])
+        );
+        assert_eq!(subscription_operations("subscription { a }}"), None);
+    }
+}
@@ -2,10 +2,11 @@ use {
     http::{Request, Response},
     juniper::{http::tests as http_tests, tests::model::Database, EmptyMutation, RootNode},
     percent_encoding::{define_encode_set, utf8_percent_encode, QUERY_ENCODE_SET},
+    serde_json::json,
     std::{cell::RefCell, sync::Arc},
     tsukuyomi::{config::prelude::*, App},
-    tsukuyomi_juniper::GraphQLRequest,
-    tsukuyomi_server::test::{Output as TestOutput, Server as TestServer},
+    tsukuyomi_juniper::{GraphQLRequest, MemoryQueryStore, RequestConfig},
+    tsukuyomi_server::test::{Output as TestOutput, ResponseExt, Server as TestServer},
 };
 
 #[test]
@@ -101,3 +102,112 @@ fn make_test_response(response: &Response<TestOutput>) -> http_tests::TestRespon
         body: Some(body),
     }
 }
+
+#[test]
+fn persisted_queries_and_limits() -> tsukuyomi_server::Result<()> {
+    const QUERY: &str = "{ hero { name } }";
+    const HASH: &str = "aae585680c3470e4947255eafbd1eafe87d1c3f129259cf15e404d1bb7f1e8f4";
+
+    let database = Arc::new(Database::new());
+    let schema = Arc::new(RootNode::new(
+        Database::new(),
+        EmptyMutation::<Database>::new(),
+    ));
+    let config = RequestConfig::new()
+        .persisted_queries(MemoryQueryStore::new())
+        .max_batch_size(2)
+        .max_depth(2)
+        .max_complexity(3);
+
+    let app = App::create(
+        path!("/")
+            .to(endpoint::allow_only("GET, POST")?
+                .extract(tsukuyomi_juniper::request())
+                .extract(tsukuyomi::extractor::value(schema))
+                .call(move |request: GraphQLRequest, schema: Arc<_>| {
+                    request
+                        .execute(schema, database.clone())
+                        .config(config.clone())
+                }))
+            .modify(tsukuyomi_juniper::capture_errors()),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let mut post = |body: serde_json::Value| -> tsukuyomi_server::Result<_> {
+        let response = server.perform(
+            Request::post("/")
+                .header("content-type", "application/json")
+                .body(body.to_string()),
+        )?;
+        let body: serde_json::Value = serde_json::from_str(&response.body().to_utf8()?)?;
+        Ok((response.status().as_u16(), body))
+    };
+    let error_code = |body: &serde_json::Value| body["errors"][0]["extensions"]["code"].clone();
+    let persisted_query = json!({ "version": 1, "sha256Hash": HASH });
+
+    let (status, body) = post(json!({
+        "extensions": { "persistedQuery": persisted_query },
+    }))?;
+    assert_eq!(status, 400);
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+    assert_eq!(error_code(&body), "PERSISTED_QUERY_NOT_FOUND");
+
+    let (status, body) = post(json!({
+        "query": "{ hero { id } }",
+        "extensions": { "persistedQuery": persisted_query },
+    }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "PERSISTED_QUERY_HASH_MISMATCH");
+
+    let (status, body) = post(json!({
+        "query": QUERY,
+        "extensions": { "persistedQuery": persisted_query },
+    }))?;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["hero"]["name"], "R2-D2");
+
+    let (status, body) = post(json!({
+        "extensions": { "persistedQuery": persisted_query },
+    }))?;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["hero"]["name"], "R2-D2");
+
+    let (status, body) = post(json!({ "query": "{ hero { friends { name } } }" }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "QUERY_TOO_DEEP");
+
+    let (status, body) = post(json!({ "query": "{ hero { id name appearsIn } }" }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "QUERY_TOO_COMPLEX");
+
+    let (status, body) = post(json!({ "query": "{ hero { name }" }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "GRAPHQL_PARSE_FAILED");
+
+    let nested = format!("{}{}", "{ hero ".repeat(10_000), "}".repeat(10_000));
+    let (status, body) = post(json!({ "query": nested }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "QUERY_TOO_DEEP");
+
+    let mut chain = "{ ...F0 }".to_owned();
+    for i in 0..10_000 {
+        chain += &format!(" fragment F{} on Query {{ ...F{} }}", i, i + 1);
+    }
+    let (status, body) = post(json!({ "query": chain }))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "GRAPHQL_PARSE_FAILED");
+
+    let (status, body) = post(json!([
+        { "query": QUERY },
+        { "query": QUERY },
+        { "query": QUERY },
+    ]))?;
+    assert_eq!(status, 400);
+    assert_eq!(error_code(&body), "BATCH_TOO_LARGE");
+
+    let (status, body) = post(json!([{ "query": QUERY }, { "query": QUERY }]))?;
+    assert_eq!(status, 200);
+    assert_eq!(body[1]["data"]["hero"]["name"], "R2-D2");
+
+    Ok(())
+}
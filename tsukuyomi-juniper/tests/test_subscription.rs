@@ -0,0 +1,220 @@
+#![cfg(feature = "subscriptions")]
+
+use {
+    futures::{stream, sync::oneshot, Async, Future, Poll, Stream},
+    juniper::{graphql_object, EmptyMutation, RootNode},
+    serde_json::{json, Value},
+    std::{
+        net::{SocketAddr, TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    },
+    tsukuyomi::{config::prelude::*, App},
+    tsukuyomi_juniper::{Operation, SubscriptionHandler},
+    tsukuyomi_server::Server,
+    tungstenite::{handshake::client::Request, Message, WebSocket},
+    url::Url,
+};
+
+struct Context {
+    event: Option<i32>,
+}
+
+impl juniper::Context for Context {}
+
+struct Query;
+
+graphql_object!(Query: Context |&self| {
+    field event(&executor) -> Option<i32> {
+        executor.context().event
+    }
+
+    field hello() -> &str {
+        "world"
+    }
+});
+
+type Schema = RootNode<'static, Query, EmptyMutation<Context>>;
+
+struct Handler;
+
+impl SubscriptionHandler for Handler {
+    type Context = ();
+    type Event = i32;
+    type Events = Box<dyn Stream<Item = i32, Error = String> + Send>;
+    type ExecutionContext = Context;
+    type Error = String;
+
+    fn init(&self, payload: Option<&Value>) -> Result<Self::Context, Self::Error> {
+        match payload.and_then(|payload| payload["token"].as_str()) {
+            Some("secret") => Ok(()),
+            _ => Err("invalid token".into()),
+        }
+    }
+
+    fn subscribe(
+        &self,
+        operation: &Operation<'_>,
+        _: &Self::Context,
+    ) -> Result<Self::Events, Self::Error> {
+        match operation.operation_name() {
+            // never completes until the client stops it.
+            Some("Forever") => Ok(Box::new(stream::iter_ok(vec![1]).chain(stream::poll_fn(
+                || -> Poll<Option<i32>, String> { Ok(Async::NotReady) },
+            )))),
+            _ => Ok(Box::new(stream::iter_ok(vec![1, 2]))),
+        }
+    }
+
+    fn execution_context(&self, _: &Self::Context, event: Option<Self::Event>) -> Context {
+        Context { event }
+    }
+}
+
+fn connect(addr: SocketAddr) -> WebSocket<TcpStream> {
+    let stream = TcpStream::connect(addr).expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("failed to set the read timeout");
+
+    let mut request =
+        Request::from(Url::parse(&format!("ws://{}/subscriptions", addr)).expect("invalid URL"));
+    request.add_protocol("graphql-ws".into());
+
+    let (ws, _) = tungstenite::client(request, stream)
+        .unwrap_or_else(|err| panic!("failed to perform the handshake: {}", err));
+    ws
+}
+
+fn send(ws: &mut WebSocket<TcpStream>, message: Value) {
+    ws.write_message(Message::Text(message.to_string()))
+        .expect("failed to send a message");
+}
+
+fn recv_raw(ws: &mut WebSocket<TcpStream>) -> Value {
+    match ws.read_message().expect("failed to receive a message") {
+        Message::Text(text) => serde_json::from_str(&text).expect("invalid JSON"),
+        message => panic!("unexpected message: {:?}", message),
+    }
+}
+
+/// Receives a message from the server, skipping the keep-alive messages.
+fn recv(ws: &mut WebSocket<TcpStream>) -> Value {
+    loop {
+        let message = recv_raw(ws);
+        if message["type"] != "ka" {
+            return message;
+        }
+    }
+}
+
+fn start(ws: &mut WebSocket<TcpStream>, id: &str, query: &str, operation_name: Option<&str>) {
+    send(
+        ws,
+        json!({
+            "type": "start",
+            "id": id,
+            "payload": {
+                "query": query,
+                "operationName": operation_name,
+            },
+        }),
+    );
+}
+
+#[test]
+fn graphql_ws_protocol() -> tsukuyomi_server::Result<()> {
+    let schema = Schema::new(Query, EmptyMutation::new());
+    let subscriptions = tsukuyomi_juniper::subscriptions(schema, Handler) //
+        .keep_alive(Duration::from_millis(50));
+    let app = App::create(
+        path!("/subscriptions") //
+            .to(endpoint::get().reply(subscriptions)),
+    )?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = Server::new(app)
+        .bind(listener)
+        .shutdown_signal(rx.map_err(|_| ()));
+    let handle = thread::spawn(move || server.run());
+
+    // the connection is rejected if the payload of `connection_init` is invalid.
+    {
+        let mut ws = connect(addr);
+        send(
+            &mut ws,
+            json!({ "type": "connection_init", "payload": { "token": "wrong" } }),
+        );
+        let message = recv_raw(&mut ws);
+        assert_eq!(message["type"], "connection_error");
+        assert_eq!(message["payload"]["message"], "invalid token");
+        match ws.read_message() {
+            Ok(Message::Close(..)) | Err(..) => {}
+            Ok(message) => panic!("the connection is not closed: {:?}", message),
+        }
+    }
+
+    let mut ws = connect(addr);
+
+    // the operations started before `connection_init` are rejected.
+    start(&mut ws, "0", "{ hello }", None);
+    let message = recv_raw(&mut ws);
+    assert_eq!(message["type"], "error");
+    assert_eq!(message["id"], "0");
+
+    // the keep-alive messages are sent after the acknowledgement.
+    send(
+        &mut ws,
+        json!({ "type": "connection_init", "payload": { "token": "secret" } }),
+    );
+    assert_eq!(recv_raw(&mut ws)["type"], "connection_ack");
+    assert_eq!(recv_raw(&mut ws)["type"], "ka");
+    assert_eq!(recv_raw(&mut ws)["type"], "ka");
+
+    // the query is executed once.
+    start(&mut ws, "1", "{ hello }", None);
+    let message = recv(&mut ws);
+    assert_eq!(message["type"], "data");
+    assert_eq!(message["id"], "1");
+    assert_eq!(message["payload"]["data"], json!({ "hello": "world" }));
+    assert_eq!(recv(&mut ws), json!({ "type": "complete", "id": "1" }));
+
+    // the subscription is re-executed with each event.
+    start(&mut ws, "2", "subscription { event }", None);
+    for event in &[1, 2] {
+        let message = recv(&mut ws);
+        assert_eq!(message["type"], "data");
+        assert_eq!(message["id"], "2");
+        assert_eq!(message["payload"]["data"], json!({ "event": event }));
+    }
+    assert_eq!(recv(&mut ws), json!({ "type": "complete", "id": "2" }));
+
+    // the subscription is cancelled by `stop`, and only one `complete` is sent.
+    start(
+        &mut ws,
+        "3",
+        "subscription Forever { event }",
+        Some("Forever"),
+    );
+    let message = recv(&mut ws);
+    assert_eq!(message["type"], "data");
+    assert_eq!(message["id"], "3");
+    assert_eq!(message["payload"]["data"], json!({ "event": 1 }));
+    send(&mut ws, json!({ "type": "stop", "id": "3" }));
+    assert_eq!(recv(&mut ws), json!({ "type": "complete", "id": "3" }));
+
+    // the next message belongs to the following operation.
+    start(&mut ws, "4", "{ hello }", None);
+    let message = recv(&mut ws);
+    assert_eq!(message["type"], "data");
+    assert_eq!(message["id"], "4");
+    assert_eq!(recv(&mut ws), json!({ "type": "complete", "id": "4" }));
+
+    send(&mut ws, json!({ "type": "connection_terminate" }));
+    drop(ws);
+
+    tx.send(()).expect("the server has already stopped");
+    handle.join().expect("the server thread has panicked")
+}
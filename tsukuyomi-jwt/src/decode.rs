@@ -0,0 +1,334 @@
+use {
+    crate::key::{Algorithm, KeySet},
+    serde::{de::DeserializeOwned, Deserialize},
+    serde_json::{Map, Value},
+    std::{
+        error, fmt,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// A set of rules for validating the registered claims.
+#[derive(Debug, Clone)]
+pub struct Validation {
+    leeway: Duration,
+    audience: Vec<String>,
+    issuer: Vec<String>,
+    require_exp: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            leeway: Duration::from_secs(60),
+            audience: vec![],
+            issuer: vec![],
+            require_exp: true,
+        }
+    }
+}
+
+impl Validation {
+    /// Sets the clock skew tolerated when checking `exp` and `nbf`.
+    ///
+    /// The default value is 60 seconds.
+    pub fn leeway(self, leeway: Duration) -> Self {
+        Self { leeway, ..self }
+    }
+
+    /// Appends an accepted value of `aud`.
+    ///
+    /// If any audiences are specified, the tokens must contain one of them.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience.push(audience.into());
+        self
+    }
+
+    /// Appends an accepted value of `iss`.
+    ///
+    /// If any issuers are specified, the tokens must contain one of them.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer.push(issuer.into());
+        self
+    }
+
+    /// Sets whether to reject the tokens without `exp`.
+    ///
+    /// The default value is `true`.
+    pub fn require_exp(self, require_exp: bool) -> Self {
+        Self {
+            require_exp,
+            ..self
+        }
+    }
+
+    fn validate(&self, claims: &Map<String, Value>, now: Duration) -> Result<(), DecodeError> {
+        let now = now.as_secs() as f64;
+        let leeway = self.leeway.as_secs() as f64;
+
+        match numeric_date(claims, "exp")? {
+            Some(exp) if now - leeway >= exp => return Err(DecodeError::Expired),
+            None if self.require_exp => {
+                return Err(DecodeError::InvalidClaims("missing `exp`".into()))
+            }
+            _ => {}
+        }
+
+        if let Some(nbf) = numeric_date(claims, "nbf")? {
+            if now + leeway < nbf {
+                return Err(DecodeError::NotYetValid);
+            }
+        }
+
+        if !self.audience.is_empty() {
+            let matched = match claims.get("aud") {
+                Some(Value::String(aud)) => self.audience.contains(aud),
+                Some(Value::Array(auds)) => auds
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .any(|aud| self.audience.iter().any(|a| a == aud)),
+                _ => false,
+            };
+            if !matched {
+                return Err(DecodeError::InvalidAudience);
+            }
+        }
+
+        if !self.issuer.is_empty() {
+            let matched = match claims.get("iss") {
+                Some(Value::String(iss)) => self.issuer.contains(iss),
+                _ => false,
+            };
+            if !matched {
+                return Err(DecodeError::InvalidIssuer);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn numeric_date(claims: &Map<String, Value>, name: &str) -> Result<Option<f64>, DecodeError> {
+    match claims.get(name) {
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| DecodeError::InvalidClaims(format!("`{}` is not a number", name))),
+        None => Ok(None),
+    }
+}
+
+/// The error that occurs during decoding a token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The token is not a well-formed JWS in compact serialization.
+    Malformed,
+    /// The algorithm in the header is not supported.
+    UnsupportedAlgorithm(String),
+    /// No key matches the algorithm and the key ID in the header.
+    UnknownKey,
+    /// The signature is invalid.
+    InvalidSignature,
+    /// The token is expired.
+    Expired,
+    /// The token is not valid yet.
+    NotYetValid,
+    /// The token is not issued for this audience.
+    InvalidAudience,
+    /// The token is not issued by the trusted issuer.
+    InvalidIssuer,
+    /// The claims cannot be validated or deserialized.
+    InvalidClaims(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed => f.write_str("the token is malformed"),
+            DecodeError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm: {}", alg),
+            DecodeError::UnknownKey => f.write_str("unknown signing key"),
+            DecodeError::InvalidSignature => f.write_str("the signature is invalid"),
+            DecodeError::Expired => f.write_str("the token is expired"),
+            DecodeError::NotYetValid => f.write_str("the token is not valid yet"),
+            DecodeError::InvalidAudience => f.write_str("invalid audience"),
+            DecodeError::InvalidIssuer => f.write_str("invalid issuer"),
+            DecodeError::InvalidClaims(msg) => write!(f, "invalid claims: {}", msg),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Verifies the signature of the token and deserializes its claims.
+pub fn decode<T>(token: &str, keys: &KeySet, validation: &Validation) -> Result<T, DecodeError>
+where
+    T: DeserializeOwned,
+{
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+    decode_at(token, keys, validation, now)
+}
+
+fn decode_at<T>(
+    token: &str,
+    keys: &KeySet,
+    validation: &Validation,
+    now: Duration,
+) -> Result<T, DecodeError>
+where
+    T: DeserializeOwned,
+{
+    let mut parts = token.rsplitn(2, '.');
+    let (sig, message) = match (parts.next(), parts.next()) {
+        (Some(sig), Some(message)) => (sig, message),
+        _ => return Err(DecodeError::Malformed),
+    };
+    let mut parts = message.splitn(2, '.');
+    let (header, payload) = match (parts.next(), parts.next()) {
+        (Some(header), Some(payload)) if !payload.contains('.') => (header, payload),
+        _ => return Err(DecodeError::Malformed),
+    };
+
+    let header: Header = decode_json(header)?;
+    let alg = header
+        .alg
+        .parse::<Algorithm>()
+        .map_err(|_| DecodeError::UnsupportedAlgorithm(header.alg.clone()))?;
+    let sig = decode_base64(sig)?;
+
+    let mut candidates = keys
+        .find(alg, header.kid.as_ref().map(|kid| &**kid))
+        .peekable();
+    if candidates.peek().is_none() {
+        return Err(DecodeError::UnknownKey);
+    }
+    if !candidates.any(|key| key.verify(message.as_bytes(), &sig)) {
+        return Err(DecodeError::InvalidSignature);
+    }
+
+    let claims: Map<String, Value> = decode_json(payload)?;
+    validation.validate(&claims, now)?;
+
+    serde_json::from_value(Value::Object(claims))
+        .map_err(|err| DecodeError::InvalidClaims(err.to_string()))
+}
+
+fn decode_base64(input: &str) -> Result<Vec<u8>, DecodeError> {
+    base64::decode_config(input, base64::URL_SAFE_NO_PAD).map_err(|_| DecodeError::Malformed)
+}
+
+fn decode_json<T>(input: &str) -> Result<T, DecodeError>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_slice(&decode_base64(input)?).map_err(|_| DecodeError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::key::Key,
+        ring::{digest, hmac},
+    };
+
+    fn sign_hs256(secret: &[u8], claims: &Value) -> String {
+        let header =
+            base64::encode_config(br#"{"alg":"HS256","typ":"JWT"}"#, base64::URL_SAFE_NO_PAD);
+        let payload = base64::encode_config(&claims.to_string(), base64::URL_SAFE_NO_PAD);
+        let message = format!("{}.{}", header, payload);
+        let key = hmac::SigningKey::new(&digest::SHA256, secret);
+        let sig = hmac::sign(&key, message.as_bytes());
+        format!(
+            "{}.{}",
+            message,
+            base64::encode_config(sig.as_ref(), base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn decode_value(token: &str, validation: &Validation) -> Result<Value, DecodeError> {
+        let keys = KeySet::new().key(Key::hmac(&b"secret"[..]));
+        decode_at(token, &keys, validation, Duration::from_secs(1000))
+    }
+
+    #[test]
+    fn test_signature() {
+        let claims = serde_json::json!({ "sub": "alice", "exp": 2000 });
+        let token = sign_hs256(b"secret", &claims);
+        assert_eq!(decode_value(&token, &Validation::default()), Ok(claims));
+
+        let token = sign_hs256(b"another secret", &serde_json::json!({ "exp": 2000 }));
+        assert_eq!(
+            decode_value(&token, &Validation::default()),
+            Err(DecodeError::InvalidSignature)
+        );
+
+        assert_eq!(
+            decode_value("invalid", &Validation::default()),
+            Err(DecodeError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_time_claims() {
+        let validation = Validation::default().leeway(Duration::from_secs(10));
+
+        let token = sign_hs256(b"secret", &serde_json::json!({ "exp": 995 }));
+        assert!(decode_value(&token, &validation).is_ok());
+
+        let token = sign_hs256(b"secret", &serde_json::json!({ "exp": 990 }));
+        assert_eq!(decode_value(&token, &validation), Err(DecodeError::Expired));
+
+        let token = sign_hs256(b"secret", &serde_json::json!({ "exp": 2000, "nbf": 1020 }));
+        assert_eq!(
+            decode_value(&token, &validation),
+            Err(DecodeError::NotYetValid)
+        );
+
+        let token = sign_hs256(b"secret", &serde_json::json!({}));
+        assert!(decode_value(&token, &validation).is_err());
+        assert!(decode_value(&token, &validation.require_exp(false)).is_ok());
+    }
+
+    #[test]
+    fn test_audience_and_issuer() {
+        let validation = Validation::default()
+            .audience("api")
+            .issuer("https://issuer.example.com");
+
+        let token = sign_hs256(
+            b"secret",
+            &serde_json::json!({
+                "exp": 2000,
+                "aud": ["web", "api"],
+                "iss": "https://issuer.example.com",
+            }),
+        );
+        assert!(decode_value(&token, &validation).is_ok());
+
+        let token = sign_hs256(
+            b"secret",
+            &serde_json::json!({ "exp": 2000, "aud": "web", "iss": "https://issuer.example.com" }),
+        );
+        assert_eq!(
+            decode_value(&token, &validation),
+            Err(DecodeError::InvalidAudience)
+        );
+
+        let token = sign_hs256(
+            b"secret",
+            &serde_json::json!({ "exp": 2000, "aud": "api", "iss": "https://evil.example.com" }),
+        );
+        assert_eq!(
+            decode_value(&token, &validation),
+            Err(DecodeError::InvalidIssuer)
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Verification keys and key sets.
+
+use {
+    ring::{digest, hmac, signature},
+    serde::Deserialize,
+    std::{error, fmt, fs, io, path::Path, str::FromStr},
+    untrusted::Input,
+};
+
+/// The signature algorithms supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// HMAC using SHA-256.
+    HS256,
+    /// RSASSA-PKCS1-v1_5 using SHA-256.
+    RS256,
+    /// ECDSA using P-256 and SHA-256.
+    ES256,
+}
+
+impl Algorithm {
+    /// Returns the name of this algorithm used in the `alg` parameter.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::HS256 => "HS256",
+            Algorithm::RS256 => "RS256",
+            Algorithm::ES256 => "ES256",
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" => Ok(Algorithm::HS256),
+            "RS256" => Ok(Algorithm::RS256),
+            "ES256" => Ok(Algorithm::ES256),
+            s => Err(KeyError::new(format!("unsupported algorithm: {}", s))),
+        }
+    }
+}
+
+/// A key used to verify the signature of tokens.
+///
+/// Each key is bound to an algorithm, and the tokens signed with another
+/// algorithm are never verified by the key.
+#[derive(Clone)]
+pub struct Key {
+    kid: Option<String>,
+    kind: KeyKind,
+}
+
+#[derive(Clone)]
+enum KeyKind {
+    Hmac(Vec<u8>),
+    RsaDer(Vec<u8>),
+    RsaComponents { n: Vec<u8>, e: Vec<u8> },
+    EcPoint(Vec<u8>),
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("kid", &self.kid)
+            .field("alg", &self.algorithm())
+            .finish()
+    }
+}
+
+impl Key {
+    fn new(kind: KeyKind) -> Self {
+        Self { kid: None, kind }
+    }
+
+    /// Creates a `Key` for `HS256` with the specified shared secret.
+    pub fn hmac(secret: impl Into<Vec<u8>>) -> Self {
+        Self::new(KeyKind::Hmac(secret.into()))
+    }
+
+    /// Creates a `Key` for `RS256` from a DER-encoded `RSAPublicKey` (PKCS#1).
+    pub fn rsa_der(der: impl Into<Vec<u8>>) -> Self {
+        Self::new(KeyKind::RsaDer(der.into()))
+    }
+
+    /// Creates a `Key` for `RS256` from the big-endian encoded modulus and exponent.
+    pub fn rsa_components(n: impl Into<Vec<u8>>, e: impl Into<Vec<u8>>) -> Self {
+        Self::new(KeyKind::RsaComponents {
+            n: n.into(),
+            e: e.into(),
+        })
+    }
+
+    /// Creates a `Key` for `ES256` from an uncompressed P-256 point (`0x04 || x || y`).
+    pub fn ec_point(point: impl Into<Vec<u8>>) -> Self {
+        Self::new(KeyKind::EcPoint(point.into()))
+    }
+
+    /// Sets the key ID matched against the `kid` parameter of tokens.
+    pub fn kid(self, kid: impl Into<String>) -> Self {
+        Self {
+            kid: Some(kid.into()),
+            ..self
+        }
+    }
+
+    /// Returns the key ID if specified.
+    pub fn key_id(&self) -> Option<&str> {
+        self.kid.as_ref().map(|kid| &**kid)
+    }
+
+    /// Returns the algorithm which this key is used with.
+    pub fn algorithm(&self) -> Algorithm {
+        match self.kind {
+            KeyKind::Hmac(..) => Algorithm::HS256,
+            KeyKind::RsaDer(..) | KeyKind::RsaComponents { .. } => Algorithm::RS256,
+            KeyKind::EcPoint(..) => Algorithm::ES256,
+        }
+    }
+
+    /// Verifies the signature of the specified message with this key.
+    pub fn verify(&self, message: &[u8], sig: &[u8]) -> bool {
+        let result = match self.kind {
+            KeyKind::Hmac(ref secret) => {
+                let key = hmac::VerificationKey::new(&digest::SHA256, secret);
+                return hmac::verify(&key, message, sig).is_ok();
+            }
+            KeyKind::RsaDer(ref der) => signature::verify(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                Input::from(der),
+                Input::from(message),
+                Input::from(sig),
+            ),
+            KeyKind::RsaComponents { ref n, ref e } => signature::primitive::verify_rsa(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                (Input::from(n), Input::from(e)),
+                Input::from(message),
+                Input::from(sig),
+            ),
+            KeyKind::EcPoint(ref point) => signature::verify(
+                &signature::ECDSA_P256_SHA256_FIXED,
+                Input::from(point),
+                Input::from(message),
+                Input::from(sig),
+            ),
+        };
+        result.is_ok()
+    }
+}
+
+/// A set of verification keys.
+#[derive(Debug, Clone, Default)]
+pub struct KeySet {
+    keys: Vec<Key>,
+}
+
+impl KeySet {
+    /// Creates an empty `KeySet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a key to this set.
+    pub fn key(mut self, key: Key) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// Parses a JSON Web Key Set (RFC 7517).
+    ///
+    /// The keys whose type or algorithm is not supported, and the keys not used
+    /// for signatures, are skipped.
+    pub fn from_jwks(jwks: &str) -> Result<Self, KeyError> {
+        let jwks: Jwks = serde_json::from_str(jwks).map_err(KeyError::json)?;
+        let mut keys = vec![];
+        for jwk in jwks.keys {
+            if let Some(key) = jwk.into_key()? {
+                keys.push(key);
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// Loads a JSON Web Key Set from the specified file.
+    pub fn from_jwks_file(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let jwks = fs::read_to_string(path).map_err(KeyError::io)?;
+        Self::from_jwks(&jwks)
+    }
+
+    /// Returns an iterator of keys matching the specified algorithm and key ID.
+    ///
+    /// If the key ID is not given, all keys for the algorithm are returned.
+    /// If no key has the given key ID, the keys for the algorithm without
+    /// the key ID are returned instead.
+    pub fn find<'a>(
+        &'a self,
+        alg: Algorithm,
+        kid: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Key> + 'a {
+        let kid_matched = kid.map_or(false, |kid| {
+            self.keys
+                .iter()
+                .any(|key| key.algorithm() == alg && key.key_id() == Some(kid))
+        });
+        self.keys.iter().filter(move |key| {
+            key.algorithm() == alg
+                && match kid {
+                    Some(kid) if kid_matched => key.key_id() == Some(kid),
+                    Some(..) => key.key_id().is_none(),
+                    None => true,
+                }
+        })
+    }
+
+    /// Returns the number of keys contained in this set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if this set contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    use_: Option<String>,
+    crv: Option<String>,
+    k: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+impl Jwk {
+    fn into_key(self) -> Result<Option<Key>, KeyError> {
+        if self.use_.as_ref().map_or(false, |u| u != "sig") {
+            return Ok(None);
+        }
+
+        let alg = match self.alg.as_ref().map(|alg| alg.parse::<Algorithm>()) {
+            Some(Ok(alg)) => Some(alg),
+            Some(Err(..)) => return Ok(None),
+            None => None,
+        };
+
+        let key = match (&*self.kty, alg) {
+            ("oct", None) | ("oct", Some(Algorithm::HS256)) => {
+                Key::hmac(decode_param("k", &self.k)?)
+            }
+            ("RSA", None) | ("RSA", Some(Algorithm::RS256)) => {
+                Key::rsa_components(decode_param("n", &self.n)?, decode_param("e", &self.e)?)
+            }
+            ("EC", None) | ("EC", Some(Algorithm::ES256)) => {
+                if self.crv.as_ref().map_or(true, |crv| crv != "P-256") {
+                    return Ok(None);
+                }
+                let mut point = vec![0x04];
+                point.extend(decode_param("x", &self.x)?);
+                point.extend(decode_param("y", &self.y)?);
+                Key::ec_point(point)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(match self.kid {
+            Some(kid) => key.kid(kid),
+            None => key,
+        }))
+    }
+}
+
+fn decode_param(name: &str, value: &Option<String>) -> Result<Vec<u8>, KeyError> {
+    let value = value
+        .as_ref()
+        .ok_or_else(|| KeyError::new(format!("missing JWK parameter: {}", name)))?;
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| KeyError::new(format!("invalid JWK parameter `{}`: {}", name, e)))
+}
+
+/// An error that occurs during loading the keys.
+#[derive(Debug)]
+pub struct KeyError {
+    message: String,
+    source: Option<Box<dyn error::Error + Send + Sync>>,
+}
+
+impl KeyError {
+    fn new(message: String) -> Self {
+        Self {
+            message,
+            source: None,
+        }
+    }
+
+    fn io(err: io::Error) -> Self {
+        Self {
+            message: format!("failed to read the key set: {}", err),
+            source: Some(Box::new(err)),
+        }
+    }
+
+    fn json(err: serde_json::Error) -> Self {
+        Self {
+            message: format!("invalid JWK set: {}", err),
+            source: Some(Box::new(err)),
+        }
+    }
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for KeyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|err| &**err as &(dyn error::Error + 'static))
+    }
+}
@@ -0,0 +1,253 @@
+//! JWT authentication support for Tsukuyomi.
+//!
+//! The extractor `Jwt` takes a token from the `Authorization: Bearer` header field
+//! (or a cookie), verifies its signature with the configured keys, validates
+//! the registered claims and deserializes the claims into the specified type.
+//!
+//! ```ignore
+//! use tsukuyomi_jwt::{Jwt, KeySet, Validation};
+//!
+//! #[derive(Debug, serde::Deserialize)]
+//! struct Claims {
+//!     sub: String,
+//! }
+//!
+//! let jwt = Jwt::<Claims>::new(KeySet::from_jwks_file("jwks.json")?)
+//!     .validation(
+//!         Validation::default()
+//!             .audience("https://api.example.com")
+//!             .issuer("https://auth.example.com/"),
+//!     );
+//!
+//! let app = App::create(
+//!     path!("/me").to(endpoint::get()
+//!         .extract(jwt)
+//!         .call(|claims: Claims| claims.sub)),
+//! )?;
+//! ```
+//!
+//! The supported algorithms are `HS256`, `RS256` and `ES256`.
+
+#![doc(html_root_url = "https://docs.rs/tsukuyomi-jwt/0.1.0")]
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    rust_2018_compatibility,
+    unused
+)]
+#![forbid(clippy::unimplemented)]
+
+mod decode;
+pub mod key;
+
+pub use crate::{
+    decode::{decode, DecodeError, Validation},
+    key::{Algorithm, Key, KeySet},
+};
+
+use {
+    serde::de::DeserializeOwned,
+    serde_json::Value,
+    std::{fmt, marker::PhantomData, sync::Arc},
+    tsukuyomi::{
+        error::Error,
+        extractor::{
+            auth::{AuthError, BearerErrorCode},
+            Extractor,
+        },
+        future::{Async, Poll, TryFuture},
+        input::Input,
+    },
+};
+
+/// An `Extractor` that authenticates the request with a JWT.
+///
+/// If the request does not contain a token, it is rejected with
+/// `401 Unauthorized` and a `Bearer` challenge.  If the token is invalid or
+/// expired, the challenge contains `error="invalid_token"` and the description
+/// of the error.  If the token does not have the required scopes, the request
+/// is rejected with `403 Forbidden` and `error="insufficient_scope"`.
+pub struct Jwt<T> {
+    inner: Arc<Config>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Debug)]
+struct Config {
+    keys: KeySet,
+    validation: Validation,
+    realm: String,
+    cookie: Option<String>,
+    scopes: Vec<String>,
+}
+
+impl<T> fmt::Debug for Jwt<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt").field("inner", &self.inner).finish()
+    }
+}
+
+impl<T> Clone for Jwt<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Jwt<T>
+where
+    T: DeserializeOwned,
+{
+    /// Creates a `Jwt` that verifies the tokens with the specified keys.
+    pub fn new(keys: KeySet) -> Self {
+        Self {
+            inner: Arc::new(Config {
+                keys,
+                validation: Validation::default(),
+                realm: "Restricted".into(),
+                cookie: None,
+                scopes: vec![],
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Config {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the rules for validating the registered claims.
+    pub fn validation(mut self, validation: Validation) -> Self {
+        self.inner_mut().validation = validation;
+        self
+    }
+
+    /// Sets the realm included in the challenge.
+    ///
+    /// The default value is `"Restricted"`.
+    pub fn realm(mut self, realm: impl Into<String>) -> Self {
+        self.inner_mut().realm = realm.into();
+        self
+    }
+
+    /// Sets the name of cookie from which the token is taken
+    /// when the request does not have `Authorization` header field.
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        self.inner_mut().cookie = Some(name.into());
+        self
+    }
+
+    /// Appends a scope that the token must have.
+    ///
+    /// The scopes of the token are read from the claim `scope`
+    /// (a space-delimited string) or `scp` (an array of strings).
+    pub fn require_scope(mut self, scope: impl Into<String>) -> Self {
+        self.inner_mut().scopes.push(scope.into());
+        self
+    }
+}
+
+impl<T> Extractor for Jwt<T>
+where
+    T: DeserializeOwned,
+{
+    type Output = (T,);
+    type Error = Error;
+    type Extract = JwtExtract<T>; // private
+
+    fn extract(&self) -> Self::Extract {
+        JwtExtract {
+            config: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct JwtExtract<T> {
+    config: Arc<Config>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> TryFuture for JwtExtract<T>
+where
+    T: DeserializeOwned,
+{
+    type Ok = (T,);
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        let config = &*self.config;
+
+        let token = match find_token(input, config)? {
+            Some(token) => token,
+            None => return Err(AuthError::bearer(&*config.realm).into()),
+        };
+
+        let invalid_token = |err: DecodeError| {
+            AuthError::bearer_error(&*config.realm, BearerErrorCode::InvalidToken)
+                .description(err.to_string())
+        };
+
+        let claims: Value =
+            decode(&token, &config.keys, &config.validation).map_err(invalid_token)?;
+
+        if !config.scopes.is_empty() {
+            let granted = granted_scopes(&claims);
+            if !config
+                .scopes
+                .iter()
+                .all(|scope| granted.contains(&&**scope))
+            {
+                return Err(AuthError::bearer_error(
+                    &*config.realm,
+                    BearerErrorCode::InsufficientScope,
+                )
+                .description("the access token does not have the required scopes")
+                .scope(config.scopes.join(" "))
+                .into());
+            }
+        }
+
+        let claims = serde_json::from_value(claims)
+            .map_err(|err| invalid_token(DecodeError::InvalidClaims(err.to_string())))?;
+
+        Ok(Async::Ready((claims,)))
+    }
+}
+
+fn find_token(input: &mut Input<'_>, config: &Config) -> tsukuyomi::Result<Option<String>> {
+    if let Some(value) = input.request.headers().get(http::header::AUTHORIZATION) {
+        let value = value.to_str().map_err(|_| {
+            AuthError::bearer_error(&*config.realm, BearerErrorCode::InvalidRequest)
+                .description("the header field is not a valid string")
+        })?;
+        let value = value.trim();
+        if let Some(pos) = value.find(' ') {
+            if value[..pos].eq_ignore_ascii_case("Bearer") {
+                return Ok(Some(value[pos + 1..].trim_start().to_owned()));
+            }
+        }
+    }
+
+    if let Some(ref name) = config.cookie {
+        if let Some(cookie) = input.cookies.jar()?.get(name) {
+            return Ok(Some(cookie.value().to_owned()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn granted_scopes(claims: &Value) -> Vec<&str> {
+    match (claims.get("scope"), claims.get("scp")) {
+        (Some(Value::String(scope)), _) => scope.split_whitespace().collect(),
+        (_, Some(Value::Array(scp))) => scp.iter().filter_map(Value::as_str).collect(),
+        (_, Some(Value::String(scp))) => scp.split_whitespace().collect(),
+        _ => vec![],
+    }
+}
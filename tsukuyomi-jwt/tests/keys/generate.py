@@ -0,0 +1,77 @@
+#!/usr/bin/env python3
+# Generates the keys and the known-good tokens used in the tests of signature verification.
+# Requires the package `cryptography`.
+
+import base64
+import json
+import os
+
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, padding, rsa
+from cryptography.hazmat.primitives.asymmetric.utils import decode_dss_signature
+
+os.chdir(os.path.dirname(os.path.abspath(__file__)))
+
+# The claims of the tokens, expiring at 2100-01-01T00:00:00Z.
+CLAIMS = {"sub": "alice", "aud": "api", "scope": "read", "exp": 4102444800}
+
+
+def b64(data):
+    return base64.urlsafe_b64encode(data).rstrip(b"=").decode()
+
+
+def uint(n, length=None):
+    return n.to_bytes(length or (n.bit_length() + 7) // 8, "big")
+
+
+def token(alg, kid, sign):
+    header = b64(json.dumps({"alg": alg, "typ": "JWT", "kid": kid}, separators=(",", ":")).encode())
+    payload = b64(json.dumps(CLAIMS, separators=(",", ":")).encode())
+    message = "{}.{}".format(header, payload)
+    return "{}.{}".format(message, b64(sign(message.encode())))
+
+
+def write(name, data):
+    with open(name, "wb") as f:
+        f.write(data)
+
+
+rsa_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+ec_key = ec.generate_private_key(ec.SECP256R1())
+
+write("rsa.key", rsa_key.private_bytes(
+    serialization.Encoding.PEM, serialization.PrivateFormat.PKCS8, serialization.NoEncryption()))
+write("ec.key", ec_key.private_bytes(
+    serialization.Encoding.PEM, serialization.PrivateFormat.PKCS8, serialization.NoEncryption()))
+
+# `RSAPublicKey` (PKCS#1) and the uncompressed point, used by `Key::rsa_der` and `Key::ec_point`.
+write("rsa_public.der", rsa_key.public_key().public_bytes(
+    serialization.Encoding.DER, serialization.PublicFormat.PKCS1))
+write("ec_public.bin", ec_key.public_key().public_bytes(
+    serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint))
+
+rsa_numbers = rsa_key.public_key().public_numbers()
+ec_numbers = ec_key.public_key().public_numbers()
+jwks = {
+    "keys": [
+        {"kty": "RSA", "kid": "rsa", "alg": "RS256", "use": "sig",
+         "n": b64(uint(rsa_numbers.n)), "e": b64(uint(rsa_numbers.e))},
+        {"kty": "EC", "kid": "ec", "crv": "P-256",
+         "x": b64(uint(ec_numbers.x, 32)), "y": b64(uint(ec_numbers.y, 32))},
+    ]
+}
+write("jwks.json", (json.dumps(jwks, indent=2) + "\n").encode())
+
+
+def sign_rs256(message):
+    return rsa_key.sign(message, padding.PKCS1v15(), hashes.SHA256())
+
+
+def sign_es256(message):
+    # JWS uses the fixed-length concatenation of r and s, rather than DER.
+    r, s = decode_dss_signature(ec_key.sign(message, ec.ECDSA(hashes.SHA256())))
+    return uint(r, 32) + uint(s, 32)
+
+
+write("rs256.jwt", token("RS256", "rsa", sign_rs256).encode())
+write("es256.jwt", token("ES256", "ec", sign_es256).encode())
@@ -0,0 +1,236 @@
+use {
+    http::Request,
+    ring::{digest, hmac},
+    serde::Deserialize,
+    std::time::{SystemTime, UNIX_EPOCH},
+    tsukuyomi::{config::prelude::*, App},
+    tsukuyomi_jwt::{decode, Algorithm, DecodeError, Jwt, Key, KeySet, Validation},
+    tsukuyomi_server::test::ResponseExt,
+};
+
+#[test]
+fn test_version_sync() {
+    version_sync::assert_html_root_url_updated!("src/lib.rs");
+}
+
+fn sign_hs256(secret: &[u8], claims: &serde_json::Value) -> String {
+    let header = base64::encode_config(br#"{"alg":"HS256","typ":"JWT"}"#, base64::URL_SAFE_NO_PAD);
+    let payload = base64::encode_config(&claims.to_string(), base64::URL_SAFE_NO_PAD);
+    let message = format!("{}.{}", header, payload);
+    let key = hmac::SigningKey::new(&digest::SHA256, secret);
+    let sig = hmac::sign(&key, message.as_bytes());
+    format!(
+        "{}.{}",
+        message,
+        base64::encode_config(sig.as_ref(), base64::URL_SAFE_NO_PAD)
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+// The keys and the known-good tokens generated by `tests/keys/generate.py`.
+const JWKS: &str = include_str!("keys/jwks.json");
+const RSA_PUBLIC_DER: &[u8] = include_bytes!("keys/rsa_public.der");
+const EC_PUBLIC_POINT: &[u8] = include_bytes!("keys/ec_public.bin");
+const RS256_TOKEN: &str = include_str!("keys/rs256.jwt");
+const ES256_TOKEN: &str = include_str!("keys/es256.jwt");
+
+fn validation() -> Validation {
+    Validation::default().audience("api")
+}
+
+#[test]
+fn test_rs256() -> Result<(), tsukuyomi_jwt::key::KeyError> {
+    let keys = KeySet::new().key(Key::rsa_der(RSA_PUBLIC_DER).kid("rsa"));
+    let claims: Claims = decode(RS256_TOKEN, &keys, &validation()).unwrap();
+    assert_eq!(claims.sub, "alice");
+
+    let keys = KeySet::from_jwks(JWKS)?;
+    assert_eq!(keys.find(Algorithm::RS256, Some("rsa")).count(), 1);
+    let claims: Claims = decode(RS256_TOKEN, &keys, &validation()).unwrap();
+    assert_eq!(claims.sub, "alice");
+
+    // the signature does not match to the tampered payload.
+    let mut parts: Vec<&str> = RS256_TOKEN.split('.').collect();
+    let payload = base64::encode_config(
+        br#"{"sub":"mallory","aud":"api","scope":"read","exp":4102444800}"#,
+        base64::URL_SAFE_NO_PAD,
+    );
+    parts[1] = &payload;
+    assert_eq!(
+        decode::<Claims>(&parts.join("."), &keys, &validation()).unwrap_err(),
+        DecodeError::InvalidSignature
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_es256() -> Result<(), tsukuyomi_jwt::key::KeyError> {
+    let keys = KeySet::new().key(Key::ec_point(EC_PUBLIC_POINT).kid("ec"));
+    let claims: Claims = decode(ES256_TOKEN, &keys, &validation()).unwrap();
+    assert_eq!(claims.sub, "alice");
+
+    let keys = KeySet::from_jwks(JWKS)?;
+    assert_eq!(keys.find(Algorithm::ES256, Some("ec")).count(), 1);
+    let claims: Claims = decode(ES256_TOKEN, &keys, &validation()).unwrap();
+    assert_eq!(claims.sub, "alice");
+
+    // the signature is verified only with the key specified by `kid`.
+    let keys = KeySet::new().key(Key::ec_point(EC_PUBLIC_POINT).kid("another"));
+    assert_eq!(
+        decode::<Claims>(ES256_TOKEN, &keys, &validation()).unwrap_err(),
+        DecodeError::UnknownKey
+    );
+
+    // the keys without `kid` are used if no key matches `kid`.
+    let keys = KeySet::new()
+        .key(Key::ec_point(EC_PUBLIC_POINT).kid("another"))
+        .key(Key::ec_point(EC_PUBLIC_POINT));
+    assert_eq!(keys.find(Algorithm::ES256, Some("ec")).count(), 1);
+    let claims: Claims = decode(ES256_TOKEN, &keys, &validation()).unwrap();
+    assert_eq!(claims.sub, "alice");
+
+    Ok(())
+}
+
+#[test]
+fn test_algorithm_confusion() -> Result<(), tsukuyomi_jwt::key::KeyError> {
+    // A forged token signed by HMAC using the public key of RS256 as the shared secret,
+    // which is accepted by the implementations selecting the algorithm from the header.
+    let forged = {
+        let header = base64::encode_config(
+            br#"{"alg":"HS256","typ":"JWT","kid":"rsa"}"#,
+            base64::URL_SAFE_NO_PAD,
+        );
+        let payload = base64::encode_config(
+            br#"{"sub":"mallory","aud":"api","scope":"read","exp":4102444800}"#,
+            base64::URL_SAFE_NO_PAD,
+        );
+        let message = format!("{}.{}", header, payload);
+        let key = hmac::SigningKey::new(&digest::SHA256, RSA_PUBLIC_DER);
+        let sig = hmac::sign(&key, message.as_bytes());
+        format!(
+            "{}.{}",
+            message,
+            base64::encode_config(sig.as_ref(), base64::URL_SAFE_NO_PAD)
+        )
+    };
+
+    for keys in vec![
+        KeySet::new().key(Key::rsa_der(RSA_PUBLIC_DER).kid("rsa")),
+        KeySet::from_jwks(JWKS)?,
+    ] {
+        assert_eq!(
+            decode::<Claims>(&forged, &keys, &validation()).unwrap_err(),
+            DecodeError::UnknownKey
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_jwks() -> Result<(), tsukuyomi_jwt::key::KeyError> {
+    let keys = KeySet::from_jwks(
+        r#"{
+            "keys": [
+                { "kty": "oct", "kid": "hmac", "k": "c2VjcmV0" },
+                { "kty": "RSA", "kid": "enc", "use": "enc", "n": "AQAB", "e": "AQAB" },
+                { "kty": "OKP", "kid": "ed25519", "crv": "Ed25519", "x": "AAAA" }
+            ]
+        }"#,
+    )?;
+    assert_eq!(keys.len(), 1);
+
+    let key = keys
+        .find(tsukuyomi_jwt::Algorithm::HS256, Some("hmac"))
+        .next()
+        .unwrap();
+    let token = sign_hs256(b"secret", &serde_json::json!({}));
+    let pos = token.rfind('.').unwrap();
+    let sig = base64::decode_config(&token[pos + 1..], base64::URL_SAFE_NO_PAD).unwrap();
+    assert!(key.verify(token[..pos].as_bytes(), &sig));
+
+    Ok(())
+}
+
+#[test]
+fn test_jwt_extractor() -> tsukuyomi_server::Result<()> {
+    let jwt = Jwt::<Claims>::new(KeySet::new().key(Key::hmac(&b"secret"[..])))
+        .validation(Validation::default().audience("api"))
+        .cookie("access-token")
+        .require_scope("read");
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get()
+                .extract(jwt)
+                .call(|claims: Claims| claims.sub)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let valid = sign_hs256(
+        b"secret",
+        &serde_json::json!({ "sub": "alice", "aud": "api", "scope": "read write", "exp": now() + 60 }),
+    );
+    let response =
+        server.perform(Request::get("/").header("authorization", format!("Bearer {}", valid)))?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "alice");
+
+    let response =
+        server.perform(Request::get("/").header("cookie", format!("access-token={}", valid)))?;
+    assert_eq!(response.status(), 200);
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Bearer realm="Restricted""#
+    );
+
+    let expired = sign_hs256(
+        b"secret",
+        &serde_json::json!({ "sub": "alice", "aud": "api", "scope": "read", "exp": now() - 120 }),
+    );
+    let response =
+        server.perform(Request::get("/").header("authorization", format!("Bearer {}", expired)))?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Bearer realm="Restricted", error="invalid_token", error_description="the token is expired""#
+    );
+
+    let forged = sign_hs256(
+        b"another secret",
+        &serde_json::json!({ "sub": "alice", "aud": "api", "scope": "read", "exp": now() + 60 }),
+    );
+    let response =
+        server.perform(Request::get("/").header("authorization", format!("Bearer {}", forged)))?;
+    assert_eq!(response.status(), 401);
+
+    let insufficient = sign_hs256(
+        b"secret",
+        &serde_json::json!({ "sub": "alice", "aud": "api", "scope": "write", "exp": now() + 60 }),
+    );
+    let response = server
+        .perform(Request::get("/").header("authorization", format!("Bearer {}", insufficient)))?;
+    assert_eq!(response.status(), 403);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Bearer realm="Restricted", error="insufficient_scope", error_description="the access token does not have the required scopes", scope="read""#
+    );
+
+    Ok(())
+}
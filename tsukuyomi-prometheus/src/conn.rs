@@ -0,0 +1,186 @@
+use {
+    crate::Metrics,
+    futures::{Future, Poll, Stream},
+    std::io::{self, Read, Write},
+    tokio::io::{AsyncRead, AsyncWrite},
+    tsukuyomi_server::{Acceptor, Listener},
+};
+
+/// A `Listener` that records the metrics about connections, created by `Metrics::instrument_listener`.
+#[derive(Debug)]
+pub struct InstrumentedListener<L> {
+    pub(crate) listener: L,
+    pub(crate) metrics: Metrics,
+}
+
+impl<L> Listener for InstrumentedListener<L>
+where
+    L: Listener,
+{
+    type Conn = InstrumentedConn<L::Conn>;
+    type Error = L::Error;
+    type Incoming = Incoming<L::Incoming>;
+
+    fn listen(self) -> Result<Self::Incoming, Self::Error> {
+        Ok(Incoming {
+            incoming: self.listener.listen()?,
+            metrics: self.metrics,
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct Incoming<S> {
+    incoming: S,
+    metrics: Metrics,
+}
+
+impl<S> Stream for Incoming<S>
+where
+    S: Stream,
+{
+    type Item = InstrumentedConn<S::Item>;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let conn = futures::try_ready!(self.incoming.poll());
+        Ok(conn
+            .map(|io| {
+                let inner = &self.metrics.inner;
+                inner.connections_accepted.inc();
+                inner.connections_active.inc();
+                InstrumentedConn {
+                    io,
+                    metrics: self.metrics.clone(),
+                }
+            })
+            .into())
+    }
+}
+
+/// An asynchronous I/O tracked as an active connection.
+///
+/// The gauge of active connections is decremented when the value of this type is dropped.
+#[derive(Debug)]
+pub struct InstrumentedConn<T> {
+    io: T,
+    metrics: Metrics,
+}
+
+impl<T> InstrumentedConn<T> {
+    /// Returns a reference to the underlying I/O.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Returns a mutable reference to the underlying I/O.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+}
+
+impl<T> Drop for InstrumentedConn<T> {
+    fn drop(&mut self) {
+        self.metrics.inner.connections_active.dec();
+    }
+}
+
+impl<T: Read> Read for InstrumentedConn<T> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<T: Write> Write for InstrumentedConn<T> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for InstrumentedConn<T> {
+    #[inline]
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.io.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for InstrumentedConn<T> {
+    #[inline]
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// A trait for classifying the errors returned from acceptors.
+pub trait AcceptError {
+    /// Returns whether the error is a failure in the TLS handshake,
+    /// rather than an I/O error in the underlying transport.
+    fn is_handshake_failure(&self) -> bool;
+}
+
+/// The TLS acceptors that report errors as `io::Error` (e.g. `tokio_rustls::TlsAcceptor`)
+/// use `ErrorKind::InvalidData` for the errors in the TLS protocol.
+impl AcceptError for io::Error {
+    fn is_handshake_failure(&self) -> bool {
+        self.kind() == io::ErrorKind::InvalidData
+    }
+}
+
+/// An `Acceptor` that counts the failures while establishing connections,
+/// created by `Metrics::instrument_acceptor`.
+#[derive(Debug)]
+pub struct InstrumentedAcceptor<A> {
+    pub(crate) acceptor: A,
+    pub(crate) metrics: Metrics,
+}
+
+impl<A, T> Acceptor<T> for InstrumentedAcceptor<A>
+where
+    A: Acceptor<T>,
+    A::Error: AcceptError,
+{
+    type Conn = A::Conn;
+    type Error = A::Error;
+    type Accept = Accept<A::Accept>;
+
+    fn accept(&self, io: T) -> Self::Accept {
+        Accept {
+            accept: self.acceptor.accept(io),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct Accept<F> {
+    accept: F,
+    metrics: Metrics,
+}
+
+impl<F> Future for Accept<F>
+where
+    F: Future,
+    F::Error: AcceptError,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.accept.poll().map_err(|err| {
+            let inner = &self.metrics.inner;
+            if err.is_handshake_failure() {
+                inner.tls_handshake_failures.inc();
+            } else {
+                inner.accept_errors.inc();
+            }
+            err
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use {
+    crate::Metrics,
+    futures::{
+        future::{self, FutureResult},
+        Async, Future, Poll,
+    },
+    http::{Method, Request, Response},
+    prometheus::IntGauge,
+    std::{io, time::Instant},
+    tsukuyomi::{app::MatchedRoute, output::ResponseBody},
+    tsukuyomi_service::{ModifyService, Service},
+};
+
+/// A `ModifyService` that records the metrics about the requests, created by `Metrics::instrument`.
+///
+/// The instrumentation is provided only at the service level, not as a `ModifyHandler`.
+/// The handlers return their output before it is converted into a response, so the status
+/// code and the size of the response body are not available to the modifiers of handlers,
+/// and the requests which do not match any route never reach the handlers.
+#[derive(Debug, Clone)]
+pub struct Instrument {
+    pub(crate) metrics: Metrics,
+}
+
+impl<Ctx, Bd, S> ModifyService<Ctx, Request<Bd>, S> for Instrument
+where
+    S: Service<Request<Bd>, Response = Response<ResponseBody>>,
+{
+    type Response = Response<ResponseBody>;
+    type Error = S::Error;
+    type Service = InstrumentService<S>; // private
+    type ModifyError = io::Error;
+    type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+    fn modify_service(&self, inner: S, _: Ctx) -> Self::Future {
+        future::ok(InstrumentService {
+            inner,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct InstrumentService<S> {
+    inner: S,
+    metrics: Metrics,
+}
+
+impl<S, Bd> Service<Request<Bd>> for InstrumentService<S>
+where
+    S: Service<Request<Bd>, Response = Response<ResponseBody>>,
+{
+    type Response = Response<ResponseBody>;
+    type Error = S::Error;
+    type Future = InstrumentFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, request: Request<Bd>) -> Self::Future {
+        let method = method_label(request.method());
+        let gauge = self
+            .metrics
+            .inner
+            .requests_in_flight
+            .with_label_values(&[method]);
+        gauge.inc();
+        InstrumentFuture {
+            inner: self.inner.call(request),
+            metrics: self.metrics.clone(),
+            method,
+            start: Instant::now(),
+            in_flight: Some(InFlight(gauge)),
+        }
+    }
+}
+
+/// Returns the value of label `method`.
+///
+/// The unknown methods are bucketed into `"OTHER"` in order to bound
+/// the cardinality of labels, since the clients can send arbitrary methods.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::CONNECT => "CONNECT",
+        Method::OPTIONS => "OPTIONS",
+        Method::TRACE => "TRACE",
+        Method::PATCH => "PATCH",
+        _ => "OTHER",
+    }
+}
+
+/// A guard that decrements the gauge when the request is completed or aborted.
+struct InFlight(IntGauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct InstrumentFuture<F> {
+    inner: F,
+    metrics: Metrics,
+    method: &'static str,
+    start: Instant,
+    in_flight: Option<InFlight>,
+}
+
+impl<F> Future for InstrumentFuture<F>
+where
+    F: Future<Item = Response<ResponseBody>>,
+{
+    type Item = Response<ResponseBody>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let response = futures::try_ready!(self.inner.poll());
+        drop(self.in_flight.take());
+
+        let route = response
+            .extensions()
+            .get::<MatchedRoute>()
+            .map_or("*", |route| route.pattern());
+        let labels = &[self.method, route];
+
+        let inner = &self.metrics.inner;
+        let elapsed = self.start.elapsed();
+        inner
+            .request_duration
+            .with_label_values(labels)
+            .observe(elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9);
+        inner
+            .requests_total
+            .with_label_values(&[self.method, route, response.status().as_str()])
+            .inc();
+
+        let response_size = inner.response_size.with_label_values(labels);
+        Ok(Async::Ready(response.map(move |body| {
+            body.on_complete(move |sent| response_size.observe(sent as f64))
+        })))
+    }
+}
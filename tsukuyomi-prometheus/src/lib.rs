@@ -0,0 +1,260 @@
+//! Prometheus metrics support for Tsukuyomi.
+//!
+//! ```ignore
+//! let metrics = Metrics::new()?;
+//!
+//! let app = App::create(chain![
+//!     path!("/metrics").to(metrics.endpoint()),
+//!     path!("/").to(endpoint::reply("Hello")),
+//! ])?;
+//!
+//! Server::new(app.with_modify_service(metrics.instrument()))
+//!     .bind(metrics.instrument_listener(addr))
+//!     .acceptor(metrics.instrument_acceptor(acceptor))
+//!     .run()
+//! ```
+
+#![doc(html_root_url = "https://docs.rs/tsukuyomi-prometheus/0.1.0")]
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    rust_2018_compatibility,
+    unused
+)]
+#![forbid(clippy::unimplemented)]
+
+mod conn;
+mod instrument;
+
+pub use crate::{
+    conn::{AcceptError, InstrumentedAcceptor, InstrumentedConn, InstrumentedListener},
+    instrument::Instrument,
+};
+
+use {
+    futures::future::{self, FutureResult},
+    http::{header, Method, Response},
+    prometheus::{
+        Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+        Opts, Registry, TextEncoder,
+    },
+    std::{fmt, sync::Arc},
+    tsukuyomi::{
+        endpoint::{ApplyContext, ApplyError, ApplyResult, Endpoint},
+        future::Compat01,
+        handler::AllowedMethods,
+    },
+};
+
+#[doc(no_inline)]
+pub use prometheus;
+
+/// A collection of metrics about the HTTP server.
+///
+/// The following metrics are registered:
+///
+/// * `http_requests_total` - the number of handled requests, labelled by `method`, `route` and `status`.
+/// * `http_request_duration_seconds` - the latency until the application returns a response,
+///   labelled by `method` and `route`.
+/// * `http_requests_in_flight` - the number of requests being handled, labelled by `method`.
+/// * `http_response_size_bytes` - the size of sent response bodies, labelled by `method` and `route`.
+/// * `http_connections_accepted_total` - the number of accepted connections.
+/// * `http_connections_active` - the number of active connections.
+/// * `http_tls_handshake_failures_total` - the number of failures in the TLS handshakes.
+/// * `http_accept_errors_total` - the number of I/O errors while establishing connections.
+///
+/// The label `route` is the pattern of matched route (e.g. `"/users/:id"`),
+/// or `"*"` when no route matches to the request, that is, the request is handled by
+/// a default handler or rejected with `404 Not Found` by the application.
+/// The gauge of in-flight requests is not labelled by `route`, since the route
+/// is unknown until the application returns a response.
+///
+/// The label `method` is one of the standard methods, or `"OTHER"` for the extension methods.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    registry: Registry,
+    requests_total: IntCounterVec,
+    request_duration: HistogramVec,
+    requests_in_flight: IntGaugeVec,
+    response_size: HistogramVec,
+    connections_accepted: IntCounter,
+    connections_active: IntGauge,
+    tls_handshake_failures: IntCounter,
+    accept_errors: IntCounter,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish()
+    }
+}
+
+impl Metrics {
+    /// Creates a `Metrics` with a new registry.
+    pub fn new() -> prometheus::Result<Self> {
+        Self::with_registry(Registry::new())
+    }
+
+    /// Creates a `Metrics` whose metrics are registered into the specified registry.
+    pub fn with_registry(registry: Registry) -> prometheus::Result<Self> {
+        let requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "The number of handled requests."),
+            &["method", "route", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "The latency until the handler returns a response.",
+            ),
+            &["method", "route"],
+        )?;
+        let requests_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "http_requests_in_flight",
+                "The number of requests being handled.",
+            ),
+            &["method"],
+        )?;
+        let response_size = HistogramVec::new(
+            HistogramOpts::new(
+                "http_response_size_bytes",
+                "The size of sent response bodies.",
+            )
+            .buckets(prometheus::exponential_buckets(64.0, 4.0, 10)?),
+            &["method", "route"],
+        )?;
+        let connections_accepted = IntCounter::new(
+            "http_connections_accepted_total",
+            "The number of accepted connections.",
+        )?;
+        let connections_active = IntGauge::new(
+            "http_connections_active",
+            "The number of active connections.",
+        )?;
+        let tls_handshake_failures = IntCounter::new(
+            "http_tls_handshake_failures_total",
+            "The number of failures in the TLS handshakes.",
+        )?;
+        let accept_errors = IntCounter::new(
+            "http_accept_errors_total",
+            "The number of I/O errors while establishing connections.",
+        )?;
+
+        registry.register(Box::new(requests_total.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(requests_in_flight.clone()))?;
+        registry.register(Box::new(response_size.clone()))?;
+        registry.register(Box::new(connections_accepted.clone()))?;
+        registry.register(Box::new(connections_active.clone()))?;
+        registry.register(Box::new(tls_handshake_failures.clone()))?;
+        registry.register(Box::new(accept_errors.clone()))?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                registry,
+                requests_total,
+                request_duration,
+                requests_in_flight,
+                response_size,
+                connections_accepted,
+                connections_active,
+                tls_handshake_failures,
+                accept_errors,
+            }),
+        })
+    }
+
+    /// Returns the reference to the registry.
+    ///
+    /// The registry can be used for registering application-specific metrics,
+    /// which are exposed from the same endpoint.
+    pub fn registry(&self) -> &Registry {
+        &self.inner.registry
+    }
+
+    /// Creates a `ModifyService` that records the metrics about the requests.
+    ///
+    /// The modifier is applied to the whole application by `App::with_modify_service`,
+    /// so that the requests which do not match any route are also recorded.
+    /// The size of response bodies is measured by `ResponseBody::on_complete`.
+    /// The metrics are recorded only at the service level, since the status code and
+    /// the size of the response are unknown to the modifiers of handlers.
+    pub fn instrument(&self) -> Instrument {
+        Instrument {
+            metrics: self.clone(),
+        }
+    }
+
+    /// Wraps a `Listener` to record the metrics about the connections.
+    pub fn instrument_listener<L>(&self, listener: L) -> InstrumentedListener<L> {
+        InstrumentedListener {
+            listener,
+            metrics: self.clone(),
+        }
+    }
+
+    /// Wraps an `Acceptor` to record the failures while establishing the connections.
+    ///
+    /// The errors from the acceptor are classified by `AcceptError`. The acceptors whose
+    /// error type is not `io::Error` can be used by mapping the errors, e.g. the failures
+    /// of the handshake into `io::ErrorKind::InvalidData`.
+    pub fn instrument_acceptor<A>(&self, acceptor: A) -> InstrumentedAcceptor<A> {
+        InstrumentedAcceptor {
+            acceptor,
+            metrics: self.clone(),
+        }
+    }
+
+    /// Creates an `Endpoint` that exposes the metrics in the Prometheus text format.
+    pub fn endpoint(&self) -> MetricsEndpoint {
+        MetricsEndpoint {
+            metrics: self.clone(),
+            allowed_methods: vec![Method::GET, Method::HEAD].into_iter().collect(),
+        }
+    }
+
+    /// Encodes the metrics gathered from the registry in the Prometheus text format.
+    pub fn render(&self) -> prometheus::Result<Response<Vec<u8>>> {
+        let encoder = TextEncoder::new();
+        let mut buf = vec![];
+        encoder.encode(&self.inner.registry.gather(), &mut buf)?;
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, encoder.format_type())
+            .body(buf)
+            .expect("should be a valid response"))
+    }
+}
+
+/// An `Endpoint` that exposes the metrics, created by `Metrics::endpoint`.
+#[derive(Debug)]
+pub struct MetricsEndpoint {
+    metrics: Metrics,
+    allowed_methods: AllowedMethods,
+}
+
+impl<T> Endpoint<T> for MetricsEndpoint {
+    type Output = Response<Vec<u8>>;
+    type Error = tsukuyomi::Error;
+    type Future = Compat01<FutureResult<Self::Output, Self::Error>>;
+
+    fn apply(&self, args: T, cx: &mut ApplyContext<'_, '_>) -> ApplyResult<T, Self> {
+        if !self.allowed_methods.contains(cx.method()) {
+            return Err((args, ApplyError::method_not_allowed()));
+        }
+        Ok(Compat01::from(future::result(
+            self.metrics
+                .render()
+                .map_err(tsukuyomi::error::internal_server_error),
+        )))
+    }
+
+    fn allowed_methods(&self) -> Option<AllowedMethods> {
+        Some(self.allowed_methods.clone())
+    }
+}
@@ -0,0 +1,152 @@
+use {
+    futures::{Future, Stream},
+    http::{Method, Request},
+    std::{
+        io::{self, Cursor},
+        net::{TcpListener, TcpStream},
+    },
+    tsukuyomi::{config::prelude::*, App},
+    tsukuyomi_prometheus::Metrics,
+    tsukuyomi_server::{test::ResponseExt, Acceptor, Listener},
+};
+
+#[test]
+fn test_version_sync() {
+    version_sync::assert_html_root_url_updated!("src/lib.rs");
+}
+
+fn render(metrics: &Metrics) -> String {
+    String::from_utf8(metrics.render().unwrap().into_body()).unwrap()
+}
+
+#[test]
+fn test_instrument() -> tsukuyomi_server::Result<()> {
+    let metrics = Metrics::new()?;
+
+    let app = App::create(chain![
+        path!("/metrics").to(metrics.endpoint()),
+        path!("/hello/:name") //
+            .to(endpoint::allow_only("GET, PURGE")?
+                .call(|name: String| format!("Hello, {}", name))),
+    ])?;
+    let mut server = tsukuyomi_server::test::server(app.with_modify_service(metrics.instrument()))?;
+
+    let response = server.perform("/hello/alice")?;
+    assert_eq!(response.header("content-length")?, "12");
+    let _ = server.perform("/hello/bob")?;
+    let _ = server.perform(
+        Request::builder()
+            .method(Method::from_bytes(b"PURGE")?)
+            .uri("/hello/carol"),
+    )?;
+
+    // the requests which do not match any route are also recorded.
+    let response = server.perform("/noroute")?;
+    assert_eq!(response.status(), 404);
+
+    let response = server.perform("/metrics")?;
+    assert_eq!(response.status(), 200);
+    let body = response.body().to_utf8()?;
+    assert!(
+        body.contains(r#"http_requests_total{method="GET",route="/hello/:name",status="200"} 2"#),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains(r#"http_requests_total{method="OTHER",route="/hello/:name",status="200"} 1"#),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains(r#"http_requests_total{method="GET",route="*",status="404"} 1"#),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains(r#"http_requests_in_flight{method="GET"} 1"#),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains(r#"http_response_size_bytes_sum{method="GET",route="/hello/:name"} 22"#),
+        "{}",
+        body
+    );
+    assert!(body.contains("http_request_duration_seconds_count"));
+
+    // the request for /metrics has been completed.
+    assert!(render(&metrics).contains(r#"http_requests_in_flight{method="GET"} 0"#));
+
+    Ok(())
+}
+
+#[test]
+fn test_instrument_listener() -> tsukuyomi_server::Result<()> {
+    let metrics = Metrics::new()?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let incoming = metrics.instrument_listener(listener).listen()?;
+
+    let _client1 = TcpStream::connect(addr)?;
+    let _client2 = TcpStream::connect(addr)?;
+    let (conn1, incoming) = incoming.into_future().wait().map_err(|(err, _)| err)?;
+    let (conn2, _incoming) = incoming.into_future().wait().map_err(|(err, _)| err)?;
+    assert!(conn1.is_some());
+    assert!(conn2.is_some());
+
+    let body = render(&metrics);
+    assert!(
+        body.contains("http_connections_accepted_total 2"),
+        "{}",
+        body
+    );
+    assert!(body.contains("http_connections_active 2"), "{}", body);
+
+    drop(conn1);
+    let body = render(&metrics);
+    assert!(
+        body.contains("http_connections_accepted_total 2"),
+        "{}",
+        body
+    );
+    assert!(body.contains("http_connections_active 1"), "{}", body);
+
+    Ok(())
+}
+
+#[test]
+fn test_instrument_acceptor() -> tsukuyomi_server::Result<()> {
+    let metrics = Metrics::new()?;
+
+    // a mock of TLS acceptor that fails according to the first byte from the client.
+    let acceptor = metrics.instrument_acceptor(|io: Cursor<Vec<u8>>| match io.get_ref().first() {
+        Some(0x16) => Ok(io),
+        Some(..) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a handshake",
+        )),
+        None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "early eof")),
+    });
+
+    assert!(acceptor.accept(Cursor::new(vec![0x16])).wait().is_ok());
+    assert!(acceptor
+        .accept(Cursor::new(b"GET".to_vec()))
+        .wait()
+        .is_err());
+    assert!(acceptor
+        .accept(Cursor::new(b"GET".to_vec()))
+        .wait()
+        .is_err());
+    assert!(acceptor.accept(Cursor::new(vec![])).wait().is_err());
+
+    let body = render(&metrics);
+    assert!(
+        body.contains("http_tls_handshake_failures_total 2"),
+        "{}",
+        body
+    );
+    assert!(body.contains("http_accept_errors_total 1"), "{}", body);
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+//! Rate limiting support for Tsukuyomi.
+//!
+//! The limit is enforced by GCRA (generic cell rate algorithm), which behaves like
+//! a token bucket whose state is a single timestamp per key.
+//!
+//! ```ignore
+//! use tsukuyomi_ratelimit::{store::MemoryStore, Quota, RateLimit};
+//!
+//! // limit the requests per client IP address.
+//! let client_ip = tsukuyomi::extractor::extension::<SocketAddr>()
+//!     .map(|addr: SocketAddr| addr.ip());
+//!
+//! let app = App::create(
+//!     path!("/api").to(endpoint::reply("Hello"))
+//!         .modify(RateLimit::new(client_ip, Quota::per_minute(60), MemoryStore::new())),
+//! )?;
+//! ```
+
+#![doc(html_root_url = "https://docs.rs/tsukuyomi-ratelimit/0.1.0")]
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    rust_2018_compatibility,
+    unused
+)]
+#![forbid(clippy::unimplemented)]
+
+mod modifier;
+pub mod store;
+
+pub use crate::modifier::RateLimit;
+
+use {
+    futures::Future,
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+    tsukuyomi::error::Error,
+};
+
+/// A trait representing the storage of rate limiting states.
+pub trait Store {
+    /// The type of errors which will occur when polling `Check`.
+    type Error: Into<Error>;
+    /// The type of `Future` that will return the decision.
+    type Check: Future<Item = Decision, Error = Self::Error>;
+
+    /// Consumes a cell of the quota associated with the specified key.
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check;
+}
+
+impl<S> Store for Box<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Check = S::Check;
+
+    #[inline]
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check {
+        (**self).check(key, quota)
+    }
+}
+
+impl<S> Store for std::rc::Rc<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Check = S::Check;
+
+    #[inline]
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check {
+        (**self).check(key, quota)
+    }
+}
+
+impl<S> Store for std::sync::Arc<S>
+where
+    S: Store,
+{
+    type Error = S::Error;
+    type Check = S::Check;
+
+    #[inline]
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check {
+        (**self).check(key, quota)
+    }
+}
+
+/// The number of requests allowed within a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    limit: u32,
+    interval: u64, // in microseconds
+}
+
+impl Quota {
+    /// Creates a `Quota` allowing `limit` requests per `period`.
+    ///
+    /// By default, all of `limit` requests are permitted to be sent at once.
+    ///
+    /// # Panics
+    /// This function panics if `limit` is zero or `period` is too short to
+    /// be divided by `limit` in microseconds.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        assert!(limit > 0, "the limit must be greater than zero");
+        let period = period.as_secs() * 1_000_000 + u64::from(period.subsec_micros());
+        let interval = period / u64::from(limit);
+        assert!(interval > 0, "the period is too short");
+        Self { limit, interval }
+    }
+
+    /// Creates a `Quota` allowing `limit` requests per second.
+    pub fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    /// Creates a `Quota` allowing `limit` requests per minute.
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    /// Creates a `Quota` allowing `limit` requests per hour.
+    pub fn per_hour(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60 * 60))
+    }
+
+    /// Sets the maximum number of requests permitted to be sent at once.
+    ///
+    /// The requests are replenished at the same rate regardless of this value.
+    ///
+    /// # Panics
+    /// This method panics if `burst` is zero.
+    pub fn burst(self, burst: u32) -> Self {
+        assert!(burst > 0, "the burst must be greater than zero");
+        Self {
+            limit: burst,
+            ..self
+        }
+    }
+
+    /// Returns the maximum number of requests permitted to be sent at once.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Returns the interval between the replenishment of a request, in microseconds.
+    pub fn emission_interval(&self) -> u64 {
+        self.interval
+    }
+
+    fn tolerance(&self) -> u64 {
+        self.interval * u64::from(self.limit)
+    }
+
+    /// Applies GCRA to the stored theoretical arrival time (TAT), and returns the
+    /// decision with the TAT to be stored.
+    ///
+    /// All timestamps are represented in microseconds since the UNIX epoch
+    /// (see `now_micros`).  The returned TAT must be stored only when the
+    /// request is allowed.
+    pub fn apply(&self, tat: Option<u64>, now: u64) -> (Decision, u64) {
+        let tat = tat.map_or(now, |tat| tat.max(now));
+        let new_tat = tat + self.interval;
+        if new_tat - now > self.tolerance() {
+            (self.decision(false, tat, now), tat)
+        } else {
+            (self.decision(true, new_tat, now), new_tat)
+        }
+    }
+
+    /// Creates a `Decision` from the result of GCRA.
+    ///
+    /// `tat` is the updated TAT if the request is allowed, and otherwise the current one.
+    fn decision(&self, allowed: bool, tat: u64, now: u64) -> Decision {
+        let tat = tat.max(now);
+        let reset_after = micros_to_duration(tat - now);
+        if allowed {
+            let remaining = self.tolerance().saturating_sub(tat - now) / self.interval;
+            Decision {
+                allowed,
+                limit: self.limit,
+                remaining: remaining as u32,
+                reset_after,
+                retry_after: None,
+            }
+        } else {
+            let allow_at = (tat + self.interval).saturating_sub(self.tolerance());
+            Decision {
+                allowed,
+                limit: self.limit,
+                remaining: 0,
+                reset_after,
+                retry_after: Some(micros_to_duration(allow_at.saturating_sub(now))),
+            }
+        }
+    }
+}
+
+/// The result of checking the quota for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset_after: Duration,
+    retry_after: Option<Duration>,
+}
+
+impl Decision {
+    /// Returns whether the request is allowed or not.
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+
+    /// Returns the maximum number of requests permitted to be sent at once.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Returns the number of requests that can be sent immediately.
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+
+    /// Returns the duration until the quota is fully replenished.
+    pub fn reset_after(&self) -> Duration {
+        self.reset_after
+    }
+
+    /// Returns the duration until the next request will be allowed, if the request is rejected.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+/// Returns the current time in microseconds since the UNIX epoch.
+pub fn now_micros() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+    now.as_secs() * 1_000_000 + u64::from(now.subsec_micros())
+}
+
+fn micros_to_duration(micros: u64) -> Duration {
+    Duration::new(micros / 1_000_000, (micros % 1_000_000) as u32 * 1_000)
+}
@@ -0,0 +1,209 @@
+use {
+    crate::{Decision, Quota, Store},
+    futures::Future,
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        StatusCode,
+    },
+    std::{fmt, sync::Arc, time::Duration},
+    tsukuyomi::{
+        error::Error,
+        extractor::Extractor,
+        future::{Async, Poll, TryFuture},
+        handler::{AllowedMethods, Handler, ModifyHandler},
+        input::Input,
+    },
+};
+
+/// A `ModifyHandler` that limits the rate of requests per key.
+///
+/// The key is extracted from the request by the specified `Extractor`, for example
+/// the client IP address, the API key or the user ID stored in the session.
+///
+/// If the quota is exhausted, the request is rejected with `429 Too Many Requests`
+/// and the header field `Retry-After`.  The header fields `RateLimit-Limit`,
+/// `RateLimit-Remaining` and `RateLimit-Reset` are added to the responses.
+#[derive(Debug)]
+pub struct RateLimit<E, S> {
+    inner: Arc<Config<E, S>>,
+}
+
+#[derive(Debug)]
+struct Config<E, S> {
+    extractor: E,
+    quota: Quota,
+    store: S,
+    headers: bool,
+}
+
+impl<E, S> Clone for RateLimit<E, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<E, S> RateLimit<E, S>
+where
+    E: Extractor,
+    S: Store,
+{
+    /// Creates a `RateLimit` with the specified key extractor, quota and store.
+    pub fn new(extractor: E, quota: Quota, store: S) -> Self {
+        Self {
+            inner: Arc::new(Config {
+                extractor,
+                quota,
+                store,
+                headers: true,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Config<E, S> {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets whether to add the `RateLimit-*` header fields to the responses or not.
+    ///
+    /// The header field `Retry-After` is always added to the rejected responses.
+    /// The default value is `true`.
+    pub fn headers(mut self, enabled: bool) -> Self {
+        self.inner_mut().headers = enabled;
+        self
+    }
+}
+
+impl<E, K, S, H> ModifyHandler<H> for RateLimit<E, S>
+where
+    E: Extractor<Output = (K,)>,
+    K: fmt::Display,
+    S: Store,
+    H: Handler,
+{
+    type Output = H::Output;
+    type Handler = RateLimitHandler<E, S, H>; // private
+
+    fn modify(&self, inner: H) -> Self::Handler {
+        RateLimitHandler {
+            inner,
+            config: self.inner.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct RateLimitHandler<E, S, H> {
+    inner: H,
+    config: Arc<Config<E, S>>,
+}
+
+impl<E, K, S, H> Handler for RateLimitHandler<E, S, H>
+where
+    E: Extractor<Output = (K,)>,
+    K: fmt::Display,
+    S: Store,
+    H: Handler,
+{
+    type Output = H::Output;
+    type Error = Error;
+    type Handle = HandleRateLimit<E, S, H::Handle>;
+
+    fn handle(&self) -> Self::Handle {
+        HandleRateLimit {
+            state: State::Extract(self.config.extractor.extract()),
+            inner: self.inner.handle(),
+            config: self.config.clone(),
+        }
+    }
+
+    fn allowed_methods(&self) -> Option<&AllowedMethods> {
+        self.inner.allowed_methods()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HandleRateLimit<E: Extractor, S: Store, H> {
+    state: State<E::Extract, S::Check>,
+    inner: H,
+    config: Arc<Config<E, S>>,
+}
+
+enum State<E, C> {
+    Extract(E),
+    Check(C),
+    Handle,
+}
+
+impl<E, K, S, H> TryFuture for HandleRateLimit<E, S, H>
+where
+    E: Extractor<Output = (K,)>,
+    K: fmt::Display,
+    S: Store,
+    H: TryFuture,
+{
+    type Ok = H::Ok;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        loop {
+            self.state = match self.state {
+                State::Extract(ref mut extract) => {
+                    let (key,) = match extract.poll_ready(input) {
+                        Ok(Async::Ready(output)) => output,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(err) => return Err(err.into()),
+                    };
+                    State::Check(
+                        self.config
+                            .store
+                            .check(&key.to_string(), &self.config.quota),
+                    )
+                }
+                State::Check(ref mut check) => {
+                    let decision = match check.poll() {
+                        Ok(Async::Ready(decision)) => decision,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(err) => return Err(err.into()),
+                    };
+
+                    // The header fields are applied to the error responses as well.
+                    let headers = input.response_headers.get_or_insert_with(HeaderMap::new);
+                    if self.config.headers {
+                        insert_ratelimit_headers(headers, &decision);
+                    }
+                    if let Some(retry_after) = decision.retry_after() {
+                        headers.insert(RETRY_AFTER, ceil_secs(retry_after).into());
+                        return Err(tsukuyomi::error::custom(
+                            StatusCode::TOO_MANY_REQUESTS,
+                            "too many requests",
+                        ));
+                    }
+
+                    State::Handle
+                }
+                State::Handle => return self.inner.poll_ready(input).map_err(Into::into),
+            };
+        }
+    }
+}
+
+fn insert_ratelimit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(
+        HeaderName::from_static("ratelimit-limit"),
+        HeaderValue::from(decision.limit()),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-remaining"),
+        HeaderValue::from(decision.remaining()),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-reset"),
+        HeaderValue::from(ceil_secs(decision.reset_after())),
+    );
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
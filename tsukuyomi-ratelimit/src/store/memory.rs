@@ -0,0 +1,70 @@
+use {
+    crate::{now_micros, Decision, Quota, Store},
+    futures::future::{self, FutureResult},
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
+    tsukuyomi::util::Never,
+};
+
+/// The minimum number of entries that triggers the removal of expired entries.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// A `Store` that holds the states in the process memory.
+///
+/// The states are shared among the clones of this value, but not among the processes.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    tats: HashMap<String, u64>,
+    prune_at: usize,
+}
+
+impl MemoryStore {
+    /// Creates a new `MemoryStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of keys whose state is currently stored.
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .tats
+            .len()
+    }
+
+    /// Returns `true` if no state is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Store for MemoryStore {
+    type Error = Never;
+    type Check = FutureResult<Decision, Never>;
+
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check {
+        let now = now_micros();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Entries whose TAT has passed are equivalent to the absent ones,
+        // so they are removed once the map has grown enough.
+        if inner.tats.len() >= inner.prune_at.max(PRUNE_THRESHOLD) {
+            inner.tats.retain(|_, tat| *tat > now);
+            inner.prune_at = inner.tats.len() * 2;
+        }
+
+        let (decision, tat) = quota.apply(inner.tats.get(key).cloned(), now);
+        if decision.is_allowed() {
+            inner.tats.insert(key.to_owned(), tat);
+        }
+        future::ok(decision)
+    }
+}
@@ -0,0 +1,8 @@
+//! The definition of stores for rate limiting states.
+
+mod memory;
+mod redis;
+
+pub use self::memory::MemoryStore;
+#[cfg(feature = "use-redis")]
+pub use self::redis::RedisStore;
@@ -0,0 +1,194 @@
+#![cfg(feature = "use-redis")]
+
+use {
+    crate::{now_micros, Decision, Quota, Store},
+    futures::{try_ready, Async, Future, Poll},
+    redis::{r#async::Connection, Client, RedisFuture},
+    std::{
+        borrow::Cow,
+        fmt,
+        sync::{Arc, Mutex},
+    },
+    tsukuyomi::error::Error,
+};
+
+/// The script to apply GCRA atomically.
+///
+/// KEYS[1]: the key name, ARGV[1]: now, ARGV[2]: emission interval, ARGV[3]: tolerance.
+/// Returns the pair of the decision (1 or 0) and the resulting TAT.
+const GCRA_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2])
+local tolerance = tonumber(ARGV[3])
+local tat = tonumber(redis.call('GET', KEYS[1]))
+if tat == nil or tat < now then
+  tat = now
+end
+local new_tat = tat + interval
+if new_tat - now > tolerance then
+  return {0, tat}
+end
+redis.call('SET', KEYS[1], string.format('%d', new_tat),
+           'PX', math.ceil((new_tat - now) / 1000))
+return {1, new_tat}
+"#;
+
+/// The default value of the maximum number of idle connections.
+const DEFAULT_MAX_IDLE: usize = 16;
+
+/// A `Store` using Redis.
+///
+/// The states are shared among the processes connected to the same Redis server.
+/// Each state is stored with the expiration until the quota is fully replenished.
+///
+/// The connections are kept in a pool after the checks are completed, and
+/// reused by the subsequent checks. The connections which caused an error
+/// are discarded rather than returned to the pool.
+#[derive(Debug, Clone)]
+pub struct RedisStore {
+    inner: Arc<RedisStoreInner>,
+}
+
+struct RedisStoreInner {
+    client: Client,
+    key_prefix: Cow<'static, str>,
+    max_idle: usize,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl fmt::Debug for RedisStoreInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStoreInner")
+            .field("client", &self.client)
+            .field("key_prefix", &self.key_prefix)
+            .field("max_idle", &self.max_idle)
+            .finish()
+    }
+}
+
+impl RedisStoreInner {
+    fn acquire(&self) -> Option<Connection> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop()
+    }
+
+    fn release(&self, conn: Connection) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < self.max_idle {
+            idle.push(conn);
+        }
+    }
+}
+
+impl RedisStore {
+    /// Create a new `RedisStore` from the specified Redis client.
+    pub fn new(client: Client) -> Self {
+        Self {
+            inner: Arc::new(RedisStoreInner {
+                client,
+                key_prefix: "tsukuyomi-ratelimit".into(),
+                max_idle: DEFAULT_MAX_IDLE,
+                idle: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut RedisStoreInner {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the prefix of key name used at storing the states in Redis.
+    ///
+    /// The default value is `"tsukuyomi-ratelimit"`.
+    pub fn key_prefix(mut self, prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.inner_mut().key_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the maximum number of idle connections kept in the pool.
+    ///
+    /// The default value is `16`.
+    pub fn max_idle_connections(mut self, max_idle: usize) -> Self {
+        self.inner_mut().max_idle = max_idle;
+        self
+    }
+}
+
+impl Store for RedisStore {
+    type Error = Error;
+    type Check = Check;
+
+    fn check(&self, key: &str, quota: &Quota) -> Self::Check {
+        let key_name = format!("{}:{}", self.inner.key_prefix, key);
+        let now = now_micros();
+        let state = match self.inner.acquire() {
+            Some(conn) => CheckState::Eval(eval(conn, &key_name, quota, now)),
+            None => CheckState::Connecting(self.inner.client.get_async_connection()),
+        };
+        Check {
+            inner: self.inner.clone(),
+            state,
+            key_name,
+            quota: *quota,
+            now,
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct Check {
+    inner: Arc<RedisStoreInner>,
+    state: CheckState,
+    key_name: String,
+    quota: Quota,
+    now: u64,
+}
+
+fn eval(
+    conn: Connection,
+    key_name: &str,
+    quota: &Quota,
+    now: u64,
+) -> RedisFuture<(Connection, (i64, i64))> {
+    redis::cmd("EVAL")
+        .arg(GCRA_SCRIPT)
+        .arg(1)
+        .arg(key_name)
+        .arg(now)
+        .arg(quota.emission_interval())
+        .arg(quota.tolerance())
+        .query_async(conn)
+}
+
+enum CheckState {
+    Connecting(RedisFuture<Connection>),
+    Eval(RedisFuture<(Connection, (i64, i64))>),
+}
+
+impl Future for Check {
+    type Item = Decision;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match self.state {
+                CheckState::Connecting(ref mut future) => {
+                    let conn = try_ready!(future
+                        .poll()
+                        .map_err(tsukuyomi::error::internal_server_error));
+                    CheckState::Eval(eval(conn, &self.key_name, &self.quota, self.now))
+                }
+                CheckState::Eval(ref mut future) => {
+                    let (conn, (allowed, tat)) = try_ready!(future
+                        .poll()
+                        .map_err(tsukuyomi::error::internal_server_error));
+                    self.inner.release(conn);
+                    return Ok(Async::Ready(self.quota.decision(
+                        allowed != 0,
+                        tat as u64,
+                        self.now,
+                    )));
+                }
+            };
+        }
+    }
+}
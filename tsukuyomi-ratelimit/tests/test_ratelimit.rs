@@ -0,0 +1,135 @@
+use {
+    http::Request,
+    std::time::Duration,
+    tsukuyomi::{config::prelude::*, App},
+    tsukuyomi_ratelimit::{store::MemoryStore, Quota, RateLimit},
+    tsukuyomi_server::test::ResponseExt,
+};
+
+#[test]
+fn test_version_sync() {
+    version_sync::assert_html_root_url_updated!("src/lib.rs");
+}
+
+#[test]
+fn test_quota() {
+    let quota = Quota::new(2, Duration::from_secs(10));
+    let now = 1_000_000_000;
+
+    let (decision, tat) = quota.apply(None, now);
+    assert!(decision.is_allowed());
+    assert_eq!(decision.remaining(), 1);
+    assert_eq!(decision.reset_after(), Duration::from_secs(5));
+
+    let (decision, tat) = quota.apply(Some(tat), now);
+    assert!(decision.is_allowed());
+    assert_eq!(decision.remaining(), 0);
+    assert_eq!(decision.reset_after(), Duration::from_secs(10));
+
+    let (decision, _) = quota.apply(Some(tat), now + 1_000_000);
+    assert!(!decision.is_allowed());
+    assert_eq!(decision.retry_after(), Some(Duration::from_secs(4)));
+
+    let (decision, _) = quota.apply(Some(tat), now + 5_000_000);
+    assert!(decision.is_allowed());
+    assert_eq!(decision.remaining(), 0);
+}
+
+#[test]
+fn test_rate_limit() -> tsukuyomi_server::Result<()> {
+    let api_key = tsukuyomi::extractor::ready(|input| {
+        Ok::<_, tsukuyomi::Error>((input
+            .request
+            .headers()
+            .get("x-api-key")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("anonymous")
+            .to_owned(),))
+    });
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::reply("hello"))
+            .modify(RateLimit::new(
+                api_key,
+                Quota::per_hour(2),
+                MemoryStore::new(),
+            )),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("ratelimit-limit")?, "2");
+    assert_eq!(response.header("ratelimit-remaining")?, "1");
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("ratelimit-remaining")?, "0");
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.header("ratelimit-remaining")?, "0");
+    assert_eq!(response.header("retry-after")?, "1800");
+
+    // the quota is tracked per key.
+    let response = server.perform(Request::get("/").header("x-api-key", "alice"))?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "hello");
+
+    Ok(())
+}
+
+/// Runs against the Redis server specified by `REDIS_URL` (default: `redis://127.0.0.1/`):
+///
+/// ```sh
+/// cargo test -p tsukuyomi-ratelimit --features use-redis -- --ignored
+/// ```
+#[cfg(feature = "use-redis")]
+#[test]
+#[ignore]
+fn test_redis_store() -> redis::RedisResult<()> {
+    use {
+        futures::Future,
+        tsukuyomi_ratelimit::{store::RedisStore, Store},
+    };
+
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".into());
+    let client = redis::Client::open(url.as_str())?;
+    let prefix = format!("tsukuyomi-ratelimit-test-{}", std::process::id());
+    let store = RedisStore::new(client.clone()).key_prefix(prefix.clone());
+    let quota = Quota::new(2, Duration::from_secs(10));
+
+    let check = |key: &str| store.check(key, &quota).wait().expect("failed to check");
+
+    let decision = check("alice");
+    assert!(decision.is_allowed());
+    assert_eq!(decision.remaining(), 1);
+
+    let decision = check("alice");
+    assert!(decision.is_allowed());
+    assert_eq!(decision.remaining(), 0);
+
+    let decision = check("alice");
+    assert!(!decision.is_allowed());
+    let retry_after = decision.retry_after().expect("should be rejected");
+    assert!(retry_after > Duration::from_secs(4) && retry_after <= Duration::from_secs(5));
+
+    // the states are independent among the keys.
+    assert!(check("bob").is_allowed());
+
+    // the TAT is stored as an integer with the expiration until the quota is replenished.
+    let conn = client.get_connection()?;
+    let key = format!("{}:alice", prefix);
+    let tat: u64 = redis::cmd("GET").arg(&key).query(&conn)?;
+    assert!(tat > tsukuyomi_ratelimit::now_micros());
+    let ttl: i64 = redis::cmd("PTTL").arg(&key).query(&conn)?;
+    assert!(ttl > 9_000 && ttl <= 10_000, "ttl = {}", ttl);
+
+    redis::cmd("DEL")
+        .arg(&key)
+        .arg(format!("{}:bob", prefix))
+        .execute(&conn);
+
+    Ok(())
+}
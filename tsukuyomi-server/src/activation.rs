@@ -0,0 +1,341 @@
+//! Components for using the listeners inherited from the parent process.
+//!
+//! The file descriptors are passed according to the protocol of [systemd socket activation],
+//! which is also used by other tools such as [`systemfd`].
+//!
+//! [systemd socket activation]: https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html
+//! [`systemfd`]: https://github.com/mitsuhiko/systemfd
+
+use std::{
+    env, io, mem,
+    net::TcpListener,
+    os::unix::{
+        io::{FromRawFd, IntoRawFd, RawFd},
+        net::UnixListener,
+    },
+};
+
+/// The first file descriptor passed by the socket activation.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// A set of file descriptors inherited from the parent process.
+///
+/// The listeners taken from this value are the standard `TcpListener` and `UnixListener`,
+/// which can be passed to `Server::bind` directly.
+/// The file descriptors that have not been taken are closed when this value is dropped.
+#[derive(Debug, Default)]
+pub struct ListenFds {
+    fds: Vec<Option<RawFd>>,
+    names: Vec<String>,
+}
+
+impl ListenFds {
+    /// Collects the file descriptors from the environment variables `LISTEN_FDS`,
+    /// `LISTEN_PID` and `LISTEN_FDNAMES`.
+    ///
+    /// If the variables are missing or they are not addressed to the current process,
+    /// the returned value contains no file descriptors and the variables are left
+    /// untouched.  Otherwise, the variables are removed from the environment and
+    /// the file descriptors are marked as close-on-exec, so that they are not
+    /// inherited by the child processes.
+    pub fn from_env() -> io::Result<Self> {
+        let pid: u32 = match env::var("LISTEN_PID").ok() {
+            Some(pid) => pid.parse().map_err(invalid_data)?,
+            None => return Ok(Self::default()),
+        };
+        if pid != std::process::id() {
+            return Ok(Self::default());
+        }
+
+        let num_fds = env::var("LISTEN_FDS").ok();
+        let names = env::var("LISTEN_FDNAMES").ok();
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+
+        unsafe { Self::from_listen_fds(LISTEN_FDS_START, num_fds, names) }
+    }
+
+    /// Creates a `ListenFds` from the values of `LISTEN_FDS` and `LISTEN_FDNAMES`.
+    ///
+    /// The caller must guarantee the same conditions as `from_raw_fds`.
+    unsafe fn from_listen_fds(
+        start: RawFd,
+        num_fds: Option<String>,
+        names: Option<String>,
+    ) -> io::Result<Self> {
+        let num_fds: RawFd = match num_fds {
+            Some(num_fds) => num_fds.parse().map_err(invalid_data)?,
+            None => return Ok(Self::default()),
+        };
+
+        let mut listen_fds = Self::from_raw_fds(start..start + num_fds);
+        listen_fds.names = names
+            .map(|names| names.split(':').map(ToOwned::to_owned).collect())
+            .unwrap_or_default();
+        for fd in listen_fds.fds.iter().filter_map(|&fd| fd) {
+            set_cloexec(fd)?;
+        }
+        Ok(listen_fds)
+    }
+
+    /// Creates a `ListenFds` from the specified raw file descriptors.
+    ///
+    /// The ownership of the file descriptors is transferred to the returned value.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the file descriptors are valid and
+    /// are not owned by any other values.
+    pub unsafe fn from_raw_fds(fds: impl IntoIterator<Item = RawFd>) -> Self {
+        Self {
+            fds: fds.into_iter().map(Some).collect(),
+            names: vec![],
+        }
+    }
+
+    /// Returns the number of passed file descriptors, including the already taken ones.
+    pub fn len(&self) -> usize {
+        self.fds.len()
+    }
+
+    /// Returns `true` if no file descriptors are passed.
+    pub fn is_empty(&self) -> bool {
+        self.fds.is_empty()
+    }
+
+    /// Returns the name of file descriptor at the specified position, if available.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(|s| s.as_str())
+    }
+
+    /// Takes a TCP listener at the specified position.
+    ///
+    /// It returns a `None` if the file descriptor has already been taken or is out of range,
+    /// and an error if the file descriptor is not a listening TCP socket.
+    pub fn take_tcp_listener(&mut self, index: usize) -> io::Result<Option<TcpListener>> {
+        self.take_with(index, |fd| {
+            check_listening_stream(fd).map_err(|err| (fd, err))?;
+            let listener = unsafe { TcpListener::from_raw_fd(fd) };
+            match listener.local_addr() {
+                Ok(..) => Ok(listener),
+                Err(err) => Err((listener.into_raw_fd(), err)),
+            }
+        })
+    }
+
+    /// Takes a Unix domain socket listener at the specified position.
+    ///
+    /// It returns a `None` if the file descriptor has already been taken or is out of range,
+    /// and an error if the file descriptor is not a listening Unix domain socket.
+    pub fn take_unix_listener(&mut self, index: usize) -> io::Result<Option<UnixListener>> {
+        self.take_with(index, |fd| {
+            check_listening_stream(fd).map_err(|err| (fd, err))?;
+            let listener = unsafe { UnixListener::from_raw_fd(fd) };
+            match listener.local_addr() {
+                Ok(..) => Ok(listener),
+                Err(err) => Err((listener.into_raw_fd(), err)),
+            }
+        })
+    }
+
+    /// Takes a TCP listener whose name is equal to `name`.
+    ///
+    /// The name is specified with `FileDescriptorName=` in the socket unit of systemd.
+    pub fn take_tcp_listener_by_name(&mut self, name: &str) -> io::Result<Option<TcpListener>> {
+        match self.position(name) {
+            Some(index) => self.take_tcp_listener(index),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes a Unix domain socket listener whose name is equal to `name`.
+    ///
+    /// The name is specified with `FileDescriptorName=` in the socket unit of systemd.
+    pub fn take_unix_listener_by_name(&mut self, name: &str) -> io::Result<Option<UnixListener>> {
+        match self.position(name) {
+            Some(index) => self.take_unix_listener(index),
+            None => Ok(None),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        (0..self.names.len())
+            .find(|&i| self.names[i] == name && self.fds.get(i).map_or(false, Option::is_some))
+    }
+
+    fn take_with<T>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(RawFd) -> Result<T, (RawFd, io::Error)>,
+    ) -> io::Result<Option<T>> {
+        let slot = match self.fds.get_mut(index) {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        let fd = match slot.take() {
+            Some(fd) => fd,
+            None => return Ok(None),
+        };
+        match f(fd) {
+            Ok(listener) => Ok(Some(listener)),
+            Err((fd, err)) => {
+                // restore the file descriptor so that it can be taken with another type.
+                *slot = Some(fd);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for ListenFds {
+    fn drop(&mut self) {
+        for fd in self.fds.drain(..).filter_map(|fd| fd) {
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Checks if the file descriptor is a stream socket in the listening state.
+///
+/// The address family is checked by the conversion to the listener.
+fn check_listening_stream(fd: RawFd) -> io::Result<()> {
+    if getsockopt_int(fd, libc::SO_TYPE)? != libc::SOCK_STREAM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the file descriptor is not a stream socket",
+        ));
+    }
+    if getsockopt_int(fd, libc::SO_ACCEPTCONN)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the socket is not listening",
+        ));
+    }
+    Ok(())
+}
+
+fn getsockopt_int(fd: RawFd, name: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::ListenFds,
+        std::{
+            env,
+            net::{TcpListener, UdpSocket},
+            os::unix::io::{AsRawFd, RawFd},
+        },
+    };
+
+    fn dup_to(fd: RawFd, target: RawFd) {
+        assert_eq!(unsafe { libc::dup2(fd, target) }, target);
+    }
+
+    fn is_open(fd: RawFd) -> bool {
+        unsafe { libc::fcntl(fd, libc::F_GETFD) != -1 }
+    }
+
+    #[test]
+    fn from_listen_fds() -> std::io::Result<()> {
+        // the descriptors are placed at the high numbers in order not to
+        // conflict with the ones used by the test harness.
+        const START: RawFd = 1000;
+
+        let tcp = TcpListener::bind("127.0.0.1:0")?;
+        let udp = UdpSocket::bind("127.0.0.1:0")?;
+        let local_addr = tcp.local_addr()?;
+        dup_to(tcp.as_raw_fd(), START);
+        dup_to(udp.as_raw_fd(), START + 1);
+        dup_to(tcp.as_raw_fd(), START + 2);
+
+        let mut fds = unsafe {
+            ListenFds::from_listen_fds(START, Some("3".into()), Some("http:udp:admin".into()))?
+        };
+        assert_eq!(fds.len(), 3);
+        assert_eq!(fds.name(1), Some("udp"));
+        for fd in START..START + 3 {
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+            assert_ne!(flags & libc::FD_CLOEXEC, 0);
+        }
+
+        // a datagram socket is not accepted as a listener.
+        assert!(fds.take_tcp_listener_by_name("udp").is_err());
+        assert!(fds.take_unix_listener(1).is_err());
+
+        assert!(fds.take_tcp_listener_by_name("unknown")?.is_none());
+        let admin = fds
+            .take_tcp_listener_by_name("admin")?
+            .expect("should be available");
+        assert_eq!(admin.local_addr()?, local_addr);
+        assert!(fds.take_tcp_listener_by_name("admin")?.is_none());
+
+        // the descriptors which have not been taken are closed on drop.
+        drop(fds);
+        assert!(!is_open(START));
+        assert!(!is_open(START + 1));
+        assert!(is_open(START + 2));
+        drop(admin);
+        assert!(!is_open(START + 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_env() -> std::io::Result<()> {
+        let other_pid = (std::process::id() + 1).to_string();
+        env::set_var("LISTEN_PID", &other_pid);
+        env::set_var("LISTEN_FDS", "0");
+        env::set_var("LISTEN_FDNAMES", "");
+
+        // the variables addressed to another process are left as is.
+        let fds = ListenFds::from_env()?;
+        assert!(fds.is_empty());
+        assert_eq!(env::var("LISTEN_PID").ok(), Some(other_pid));
+        assert!(env::var("LISTEN_FDS").is_ok());
+
+        env::set_var("LISTEN_PID", std::process::id().to_string());
+        let fds = ListenFds::from_env()?;
+        assert!(fds.is_empty());
+        assert!(env::var("LISTEN_PID").is_err());
+        assert!(env::var("LISTEN_FDS").is_err());
+        assert!(env::var("LISTEN_FDNAMES").is_err());
+
+        Ok(())
+    }
+}
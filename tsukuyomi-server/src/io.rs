@@ -55,6 +55,322 @@ where
     }
 }
 
+/// An asynchronous I/O that is either one of the two types.
+///
+/// This type is used as the connection type of a pair of listeners, such as
+/// `(SocketAddr, PathBuf)`.
+#[derive(Debug)]
+pub enum Either<L, R> {
+    /// The I/O from the first listener.
+    Left(L),
+    /// The I/O from the second listener.
+    Right(R),
+}
+
+mod either {
+    use {
+        super::Either,
+        futures::Poll,
+        std::io::{self, Read, Write},
+        tokio::io::{AsyncRead, AsyncWrite},
+    };
+
+    impl<L, R> Read for Either<L, R>
+    where
+        L: Read,
+        R: Read,
+    {
+        #[inline]
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self {
+                Either::Left(l) => l.read(buf),
+                Either::Right(r) => r.read(buf),
+            }
+        }
+    }
+
+    impl<L, R> Write for Either<L, R>
+    where
+        L: Write,
+        R: Write,
+    {
+        #[inline]
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self {
+                Either::Left(l) => l.write(buf),
+                Either::Right(r) => r.write(buf),
+            }
+        }
+
+        #[inline]
+        fn flush(&mut self) -> io::Result<()> {
+            match self {
+                Either::Left(l) => l.flush(),
+                Either::Right(r) => r.flush(),
+            }
+        }
+    }
+
+    impl<L, R> AsyncRead for Either<L, R>
+    where
+        L: AsyncRead,
+        R: AsyncRead,
+    {
+        #[inline]
+        unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+            match self {
+                Either::Left(l) => l.prepare_uninitialized_buffer(buf),
+                Either::Right(r) => r.prepare_uninitialized_buffer(buf),
+            }
+        }
+    }
+
+    impl<L, R> AsyncWrite for Either<L, R>
+    where
+        L: AsyncWrite,
+        R: AsyncWrite,
+    {
+        #[inline]
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            match self {
+                Either::Left(l) => l.shutdown(),
+                Either::Right(r) => r.shutdown(),
+            }
+        }
+    }
+}
+
+mod pair {
+    use {
+        super::{Acceptor, Either, Listener},
+        crate::CritError,
+        futures::{Async, Future, Poll, Stream},
+    };
+
+    /// The implementation of `Listener` for serving two listeners at once.
+    ///
+    /// The incoming connections from both listeners are merged into a single
+    /// stream, so they share the same `MakeService` and are shut down together.
+    /// More than two listeners of different types can be combined by nesting
+    /// the tuples, and the ones of the same type by using `Vec`.
+    impl<L1, L2> Listener for (L1, L2)
+    where
+        L1: Listener,
+        L2: Listener,
+    {
+        type Conn = Either<L1::Conn, L2::Conn>;
+        type Error = CritError;
+        type Incoming = Incoming<L1::Incoming, L2::Incoming>;
+
+        fn listen(self) -> Result<Self::Incoming, Self::Error> {
+            let left = self.0.listen().map_err(Into::into)?;
+            let right = self.1.listen().map_err(Into::into)?;
+            Ok(Incoming {
+                left: Some(left),
+                right: Some(right),
+                left_first: true,
+            })
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct Incoming<S1, S2> {
+        left: Option<S1>,
+        right: Option<S2>,
+        left_first: bool,
+    }
+
+    impl<S1, S2> Incoming<S1, S2>
+    where
+        S1: Stream,
+        S2: Stream,
+        S1::Error: Into<CritError>,
+        S2::Error: Into<CritError>,
+    {
+        fn poll_left(&mut self) -> Poll<Option<Either<S1::Item, S2::Item>>, CritError> {
+            let polled = match self.left {
+                Some(ref mut left) => {
+                    futures::try_ready!(left.poll().map_err(Into::<CritError>::into))
+                }
+                None => return Ok(Async::Ready(None)),
+            };
+            if polled.is_none() {
+                self.left = None;
+            }
+            Ok(Async::Ready(polled.map(Either::Left)))
+        }
+
+        fn poll_right(&mut self) -> Poll<Option<Either<S1::Item, S2::Item>>, CritError> {
+            let polled = match self.right {
+                Some(ref mut right) => {
+                    futures::try_ready!(right.poll().map_err(Into::<CritError>::into))
+                }
+                None => return Ok(Async::Ready(None)),
+            };
+            if polled.is_none() {
+                self.right = None;
+            }
+            Ok(Async::Ready(polled.map(Either::Right)))
+        }
+    }
+
+    impl<S1, S2> Stream for Incoming<S1, S2>
+    where
+        S1: Stream,
+        S2: Stream,
+        S1::Error: Into<CritError>,
+        S2::Error: Into<CritError>,
+    {
+        type Item = Either<S1::Item, S2::Item>;
+        type Error = CritError;
+
+        fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+            // alternate the listener polled first, in order to avoid
+            // the starvation of the other one.
+            self.left_first = !self.left_first;
+
+            let mut all_done = true;
+            for &left in &[self.left_first, !self.left_first] {
+                let polled = if left {
+                    self.poll_left()?
+                } else {
+                    self.poll_right()?
+                };
+                match polled {
+                    Async::Ready(Some(io)) => return Ok(Async::Ready(Some(io))),
+                    Async::Ready(None) => {}
+                    Async::NotReady => all_done = false,
+                }
+            }
+
+            if all_done {
+                Ok(Async::Ready(None))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+    }
+
+    /// The implementation of `Acceptor` for the connections from a pair of listeners.
+    ///
+    /// The connections from the first listener are converted by the first acceptor,
+    /// and the ones from the second listener by the second acceptor.
+    impl<T1, T2, A1, A2> Acceptor<Either<T1, T2>> for (A1, A2)
+    where
+        A1: Acceptor<T1>,
+        A2: Acceptor<T2>,
+        A1::Error: Into<CritError>,
+        A2::Error: Into<CritError>,
+    {
+        type Conn = Either<A1::Conn, A2::Conn>;
+        type Error = CritError;
+        type Accept = Accept<A1::Accept, A2::Accept>;
+
+        #[inline]
+        fn accept(&self, io: Either<T1, T2>) -> Self::Accept {
+            match io {
+                Either::Left(io) => Accept(Either::Left(self.0.accept(io))),
+                Either::Right(io) => Accept(Either::Right(self.1.accept(io))),
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct Accept<F1, F2>(Either<F1, F2>);
+
+    impl<F1, F2> Future for Accept<F1, F2>
+    where
+        F1: Future,
+        F2: Future,
+        F1::Error: Into<CritError>,
+        F2::Error: Into<CritError>,
+    {
+        type Item = Either<F1::Item, F2::Item>;
+        type Error = CritError;
+
+        #[inline]
+        fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+            match self.0 {
+                Either::Left(ref mut f) => {
+                    f.poll().map(|x| x.map(Either::Left)).map_err(Into::into)
+                }
+                Either::Right(ref mut f) => {
+                    f.poll().map(|x| x.map(Either::Right)).map_err(Into::into)
+                }
+            }
+        }
+    }
+}
+
+mod vec {
+    use {
+        super::Listener,
+        futures::{Async, Poll, Stream},
+    };
+
+    /// The implementation of `Listener` for serving an arbitrary number of listeners at once.
+    ///
+    /// As with the pair of listeners, the incoming connections are merged into
+    /// a single stream and the listeners are shut down together.
+    impl<L> Listener for Vec<L>
+    where
+        L: Listener,
+    {
+        type Conn = L::Conn;
+        type Error = L::Error;
+        type Incoming = Incoming<L::Incoming>;
+
+        fn listen(self) -> Result<Self::Incoming, Self::Error> {
+            let streams = self
+                .into_iter()
+                .map(|listener| listener.listen().map(Some))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Incoming { streams, next: 0 })
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct Incoming<S> {
+        streams: Vec<Option<S>>,
+        next: usize,
+    }
+
+    impl<S> Stream for Incoming<S>
+    where
+        S: Stream,
+    {
+        type Item = S::Item;
+        type Error = S::Error;
+
+        fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+            // start polling from the listener next to the last ready one, in order
+            // to avoid the starvation of the others.
+            let len = self.streams.len();
+            for n in 0..len {
+                let i = (self.next + n) % len;
+                let polled = match self.streams[i] {
+                    Some(ref mut stream) => stream.poll()?,
+                    None => continue,
+                };
+                match polled {
+                    Async::Ready(Some(io)) => {
+                        self.next = (i + 1) % len;
+                        return Ok(Async::Ready(Some(io)));
+                    }
+                    Async::Ready(None) => self.streams[i] = None,
+                    Async::NotReady => {}
+                }
+            }
+
+            if self.streams.iter().all(Option::is_none) {
+                Ok(Async::Ready(None))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
 mod tcp {
     use {
         super::Listener,
@@ -13,14 +13,19 @@
 )]
 #![forbid(clippy::unimplemented)]
 
+#[cfg(unix)]
+pub mod activation;
 mod error;
 mod io;
+pub mod peer;
 pub mod rt;
 pub mod test;
+#[cfg(any(feature = "use-rustls", feature = "use-openssl"))]
+pub mod tls;
 
 pub use crate::{
     error::{Error, Result},
-    io::{Acceptor, Listener},
+    io::{Acceptor, Either, Listener},
 };
 
 use {
@@ -30,7 +35,7 @@ use {
         body::{Body, Payload},
         server::conn::Http,
     },
-    std::{marker::PhantomData, net::SocketAddr, rc::Rc, sync::Arc},
+    std::{fmt, marker::PhantomData, net::SocketAddr, rc::Rc, sync::Arc},
     tsukuyomi_service::{MakeServiceRef, Service},
 };
 
@@ -44,6 +49,15 @@ pub struct Server<S, L = SocketAddr, A = (), R = tokio::runtime::Runtime> {
     acceptor: A,
     protocol: Http,
     runtime: Option<R>,
+    shutdown: Option<ShutdownSignal>,
+}
+
+struct ShutdownSignal(Box<dyn Future<Item = (), Error = ()> + Send + 'static>);
+
+impl fmt::Debug for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownSignal").finish()
+    }
 }
 
 impl<S> Server<S> {
@@ -55,6 +69,7 @@ impl<S> Server<S> {
             acceptor: (),
             protocol: Http::new(),
             runtime: None,
+            shutdown: None,
         }
     }
 }
@@ -63,6 +78,11 @@ impl<S, L, A, R> Server<S, L, A, R> {
     /// Sets the transport used by the server.
     ///
     /// By default, a TCP transport with the listener address `"127.0.0.1:4000"` is set.
+    ///
+    /// Multiple listeners can be served at the same time by passing a pair of them,
+    /// e.g. `(addr, path)`. In that case, the acceptor can also be specified as
+    /// a pair whose elements are applied to the connections from each listener.
+    /// An arbitrary number of listeners of the same type can be passed as a `Vec`.
     pub fn bind<L2>(self, listener: L2) -> Server<S, L2, A, R>
     where
         L2: Listener,
@@ -73,6 +93,7 @@ impl<S, L, A, R> Server<S, L, A, R> {
             acceptor: self.acceptor,
             protocol: self.protocol,
             runtime: self.runtime,
+            shutdown: self.shutdown,
         }
     }
 
@@ -91,6 +112,7 @@ impl<S, L, A, R> Server<S, L, A, R> {
             acceptor,
             protocol: self.protocol,
             runtime: self.runtime,
+            shutdown: self.shutdown,
         }
     }
 
@@ -109,6 +131,7 @@ impl<S, L, A, R> Server<S, L, A, R> {
             acceptor: self.acceptor,
             protocol: self.protocol,
             runtime: Some(runtime),
+            shutdown: self.shutdown,
         }
     }
 
@@ -122,6 +145,22 @@ impl<S, L, A, R> Server<S, L, A, R> {
             acceptor: self.acceptor,
             protocol: self.protocol,
             runtime: None,
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Sets a `Future` that signals the server to shut down.
+    ///
+    /// When the future completes, all listeners bound to the server stop
+    /// accepting connections together, and `run` returns after the established
+    /// connections are closed.
+    pub fn shutdown_signal<F>(self, signal: F) -> Self
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        Self {
+            shutdown: Some(ShutdownSignal(Box::new(signal))),
+            ..self
         }
     }
 }
@@ -209,6 +248,7 @@ where
             ),
             spawn: |future| crate::rt::spawn(future),
         };
+        let serve = with_shutdown(serve, self.shutdown);
 
         runtime.spawn(serve);
         runtime.shutdown_on_idle().wait().unwrap();
@@ -248,6 +288,7 @@ where
             ),
             spawn: |future| tokio::runtime::current_thread::spawn(future),
         };
+        let serve = with_shutdown(serve, self.shutdown);
 
         let _ = runtime.block_on(serve);
         runtime.run()?;
@@ -256,6 +297,22 @@ where
     }
 }
 
+/// Stops the server task, and therefore drops the listeners, when the shutdown signal is received.
+fn with_shutdown<F>(
+    serve: F,
+    shutdown: Option<ShutdownSignal>,
+) -> impl Future<Item = (), Error = ()>
+where
+    F: Future<Item = (), Error = ()>,
+{
+    match shutdown {
+        Some(ShutdownSignal(signal)) => {
+            futures::future::Either::A(serve.select(signal).map(|_| ()).map_err(|_| ()))
+        }
+        None => futures::future::Either::B(serve),
+    }
+}
+
 #[allow(missing_debug_implementations)]
 struct LiftedHttpService<S> {
     service: S,
@@ -0,0 +1,149 @@
+//! Components for exposing the address of the peer to the services.
+
+use {
+    crate::io::Either,
+    futures::{
+        future::{self, FutureResult},
+        Poll,
+    },
+    http::Request,
+    std::{fmt, io, net::SocketAddr},
+    tsukuyomi_service::{ModifyService, Service},
+};
+
+/// A trait representing the connections which can provide the address of the peer.
+pub trait RemoteAddr {
+    /// Returns the address of the peer, if available.
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>>;
+}
+
+impl RemoteAddr for tokio::net::TcpStream {
+    #[inline]
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        self.peer_addr().map(Some)
+    }
+}
+
+#[cfg(unix)]
+impl RemoteAddr for tokio::net::UnixStream {
+    #[inline]
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+impl<L, R> RemoteAddr for Either<L, R>
+where
+    L: RemoteAddr,
+    R: RemoteAddr,
+{
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        match self {
+            Either::Left(l) => l.remote_addr(),
+            Either::Right(r) => r.remote_addr(),
+        }
+    }
+}
+
+#[cfg(feature = "use-native-tls")]
+impl<T> RemoteAddr for tokio_tls::TlsStream<T>
+where
+    T: RemoteAddr,
+{
+    #[inline]
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        self.get_ref().get_ref().remote_addr()
+    }
+}
+
+#[cfg(feature = "use-rustls")]
+impl<T> RemoteAddr for tokio_rustls::TlsStream<T, rustls::ServerSession>
+where
+    T: RemoteAddr,
+{
+    #[inline]
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        self.get_ref().0.remote_addr()
+    }
+}
+
+#[cfg(feature = "use-openssl")]
+impl<T> RemoteAddr for tokio_openssl::SslStream<T>
+where
+    T: RemoteAddr,
+{
+    #[inline]
+    fn remote_addr(&self) -> io::Result<Option<SocketAddr>> {
+        self.get_ref().get_ref().remote_addr()
+    }
+}
+
+/// Creates a `ModifyService` which inserts the address of the peer into the extension map
+/// of `Request` before calling the internal service.
+///
+/// The address is inserted as a value of `SocketAddr`, which is recorded by the access log
+/// of Tsukuyomi. Nothing is inserted if the connection does not have the peer address,
+/// e.g. if it is a Unix domain socket. If the address cannot be acquired, the connection
+/// is closed without calling the service.
+pub fn with_peer_addr() -> PeerAddr {
+    PeerAddr(())
+}
+
+/// A `ModifyService` created by `with_peer_addr`.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(());
+
+impl<'a, T, S, Bd> ModifyService<&'a T, Request<Bd>, S> for PeerAddr
+where
+    T: RemoteAddr,
+    S: Service<Request<Bd>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Service = WithPeerAddr<S>;
+    type ModifyError = io::Error;
+    type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+    fn modify_service(&self, service: S, io: &'a T) -> Self::Future {
+        future::result(
+            io.remote_addr()
+                .map(|peer_addr| WithPeerAddr { service, peer_addr }),
+        )
+    }
+}
+
+/// A `Service` created by `with_peer_addr`.
+pub struct WithPeerAddr<S> {
+    service: S,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl<S> fmt::Debug for WithPeerAddr<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithPeerAddr")
+            .field("peer_addr", &self.peer_addr)
+            .finish()
+    }
+}
+
+impl<S, Bd> Service<Request<Bd>> for WithPeerAddr<S>
+where
+    S: Service<Request<Bd>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    #[inline]
+    fn call(&mut self, mut request: Request<Bd>) -> Self::Future {
+        if let Some(peer_addr) = self.peer_addr {
+            request.extensions_mut().insert(peer_addr);
+        }
+        self.service.call(request)
+    }
+}
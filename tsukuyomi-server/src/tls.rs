@@ -0,0 +1,320 @@
+//! Components for configuring the TLS acceptors.
+
+#[cfg(feature = "use-openssl")]
+mod openssl;
+#[cfg(feature = "use-rustls")]
+mod rustls;
+
+#[cfg(feature = "use-openssl")]
+pub use self::openssl::set_client_auth;
+#[cfg(feature = "use-rustls")]
+pub use self::rustls::{CertResolver, CertResolverBuilder};
+
+use {
+    crate::io::Either,
+    futures::Poll,
+    http::Request,
+    std::{fmt, io, net::IpAddr, ops::Deref, path::PathBuf, sync::Arc},
+    tsukuyomi_service::{modify_service_ref, ModifyService, Service},
+};
+
+/// The configuration of client certificate verification (a.k.a. mutual TLS).
+#[derive(Debug, Clone)]
+pub struct ClientAuth {
+    ca_path: PathBuf,
+    required: bool,
+}
+
+impl ClientAuth {
+    /// Creates a `ClientAuth` that rejects the clients without a valid certificate
+    /// signed by one of the CAs in the specified PEM file.
+    pub fn required(ca_path: impl Into<PathBuf>) -> Self {
+        Self {
+            ca_path: ca_path.into(),
+            required: true,
+        }
+    }
+
+    /// Creates a `ClientAuth` that accepts anonymous clients as well.
+    ///
+    /// If the client sends a certificate, it must be signed by one of the CAs
+    /// in the specified PEM file.
+    pub fn optional(ca_path: impl Into<PathBuf>) -> Self {
+        Self {
+            ca_path: ca_path.into(),
+            required: false,
+        }
+    }
+
+    /// Returns the path of PEM file containing the trusted CA certificates.
+    pub fn ca_path(&self) -> &std::path::Path {
+        &self.ca_path
+    }
+
+    /// Returns whether the clients are required to send a certificate or not.
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+}
+
+/// An entry of the subject alternative names of a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    /// A DNS name.
+    DnsName(String),
+    /// An email address (`rfc822Name`).
+    Email(String),
+    /// A URI.
+    Uri(String),
+    /// An IP address.
+    IpAddress(IpAddr),
+}
+
+/// A verified certificate sent from the peer.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    der: Vec<u8>,
+    subject: String,
+    subject_alt_names: Vec<SubjectAltName>,
+    fingerprint: Vec<u8>,
+}
+
+impl PeerCertificate {
+    fn new(
+        der: Vec<u8>,
+        subject: String,
+        subject_alt_names: Vec<SubjectAltName>,
+        fingerprint: Vec<u8>,
+    ) -> Self {
+        Self {
+            der,
+            subject,
+            subject_alt_names,
+            fingerprint,
+        }
+    }
+
+    /// Returns the DER-encoded representation of this certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Returns the subject name of this certificate, formatted as `"C=JP, O=Example, CN=example.com"`.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Returns the subject alternative names of this certificate.
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+
+    /// Returns the SHA-256 fingerprint of this certificate.
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    /// Returns the SHA-256 fingerprint of this certificate as a colon-separated hex string.
+    pub fn fingerprint_hex(&self) -> String {
+        self.fingerprint
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+/// The certificate chain sent from the peer, which has been verified during the handshake.
+///
+/// The first element is the certificate of the peer itself.
+///
+/// When the server is configured with `with_peer_certificates`, the value of this type
+/// is inserted into the extension map of `Request`. With the feature `tsukuyomi` enabled,
+/// it can be extracted in the handlers by using `peer_certificates()`.
+#[derive(Debug, Clone)]
+pub struct PeerCertificateChain(Arc<Vec<PeerCertificate>>);
+
+impl PeerCertificateChain {
+    fn new(certs: Vec<PeerCertificate>) -> Option<Self> {
+        if certs.is_empty() {
+            None
+        } else {
+            Some(PeerCertificateChain(Arc::new(certs)))
+        }
+    }
+
+    /// Returns the certificate of the peer itself.
+    pub fn leaf(&self) -> &PeerCertificate {
+        &self.0[0]
+    }
+}
+
+impl Deref for PeerCertificateChain {
+    type Target = [PeerCertificate];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0[..]
+    }
+}
+
+/// A trait representing the connections which can provide the verified peer certificates.
+pub trait PeerCertificates {
+    /// Returns the certificate chain sent from the peer, if available.
+    ///
+    /// An error is returned if the certificates cannot be decoded.
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>>;
+}
+
+impl PeerCertificates for tokio::net::TcpStream {
+    #[inline]
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>> {
+        Ok(None)
+    }
+}
+
+#[cfg(unix)]
+impl PeerCertificates for tokio::net::UnixStream {
+    #[inline]
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>> {
+        Ok(None)
+    }
+}
+
+impl<L, R> PeerCertificates for Either<L, R>
+where
+    L: PeerCertificates,
+    R: PeerCertificates,
+{
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>> {
+        match self {
+            Either::Left(l) => l.peer_certificates(),
+            Either::Right(r) => r.peer_certificates(),
+        }
+    }
+}
+
+/// Creates a `ModifyService` which inserts the peer certificate chain into the extension map
+/// of `Request` before calling the internal service.
+///
+/// Nothing is inserted if the client does not send any certificates.
+/// If the certificates cannot be decoded, the connection is closed without calling the service.
+pub fn with_peer_certificates<T, S, Bd>() -> impl for<'a> ModifyService<
+    &'a T, //
+    Request<Bd>,
+    S,
+    Response = S::Response,
+    Error = S::Error,
+    Service = WithPeerCertificates<S>,
+    ModifyError = io::Error,
+    Future = futures::future::FutureResult<WithPeerCertificates<S>, io::Error>,
+>
+where
+    T: PeerCertificates,
+    S: Service<Request<Bd>>,
+{
+    modify_service_ref(|service: S, io: &T| {
+        Ok(WithPeerCertificates {
+            service,
+            certs: io.peer_certificates()?,
+        })
+    })
+}
+
+/// A `Service` created by `with_peer_certificates`.
+pub struct WithPeerCertificates<S> {
+    service: S,
+    certs: Option<PeerCertificateChain>,
+}
+
+impl<S> fmt::Debug for WithPeerCertificates<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithPeerCertificates")
+            .field("certs", &self.certs)
+            .finish()
+    }
+}
+
+impl<S, Bd> Service<Request<Bd>> for WithPeerCertificates<S>
+where
+    S: Service<Request<Bd>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    #[inline]
+    fn call(&mut self, mut request: Request<Bd>) -> Self::Future {
+        if let Some(ref certs) = self.certs {
+            request.extensions_mut().insert(certs.clone());
+        }
+        self.service.call(request)
+    }
+}
+
+/// Creates an `Extractor` that returns the verified certificate chain sent from the client.
+///
+/// The server must be configured with `with_peer_certificates`, e.g. by using
+/// `App::with_modify_service`. If the client does not send any certificates,
+/// the extractor fails with `403 Forbidden`; use `.optional()` to accept
+/// anonymous clients when the client authentication is optional.
+///
+/// ```ignore
+/// let endpoint = endpoint::get()
+///     .extract(tls::peer_certificates())
+///     .call(|certs: PeerCertificateChain| {
+///         format!("Hello, {}", certs.leaf().subject())
+///     });
+/// ```
+#[cfg(feature = "tsukuyomi")]
+pub fn peer_certificates() -> impl tsukuyomi::extractor::Extractor<
+    Output = (PeerCertificateChain,),
+    Error = tsukuyomi::Error,
+    Extract = impl tsukuyomi::future::TryFuture<
+        Ok = (PeerCertificateChain,),
+        Error = tsukuyomi::Error,
+    > + Send
+                  + 'static,
+> {
+    tsukuyomi::extractor::ready(|input| {
+        input
+            .request
+            .extensions()
+            .get::<PeerCertificateChain>()
+            .cloned()
+            .map(|certs| (certs,))
+            .ok_or_else(|| tsukuyomi::error::forbidden("client certificate required"))
+    })
+}
+
+/// Converts the octets in the `iPAddress` entry of subject alternative names into an `IpAddr`.
+fn ip_address(octets: &[u8]) -> io::Result<IpAddr> {
+    match octets.len() {
+        4 => {
+            let mut addr = [0u8; 4];
+            addr.copy_from_slice(octets);
+            Ok(IpAddr::from(addr))
+        }
+        16 => {
+            let mut addr = [0u8; 16];
+            addr.copy_from_slice(octets);
+            Ok(IpAddr::from(addr))
+        }
+        _ => Err(invalid_certificate(format!(
+            "invalid length of IP address in subject alternative names: {}",
+            octets.len()
+        ))),
+    }
+}
+
+fn invalid_certificate(msg: impl fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid peer certificate: {}", msg),
+    )
+}
@@ -0,0 +1,91 @@
+use {
+    super::{
+        invalid_certificate, ip_address, ClientAuth, PeerCertificate, PeerCertificateChain,
+        PeerCertificates, SubjectAltName,
+    },
+    openssl::{
+        error::ErrorStack,
+        hash::MessageDigest,
+        ssl::{SslAcceptorBuilder, SslVerifyMode},
+        x509::{X509Name, X509Ref},
+    },
+    std::io,
+    tokio_openssl::SslStream,
+};
+
+/// Configures the `SslAcceptorBuilder` to verify the client certificates.
+///
+/// The verified certificate chain can be obtained by `with_peer_certificates`.
+pub fn set_client_auth(
+    builder: &mut SslAcceptorBuilder,
+    auth: &ClientAuth,
+) -> Result<(), ErrorStack> {
+    builder.set_ca_file(&auth.ca_path)?;
+    builder.set_client_ca_list(X509Name::load_client_ca_file(&auth.ca_path)?);
+
+    let mut mode = SslVerifyMode::PEER;
+    if auth.required {
+        mode |= SslVerifyMode::FAIL_IF_NO_PEER_CERT;
+    }
+    builder.set_verify(mode);
+
+    Ok(())
+}
+
+impl<T> PeerCertificates for SslStream<T> {
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>> {
+        let ssl = self.get_ref().ssl();
+        let leaf = match ssl.peer_certificate() {
+            Some(leaf) => leaf,
+            None => return Ok(None),
+        };
+
+        let mut certs = vec![convert(&leaf)?];
+        if let Some(chain) = ssl.peer_cert_chain() {
+            // On the server side, the chain returned by OpenSSL does not contain the leaf.
+            for cert in chain {
+                certs.push(convert(cert)?);
+            }
+        }
+
+        Ok(PeerCertificateChain::new(certs))
+    }
+}
+
+fn convert(cert: &X509Ref) -> io::Result<PeerCertificate> {
+    let der = cert.to_der().map_err(invalid_certificate)?;
+    let fingerprint = cert
+        .digest(MessageDigest::sha256())
+        .map_err(invalid_certificate)?;
+
+    let mut subject = Vec::new();
+    for entry in cert.subject_name().entries() {
+        let key = entry
+            .object()
+            .nid()
+            .short_name()
+            .map_err(invalid_certificate)?;
+        let value = entry.data().as_utf8().map_err(invalid_certificate)?;
+        subject.push(format!("{}={}", key, value));
+    }
+
+    let mut subject_alt_names = Vec::new();
+    for name in cert.subject_alt_names().into_iter().flatten() {
+        if let Some(dns_name) = name.dnsname() {
+            subject_alt_names.push(SubjectAltName::DnsName(dns_name.to_owned()));
+        } else if let Some(email) = name.email() {
+            subject_alt_names.push(SubjectAltName::Email(email.to_owned()));
+        } else if let Some(uri) = name.uri() {
+            subject_alt_names.push(SubjectAltName::Uri(uri.to_owned()));
+        } else if let Some(addr) = name.ipaddress() {
+            subject_alt_names.push(SubjectAltName::IpAddress(ip_address(addr)?));
+        }
+    }
+
+    Ok(PeerCertificate::new(
+        der,
+        subject.join(", "),
+        subject_alt_names,
+        fingerprint.to_vec(),
+    ))
+}
@@ -0,0 +1,709 @@
+use {
+    super::{
+        invalid_certificate, ip_address, ClientAuth, PeerCertificate, PeerCertificateChain,
+        PeerCertificates, SubjectAltName,
+    },
+    futures::{Future, Stream},
+    rustls::{
+        internal::pemfile,
+        sign::{self, CertifiedKey},
+        AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, Certificate,
+        NoClientAuth, PrivateKey, ResolvesServerCert, RootCertStore, ServerConfig, ServerSession,
+        Session, SignatureScheme,
+    },
+    std::{
+        collections::HashMap,
+        fmt, fs,
+        io::{self, BufReader},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, RwLock},
+        time::{Duration, Instant, SystemTime},
+    },
+    tokio::timer::Interval,
+    tokio_rustls::{TlsAcceptor, TlsStream},
+    x509_parser::extensions::GeneralName,
+};
+
+/// A builder of `CertResolver`.
+#[derive(Debug, Default)]
+pub struct CertResolverBuilder {
+    sources: Vec<Source>,
+    client_auth: Option<ClientAuth>,
+}
+
+impl CertResolverBuilder {
+    /// Registers a pair of certificate chain and private key used for the specified server name.
+    ///
+    /// The server name may start with a wildcard label, e.g. `"*.example.com"`.
+    pub fn add(
+        mut self,
+        server_name: impl Into<String>,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.sources.push(Source {
+            server_name: Some(server_name.into().to_ascii_lowercase()),
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        });
+        self
+    }
+
+    /// Registers a pair of certificate chain and private key used when the client
+    /// does not send SNI or no certificate matches the requested server name.
+    pub fn default_cert(
+        mut self,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.sources.push(Source {
+            server_name: None,
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        });
+        self
+    }
+
+    /// Enables the verification of client certificates.
+    ///
+    /// The CA certificates are loaded when building the resolver and are not reloaded.
+    pub fn client_auth(self, client_auth: ClientAuth) -> Self {
+        Self {
+            client_auth: Some(client_auth),
+            ..self
+        }
+    }
+
+    /// Loads the registered certificates from the disk and creates a `CertResolver`.
+    pub fn build(self) -> io::Result<CertResolver> {
+        let client_auth = match self.client_auth {
+            Some(client_auth) => {
+                Some((load_root_certs(&client_auth.ca_path)?, client_auth.required))
+            }
+            None => None,
+        };
+        let resolver = CertResolver {
+            inner: Arc::new(Inner {
+                sources: self.sources,
+                client_auth,
+                keys: RwLock::new(CertifiedKeys::default()),
+                modified: Mutex::new(vec![]),
+            }),
+        };
+        resolver.reload()?;
+        Ok(resolver)
+    }
+}
+
+/// A `ResolvesServerCert` that selects the certificate by SNI.
+///
+/// The certificates can be reloaded from the disk without stopping the server.
+/// The connections that have already been established are not affected by reloading.
+#[derive(Debug, Clone)]
+pub struct CertResolver {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    sources: Vec<Source>,
+    client_auth: Option<(RootCertStore, bool)>,
+    keys: RwLock<CertifiedKeys>,
+    modified: Mutex<Vec<(Option<SystemTime>, Option<SystemTime>)>>,
+}
+
+#[derive(Debug)]
+struct Source {
+    server_name: Option<String>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+}
+
+#[derive(Default)]
+struct CertifiedKeys {
+    by_name: HashMap<String, CertifiedKey>,
+    default: Option<CertifiedKey>,
+}
+
+impl fmt::Debug for CertifiedKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertifiedKeys")
+            .field("by_name", &self.by_name.keys().collect::<Vec<_>>())
+            .field("default", &self.default.as_ref().map(|_| "<certified key>"))
+            .finish()
+    }
+}
+
+impl CertResolver {
+    /// Creates a builder of this type.
+    pub fn builder() -> CertResolverBuilder {
+        CertResolverBuilder::default()
+    }
+
+    /// Reloads all of the certificates from the disk.
+    ///
+    /// This method is intended to be called when the process receives a signal
+    /// (e.g. `SIGHUP`). If an error occurs, the current certificates are kept.
+    pub fn reload(&self) -> io::Result<()> {
+        let mut keys = CertifiedKeys::default();
+        let mut modified = Vec::with_capacity(self.inner.sources.len());
+        for source in &self.inner.sources {
+            modified.push(source.modified());
+            let key = source.load()?;
+            match source.server_name {
+                Some(ref name) => {
+                    keys.by_name.insert(name.clone(), key);
+                }
+                None => keys.default = Some(key),
+            }
+        }
+
+        *self.inner.keys.write().map_err(poisoned)? = keys;
+        *self.inner.modified.lock().map_err(poisoned)? = modified;
+
+        Ok(())
+    }
+
+    /// Reloads the certificates if any of the files has been modified since the last loading.
+    ///
+    /// The return value means whether the certificates are reloaded or not.
+    pub fn reload_if_modified(&self) -> io::Result<bool> {
+        let is_modified = {
+            let modified = self.inner.modified.lock().map_err(poisoned)?;
+            self.inner
+                .sources
+                .iter()
+                .zip(&*modified)
+                .any(|(source, modified)| source.modified() != *modified)
+        };
+        if is_modified {
+            self.reload()?;
+        }
+        Ok(is_modified)
+    }
+
+    /// Creates a `Future` that periodically checks the modification of certificate files
+    /// and reloads them.
+    ///
+    /// The returned future never completes and must be spawned onto the runtime.
+    pub fn watch(&self, interval: Duration) -> impl Future<Item = (), Error = ()> + Send + 'static {
+        let resolver = self.clone();
+        Interval::new(Instant::now() + interval, interval)
+            .map_err(|e| log::error!("timer error: {}", e))
+            .for_each(move |_| {
+                if let Err(e) = resolver.reload_if_modified() {
+                    log::error!("failed to reload the certificates: {}", e);
+                }
+                Ok(())
+            })
+    }
+
+    /// Creates a `ServerConfig` that uses this resolver.
+    ///
+    /// The returned configuration advertises `h2` and `http/1.1` via ALPN.
+    /// The server does not inspect the negotiated protocol by itself: the connections
+    /// are served with HTTP/1.1 by default, and switched to HTTP/2 when the client
+    /// starts with the HTTP/2 connection preface, as the clients negotiated `h2` do.
+    ///
+    /// If the client authentication is enabled, the client certificates are verified
+    /// with the configured CA certificates.
+    pub fn server_config(&self) -> ServerConfig {
+        let mut config = match self.inner.client_auth {
+            Some((ref roots, true)) => {
+                ServerConfig::new(AllowAnyAuthenticatedClient::new(roots.clone()))
+            }
+            Some((ref roots, false)) => {
+                ServerConfig::new(AllowAnyAnonymousOrAuthenticatedClient::new(roots.clone()))
+            }
+            None => ServerConfig::new(NoClientAuth::new()),
+        };
+        config.cert_resolver = Arc::new(self.clone());
+        config.set_protocols(&["h2".into(), "http/1.1".into()]);
+        config
+    }
+
+    /// Creates a `TlsAcceptor` with the default configuration.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(Arc::new(self.server_config()))
+    }
+
+    fn resolve_by_name(&self, server_name: &str) -> Option<CertifiedKey> {
+        let keys = self.inner.keys.read().ok()?;
+        let server_name = server_name.to_ascii_lowercase();
+        keys.by_name
+            .get(&server_name)
+            .or_else(|| {
+                let (_, parent) = server_name.split_at(server_name.find('.')?);
+                keys.by_name.get(&format!("*{}", parent))
+            })
+            .or_else(|| keys.default.as_ref())
+            .cloned()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(
+        &self,
+        server_name: Option<webpki::DNSNameRef<'_>>,
+        _: &[SignatureScheme],
+    ) -> Option<CertifiedKey> {
+        match server_name {
+            Some(server_name) => self.resolve_by_name(server_name.into()),
+            None => self.inner.keys.read().ok()?.default.clone(),
+        }
+    }
+}
+
+impl<T> PeerCertificates for TlsStream<T, ServerSession> {
+    fn peer_certificates(&self) -> io::Result<Option<PeerCertificateChain>> {
+        let (_, session) = self.get_ref();
+        peer_certificates(session)
+    }
+}
+
+fn peer_certificates(session: &ServerSession) -> io::Result<Option<PeerCertificateChain>> {
+    match session.get_peer_certificates() {
+        Some(certs) => Ok(PeerCertificateChain::new(
+            certs.into_iter().map(convert).collect::<io::Result<_>>()?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn convert(cert: Certificate) -> io::Result<PeerCertificate> {
+    let (subject, subject_alt_names) = {
+        let (_, parsed) =
+            x509_parser::parse_x509_certificate(&cert.0).map_err(invalid_certificate)?;
+
+        let mut subject_alt_names = Vec::new();
+        if let Some(extension) = parsed
+            .tbs_certificate
+            .subject_alternative_name()
+            .map_err(invalid_certificate)?
+        {
+            for name in &extension.value.general_names {
+                subject_alt_names.push(match *name {
+                    GeneralName::DNSName(dns_name) => SubjectAltName::DnsName(dns_name.into()),
+                    GeneralName::RFC822Name(email) => SubjectAltName::Email(email.into()),
+                    GeneralName::URI(uri) => SubjectAltName::Uri(uri.into()),
+                    GeneralName::IPAddress(addr) => SubjectAltName::IpAddress(ip_address(addr)?),
+                    _ => continue,
+                });
+            }
+        }
+
+        (parsed.subject().to_string(), subject_alt_names)
+    };
+
+    let fingerprint = ring::digest::digest(&ring::digest::SHA256, &cert.0);
+    Ok(PeerCertificate::new(
+        cert.0,
+        subject,
+        subject_alt_names,
+        fingerprint.as_ref().to_vec(),
+    ))
+}
+
+impl Source {
+    fn modified(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        (modified(&self.cert_path), modified(&self.key_path))
+    }
+
+    fn load(&self) -> io::Result<CertifiedKey> {
+        let certs = load_certs(&self.cert_path)?;
+        let key = load_private_key(&self.key_path)?;
+        let key = sign::any_supported_type(&key).map_err(|_| {
+            invalid_data(format!(
+                "unsupported private key: {}",
+                self.key_path.display()
+            ))
+        })?;
+        if !verify_key_pair(&certs[0], &**key) {
+            return Err(invalid_data(format!(
+                "the private key does not match the certificate: {}",
+                self.key_path.display()
+            )));
+        }
+        Ok(CertifiedKey::new(certs, Arc::new(key)))
+    }
+}
+
+/// Checks if the private key corresponds to the leaf certificate,
+/// by verifying a signature created with the key.
+fn verify_key_pair(cert: &Certificate, key: &dyn sign::SigningKey) -> bool {
+    const MESSAGE: &[u8] = b"tsukuyomi-server key pair verification";
+    let algorithms: [(SignatureScheme, &webpki::SignatureAlgorithm); 4] = [
+        (
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            &webpki::ECDSA_P256_SHA256,
+        ),
+        (
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            &webpki::ECDSA_P384_SHA384,
+        ),
+        (
+            SignatureScheme::RSA_PSS_SHA256,
+            &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+        ),
+        (
+            SignatureScheme::RSA_PKCS1_SHA256,
+            &webpki::RSA_PKCS1_2048_8192_SHA256,
+        ),
+    ];
+
+    let offered: Vec<_> = algorithms.iter().map(|&(scheme, _)| scheme).collect();
+    let signer = match key.choose_scheme(&offered) {
+        Some(signer) => signer,
+        None => return false,
+    };
+    let algorithm = match algorithms
+        .iter()
+        .find(|&&(scheme, _)| scheme == signer.get_scheme())
+    {
+        Some(&(_, algorithm)) => algorithm,
+        None => return false,
+    };
+    let signature = match signer.sign(MESSAGE) {
+        Ok(signature) => signature,
+        Err(..) => return false,
+    };
+
+    webpki::EndEntityCert::from(untrusted::Input::from(&cert.0))
+        .and_then(|cert| {
+            cert.verify_signature(
+                algorithm,
+                untrusted::Input::from(MESSAGE),
+                untrusted::Input::from(&signature),
+            )
+        })
+        .is_ok()
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let certs = pemfile::certs(&mut reader)
+        .map_err(|_| invalid_data(format!("failed to read certificates: {}", path.display())))?;
+    if certs.is_empty() {
+        return Err(invalid_data(format!(
+            "no certificates found: {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+fn load_root_certs(path: &Path) -> io::Result<RootCertStore> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut roots = RootCertStore::empty();
+    let (valid, _) = roots.add_pem_file(&mut reader).map_err(|_| {
+        invalid_data(format!(
+            "failed to read CA certificates: {}",
+            path.display()
+        ))
+    })?;
+    if valid == 0 {
+        return Err(invalid_data(format!(
+            "no valid CA certificates found: {}",
+            path.display()
+        )));
+    }
+    Ok(roots)
+}
+
+fn load_private_key(path: &Path) -> io::Result<PrivateKey> {
+    let pkcs8_keys = {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        pemfile::pkcs8_private_keys(&mut reader).map_err(|_| {
+            invalid_data(format!(
+                "failed to read private key as PKCS8: {}",
+                path.display()
+            ))
+        })?
+    };
+
+    let rsa_keys = {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        pemfile::rsa_private_keys(&mut reader).map_err(|_| {
+            invalid_data(format!(
+                "failed to read private key as RSA: {}",
+                path.display()
+            ))
+        })?
+    };
+
+    (pkcs8_keys.into_iter().next())
+        .or_else(|| rsa_keys.into_iter().next())
+        .ok_or_else(|| invalid_data(format!("no private key found: {}", path.display())))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn poisoned<T>(_: T) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "the lock has been poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{load_certs, load_private_key, peer_certificates, CertResolver},
+        crate::tls::{ClientAuth, SubjectAltName},
+        futures::Future,
+        rustls::{
+            ClientConfig, ClientSession, ResolvesServerCert, ServerSession, Session, TLSError,
+        },
+        std::{
+            env, fs,
+            io::{self, BufReader},
+            net::IpAddr,
+            path::{Path, PathBuf},
+            sync::Arc,
+            thread,
+            time::{Duration, Instant},
+        },
+    };
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/certs")
+            .join(name)
+    }
+
+    fn cert(name: &str) -> Option<Vec<u8>> {
+        Some(load_certs(&fixture(name)).unwrap()[0].0.clone())
+    }
+
+    fn resolve(resolver: &CertResolver, server_name: Option<&str>) -> Option<Vec<u8>> {
+        let server_name = server_name
+            .map(|name| webpki::DNSNameRef::try_from_ascii_str(name).expect("invalid DNS name"));
+        resolver
+            .resolve(server_name, &[])
+            .map(|key| key.cert[0].0.clone())
+    }
+
+    fn temp_dir(name: &str) -> io::Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("tsukuyomi-server-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Replaces the certificate and private key with the fixtures,
+    /// ensuring that the modification times are updated.
+    fn replace(name: &str, cert_path: &Path, key_path: &Path) -> io::Result<()> {
+        let files = [
+            (format!("{}.crt", name), cert_path),
+            (format!("{}.key", name), key_path),
+        ];
+        for &(ref src, dst) in &files {
+            let modified = fs::metadata(dst).and_then(|m| m.modified()).ok();
+            loop {
+                fs::copy(fixture(src), dst)?;
+                if fs::metadata(dst).and_then(|m| m.modified()).ok() != modified {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn select_by_sni() -> io::Result<()> {
+        let resolver = CertResolver::builder()
+            .add("a.example.com", fixture("a.crt"), fixture("a.key"))
+            .add(
+                "*.example.com",
+                fixture("wildcard.crt"),
+                fixture("wildcard.key"),
+            )
+            .build()?;
+
+        assert_eq!(resolve(&resolver, Some("a.example.com")), cert("a.crt"));
+        assert_eq!(resolve(&resolver, Some("A.Example.COM")), cert("a.crt"));
+        assert_eq!(
+            resolve(&resolver, Some("b.example.com")),
+            cert("wildcard.crt")
+        );
+        // the wildcard matches only a single label.
+        assert_eq!(resolve(&resolver, Some("c.b.example.com")), None);
+        assert_eq!(resolve(&resolver, Some("example.com")), None);
+        assert_eq!(resolve(&resolver, None), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fallback_to_default() -> io::Result<()> {
+        let resolver = CertResolver::builder()
+            .add("a.example.com", fixture("a.crt"), fixture("a.key"))
+            .default_cert(fixture("default.crt"), fixture("default.key"))
+            .build()?;
+
+        assert_eq!(resolve(&resolver, Some("a.example.com")), cert("a.crt"));
+        assert_eq!(
+            resolve(&resolver, Some("b.example.com")),
+            cert("default.crt")
+        );
+        assert_eq!(resolve(&resolver, None), cert("default.crt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_mismatched_key() {
+        let err = CertResolver::builder()
+            .add("a.example.com", fixture("a.crt"), fixture("wildcard.key"))
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reload() -> io::Result<()> {
+        let dir = temp_dir("reload")?;
+        let cert_path = dir.join("server.crt");
+        let key_path = dir.join("server.key");
+        fs::copy(fixture("a.crt"), &cert_path)?;
+        fs::copy(fixture("a.key"), &key_path)?;
+
+        let resolver = CertResolver::builder()
+            .default_cert(&cert_path, &key_path)
+            .build()?;
+        assert_eq!(resolve(&resolver, None), cert("a.crt"));
+        assert!(!resolver.reload_if_modified()?);
+
+        // the current certificate is kept if the new one is broken.
+        fs::write(&key_path, "broken")?;
+        assert!(resolver.reload().is_err());
+        assert_eq!(resolve(&resolver, None), cert("a.crt"));
+
+        replace("default", &cert_path, &key_path)?;
+        assert!(resolver.reload_if_modified()?);
+        assert_eq!(resolve(&resolver, None), cert("default.crt"));
+        assert!(!resolver.reload_if_modified()?);
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn watch() -> io::Result<()> {
+        let dir = temp_dir("watch")?;
+        let cert_path = dir.join("server.crt");
+        let key_path = dir.join("server.key");
+        fs::copy(fixture("a.crt"), &cert_path)?;
+        fs::copy(fixture("a.key"), &key_path)?;
+
+        let resolver = CertResolver::builder()
+            .default_cert(&cert_path, &key_path)
+            .build()?;
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        runtime.spawn(resolver.watch(Duration::from_millis(10)));
+
+        replace("default", &cert_path, &key_path)?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while resolve(&resolver, None) != cert("default.crt") {
+            assert!(Instant::now() < deadline, "the certificate is not reloaded");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        runtime.shutdown_now().wait().unwrap();
+        fs::remove_dir_all(&dir)
+    }
+
+    fn client_config(with_cert: bool) -> io::Result<Arc<ClientConfig>> {
+        let mut config = ClientConfig::new();
+        let mut ca = BufReader::new(fs::File::open(fixture("ca.crt"))?);
+        config
+            .root_store
+            .add_pem_file(&mut ca)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid CA certificate"))?;
+        if with_cert {
+            config.set_single_client_cert(
+                load_certs(&fixture("client.crt"))?,
+                load_private_key(&fixture("client.key"))?,
+            );
+        }
+        Ok(Arc::new(config))
+    }
+
+    /// Performs the TLS handshake in memory.
+    fn handshake(client: &mut ClientSession, server: &mut ServerSession) -> Result<(), TLSError> {
+        fn transfer(from: &mut dyn Session, to: &mut dyn Session) -> Result<(), TLSError> {
+            let mut buf = vec![];
+            while from.wants_write() {
+                from.write_tls(&mut buf).unwrap();
+            }
+            let mut rd = &buf[..];
+            while !rd.is_empty() {
+                to.read_tls(&mut rd).unwrap();
+            }
+            to.process_new_packets()
+        }
+
+        while client.is_handshaking() || server.is_handshaking() {
+            transfer(client, server)?;
+            transfer(server, client)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn client_auth_required() -> io::Result<()> {
+        let resolver = CertResolver::builder()
+            .default_cert(fixture("server.crt"), fixture("server.key"))
+            .client_auth(ClientAuth::required(fixture("ca.crt")))
+            .build()?;
+        let server_config = Arc::new(resolver.server_config());
+        let server_name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+        let mut server = ServerSession::new(&server_config);
+        let mut client = ClientSession::new(&client_config(true)?, server_name);
+        handshake(&mut client, &mut server).expect("handshake error");
+
+        let certs = peer_certificates(&server)?.expect("missing peer certificates");
+        assert_eq!(certs.len(), 1);
+        let leaf = certs.leaf();
+        assert_eq!(Some(leaf.der().to_vec()), cert("client.crt"));
+        assert_eq!(leaf.subject(), "C=JP, O=Example, CN=client.example.com");
+        assert_eq!(
+            leaf.subject_alt_names(),
+            &[
+                SubjectAltName::DnsName("client.example.com".into()),
+                SubjectAltName::Email("client@example.com".into()),
+                SubjectAltName::Uri("spiffe://example.com/client".into()),
+                SubjectAltName::IpAddress(IpAddr::from([127, 0, 0, 1])),
+            ][..]
+        );
+        assert_eq!(
+            leaf.fingerprint_hex(),
+            "77:88:CC:94:C2:0C:23:93:47:D4:E2:79:99:9B:A0:F8:\
+             33:DF:1D:7E:1A:D4:DE:57:46:46:10:AA:4D:4C:93:D9"
+        );
+
+        // the anonymous clients are rejected.
+        let mut server = ServerSession::new(&server_config);
+        let mut client = ClientSession::new(&client_config(false)?, server_name);
+        assert!(handshake(&mut client, &mut server).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn client_auth_optional() -> io::Result<()> {
+        let resolver = CertResolver::builder()
+            .default_cert(fixture("server.crt"), fixture("server.key"))
+            .client_auth(ClientAuth::optional(fixture("ca.crt")))
+            .build()?;
+        let server_config = Arc::new(resolver.server_config());
+        let server_name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+        let mut server = ServerSession::new(&server_config);
+        let mut client = ClientSession::new(&client_config(false)?, server_name);
+        handshake(&mut client, &mut server).expect("handshake error");
+        assert!(peer_certificates(&server)?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Generates the certificates used in the tests of TLS components.
+
+set -e
+cd "$(dirname "$0")"
+
+self_signed() {
+    openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+        -keyout "$1.key" -out "$1.crt" -days 36500 -subj "/CN=$2" \
+        -addext "subjectAltName=DNS:$2" -addext "basicConstraints=critical,CA:FALSE"
+}
+
+self_signed a a.example.com
+self_signed wildcard '*.example.com'
+self_signed default localhost
+
+# The certificates used in the tests of client certificate verification.
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -keyout ca.key -out ca.crt -days 36500 -subj "/CN=Tsukuyomi Test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign"
+
+signed_by_ca() {
+    printf 'basicConstraints=critical,CA:FALSE\nextendedKeyUsage=%s\nsubjectAltName=%s\n' "$4" "$5" > "$1.ext"
+    openssl req -new -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+        -keyout "$1.key" -out "$1.csr" -subj "$2"
+    openssl x509 -req -in "$1.csr" -CA ca.crt -CAkey ca.key -set_serial "$3" -days 36500 \
+        -extfile "$1.ext" -out "$1.crt"
+    rm "$1.csr" "$1.ext"
+}
+
+signed_by_ca server "/CN=localhost" 1 serverAuth "DNS:localhost"
+signed_by_ca client "/C=JP/O=Example/CN=client.example.com" 2 clientAuth \
+    "DNS:client.example.com,email:client@example.com,URI:spiffe://example.com/client,IP:127.0.0.1"
@@ -2,3 +2,135 @@
 fn test_version_sync() {
     version_sync::assert_html_root_url_updated!("src/lib.rs");
 }
+
+#[cfg(unix)]
+#[test]
+fn test_listen_fds_from_raw_fds() -> std::io::Result<()> {
+    use {std::os::unix::io::IntoRawFd, tsukuyomi_server::activation::ListenFds};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let local_addr = listener.local_addr()?;
+
+    let mut fds = unsafe { ListenFds::from_raw_fds(vec![listener.into_raw_fd()]) };
+    assert_eq!(fds.len(), 1);
+
+    // the file descriptor is not consumed if the socket type is mismatched.
+    assert!(fds.take_unix_listener(0).is_err());
+
+    let listener = fds.take_tcp_listener(0)?.expect("should be available");
+    assert_eq!(listener.local_addr()?, local_addr);
+    assert!(fds.take_tcp_listener(0)?.is_none());
+    assert!(fds.take_tcp_listener(1)?.is_none());
+
+    Ok(())
+}
+
+mod multiple_listeners {
+    use {
+        futures::{
+            future::{self, FutureResult},
+            sync::oneshot,
+            Async, Future, Poll,
+        },
+        http::{Request, Response},
+        hyper::Body,
+        std::{
+            io::{self, Read, Write},
+            net::{SocketAddr, TcpListener, TcpStream},
+            thread,
+        },
+        tsukuyomi_server::Server,
+        tsukuyomi_service::{MakeService, Service},
+    };
+
+    struct Hello;
+
+    impl Service<Request<Body>> for Hello {
+        type Response = Response<Body>;
+        type Error = io::Error;
+        type Future = FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: Request<Body>) -> Self::Future {
+            future::ok(Response::new(Body::from("hello")))
+        }
+    }
+
+    struct MakeHello;
+
+    impl<'a, T> MakeService<&'a T, Request<Body>> for MakeHello {
+        type Response = Response<Body>;
+        type Error = io::Error;
+        type Service = Hello;
+        type MakeError = io::Error;
+        type Future = FutureResult<Self::Service, Self::MakeError>;
+
+        fn make_service(&self, _: &'a T) -> Self::Future {
+            future::ok(Hello)
+        }
+    }
+
+    fn get(addr: SocketAddr) -> io::Result<String> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+
+    fn bind() -> io::Result<(TcpListener, SocketAddr)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        Ok((listener, addr))
+    }
+
+    macro_rules! check_listeners {
+        ($listener:expr, $addrs:expr) => {{
+            let addrs: Vec<SocketAddr> = $addrs;
+            let (tx, rx) = oneshot::channel::<()>();
+            let server = Server::new(MakeHello)
+                .bind($listener)
+                .shutdown_signal(rx.map_err(|_| ()));
+            let handle = thread::spawn(move || server.run());
+
+            for &addr in &addrs {
+                let response = get(addr)?;
+                assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+                assert!(response.ends_with("hello"), "{}", response);
+            }
+
+            // all listeners are closed together by the shutdown signal.
+            tx.send(()).expect("the server has already stopped");
+            handle
+                .join()
+                .expect("the server thread has panicked")
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            for &addr in &addrs {
+                assert!(TcpStream::connect(addr).is_err());
+            }
+        }};
+    }
+
+    #[test]
+    fn pair_of_listeners() -> io::Result<()> {
+        let (listener1, addr1) = bind()?;
+        let (listener2, addr2) = bind()?;
+        check_listeners!((listener1, listener2), vec![addr1, addr2]);
+        Ok(())
+    }
+
+    #[test]
+    fn vec_of_listeners() -> io::Result<()> {
+        let (listener1, addr1) = bind()?;
+        let (listener2, addr2) = bind()?;
+        let (listener3, addr3) = bind()?;
+        check_listeners!(
+            vec![listener1, listener2, listener3],
+            vec![addr1, addr2, addr3]
+        );
+        Ok(())
+    }
+}
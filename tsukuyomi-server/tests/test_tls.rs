@@ -0,0 +1,134 @@
+#![cfg(all(feature = "use-rustls", feature = "tsukuyomi"))]
+
+use {
+    futures::{sync::oneshot, Future},
+    rustls::{ClientConfig, ClientSession, ServerSession},
+    std::{
+        fs,
+        io::{self, BufReader, Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        path::{Path, PathBuf},
+        sync::Arc,
+        thread,
+    },
+    tokio_rustls::TlsStream,
+    tsukuyomi::{
+        config::prelude::*, //
+        App,
+    },
+    tsukuyomi_server::{
+        tls::{self, CertResolver, ClientAuth, PeerCertificateChain},
+        Server,
+    },
+};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/certs")
+        .join(name)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn client_config(with_cert: bool) -> io::Result<Arc<ClientConfig>> {
+    let mut config = ClientConfig::new();
+    let mut ca = BufReader::new(fs::File::open(fixture("ca.crt"))?);
+    config
+        .root_store
+        .add_pem_file(&mut ca)
+        .map_err(|_| invalid_data("invalid CA certificate"))?;
+    if with_cert {
+        let mut certs = BufReader::new(fs::File::open(fixture("client.crt"))?);
+        let mut key = BufReader::new(fs::File::open(fixture("client.key"))?);
+        let certs = rustls::internal::pemfile::certs(&mut certs)
+            .map_err(|_| invalid_data("invalid client certificate"))?;
+        let key = rustls::internal::pemfile::pkcs8_private_keys(&mut key)
+            .map_err(|_| invalid_data("invalid client key"))?
+            .pop()
+            .ok_or_else(|| invalid_data("missing client key"))?;
+        config.set_single_client_cert(certs, key);
+    }
+    Ok(Arc::new(config))
+}
+
+fn get(addr: SocketAddr, with_cert: bool) -> io::Result<String> {
+    let server_name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+    let mut session = ClientSession::new(&client_config(with_cert)?, server_name);
+    let mut sock = TcpStream::connect(addr)?;
+    let mut stream = rustls::Stream::new(&mut session, &mut sock);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+
+    let mut response = vec![];
+    match stream.read_to_end(&mut response) {
+        Ok(..) => {}
+        // the server may close the connection without sending close_notify.
+        Err(ref e)
+            if e.kind() == io::ErrorKind::ConnectionAborted
+                || e.kind() == io::ErrorKind::UnexpectedEof => {}
+        Err(e) => return Err(e),
+    }
+    String::from_utf8(response).map_err(|_| invalid_data("the response is not UTF-8"))
+}
+
+#[test]
+fn client_certificate() -> tsukuyomi_server::Result<()> {
+    let resolver = CertResolver::builder()
+        .default_cert(fixture("server.crt"), fixture("server.key"))
+        .client_auth(ClientAuth::optional(fixture("ca.crt")))
+        .build()?;
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get().extract(tls::peer_certificates()).call(
+                |certs: PeerCertificateChain| {
+                    let leaf = certs.leaf();
+                    format!(
+                        "subject={}; sans={:?}; fingerprint={}",
+                        leaf.subject(),
+                        leaf.subject_alt_names(),
+                        leaf.fingerprint_hex()
+                    )
+                },
+            )),
+    )?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = Server::new(app.with_modify_service(tls::with_peer_certificates::<
+        TlsStream<tokio::net::TcpStream, ServerSession>,
+        _,
+        _,
+    >()))
+    .bind(listener)
+    .acceptor(resolver.acceptor())
+    .shutdown_signal(rx.map_err(|_| ()));
+    let handle = thread::spawn(move || server.run());
+
+    let response = get(addr, true)?;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(
+        response.ends_with(
+            "subject=C=JP, O=Example, CN=client.example.com; \
+             sans=[DnsName(\"client.example.com\"), Email(\"client@example.com\"), \
+             Uri(\"spiffe://example.com/client\"), IpAddress(V4(127.0.0.1))]; \
+             fingerprint=77:88:CC:94:C2:0C:23:93:47:D4:E2:79:99:9B:A0:F8:\
+             33:DF:1D:7E:1A:D4:DE:57:46:46:10:AA:4D:4C:93:D9"
+        ),
+        "{}",
+        response
+    );
+
+    // the anonymous clients are rejected by the extractor.
+    let response = get(addr, false)?;
+    assert!(
+        response.starts_with("HTTP/1.1 403 Forbidden"),
+        "{}",
+        response
+    );
+
+    tx.send(()).expect("the server has already stopped");
+    handle.join().expect("the server thread has panicked")
+}
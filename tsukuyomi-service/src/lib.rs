@@ -13,6 +13,9 @@
 )]
 #![forbid(clippy::unimplemented)]
 
+pub mod limit;
+pub mod tower;
+
 use futures::{Async, Future, IntoFuture, Poll};
 
 #[doc(no_inline)]
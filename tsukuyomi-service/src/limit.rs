@@ -0,0 +1,606 @@
+//! `ModifyService`s for limiting the number of in-flight requests.
+//!
+//! The limit is shared among all services created from the same value,
+//! that is, it is applied to the requests across all connections.
+//! A permit is acquired when a request is received by `Service::call`, and released
+//! when the returned future is completed, so that the connections waiting for the next
+//! request do not occupy the limit.
+//! When the limit is exceeded, the request is rejected with
+//! `503 Service Unavailable` without calling the inner service.
+//! If the queue is enabled, the request waits in the queue until a room is available
+//! or the wait time exceeds the maximum.
+//!
+//! ```ignore
+//! use tsukuyomi_service::limit::concurrency_limit;
+//!
+//! let app = App::create(...)?;
+//! let limit = concurrency_limit(256).max_wait(Duration::from_secs(1));
+//! Server::new(app.with_modify_service(limit)).run()
+//! ```
+
+use {
+    crate::{ModifyService, Service},
+    futures::{
+        future::{self, FutureResult},
+        task::{self, Task},
+        Async, Future, Poll,
+    },
+    http::{Request, Response, StatusCode},
+    std::{
+        collections::VecDeque,
+        mem,
+        sync::{Arc, Mutex, MutexGuard},
+        time::{Duration, Instant},
+    },
+    tokio_timer::Delay,
+};
+
+/// Creates a `ConcurrencyLimit` with the specified maximum number of in-flight requests.
+pub fn concurrency_limit(max: usize) -> ConcurrencyLimit {
+    ConcurrencyLimit::new(max)
+}
+
+/// Creates an `AdaptiveLimit` with the default configuration.
+pub fn adaptive_limit() -> AdaptiveLimit {
+    AdaptiveLimit::new()
+}
+
+/// A `ModifyService` that limits the number of in-flight requests by a fixed value.
+///
+/// By default, the requests exceeding the limit are immediately rejected.
+/// They can be kept waiting in a queue by setting `max_wait`.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    limiter: Arc<Limiter>,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a `ConcurrencyLimit` with the specified maximum number of in-flight requests.
+    ///
+    /// # Panics
+    /// This function panics if `max` is zero.
+    pub fn new(max: usize) -> Self {
+        assert!(max > 0, "the limit must be greater than zero");
+        Self {
+            limiter: Arc::new(Limiter::new(max as f64, None)),
+        }
+    }
+
+    fn limiter_mut(&mut self) -> &mut Limiter {
+        Arc::get_mut(&mut self.limiter).expect("the value has already been shared")
+    }
+
+    /// Sets the maximum duration that a request waits in the queue for a room.
+    ///
+    /// The default value is zero, which means that the requests exceeding the limit are
+    /// rejected without waiting.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.limiter_mut().max_wait = max_wait;
+        self
+    }
+
+    /// Sets the maximum number of requests waiting in the queue.
+    ///
+    /// The requests arriving when the queue is full do not wait.
+    /// By default, the length of the queue is not limited.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.limiter_mut().queue_size = queue_size;
+        self
+    }
+
+    /// Returns the number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.limiter.lock().in_flight
+    }
+}
+
+/// A `ModifyService` that adjusts the limit of in-flight requests based on the latency.
+///
+/// The limit is controlled by AIMD (additive increase/multiplicative decrease):
+/// it is increased by `1/limit` when a request is handled within the target latency
+/// while the limit is saturated, and multiplied by the backoff ratio when the latency
+/// exceeds the target or the request fails with a server error.
+/// The requests exceeding the current limit are immediately rejected.
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimit {
+    limiter: Arc<Limiter>,
+}
+
+impl Default for AdaptiveLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdaptiveLimit {
+    /// Creates an `AdaptiveLimit` with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            limiter: Arc::new(Limiter::new(
+                10.0,
+                Some(Aimd {
+                    min_limit: 1,
+                    max_limit: 1000,
+                    target_latency: Duration::from_millis(100),
+                    backoff: 0.9,
+                }),
+            )),
+        }
+    }
+
+    fn limiter_mut(&mut self) -> &mut Limiter {
+        Arc::get_mut(&mut self.limiter).expect("the value has already been shared")
+    }
+
+    fn aimd_mut(&mut self) -> &mut Aimd {
+        self.limiter_mut()
+            .aimd
+            .as_mut()
+            .expect("the adaptive limiter should have the AIMD parameters")
+    }
+
+    /// Sets the initial value of the limit.
+    ///
+    /// The default value is `10`.
+    pub fn initial_limit(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "the limit must be greater than zero");
+        self.limiter_mut()
+            .state
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .limit = limit as f64;
+        self
+    }
+
+    /// Sets the lower bound of the limit.
+    ///
+    /// The default value is `1`.
+    pub fn min_limit(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "the limit must be greater than zero");
+        self.aimd_mut().min_limit = limit;
+        self
+    }
+
+    /// Sets the upper bound of the limit.
+    ///
+    /// The default value is `1000`.
+    pub fn max_limit(mut self, limit: usize) -> Self {
+        self.aimd_mut().max_limit = limit;
+        self
+    }
+
+    /// Sets the latency considered to be acceptable.
+    ///
+    /// The default value is 100 milliseconds.
+    pub fn target_latency(mut self, latency: Duration) -> Self {
+        self.aimd_mut().target_latency = latency;
+        self
+    }
+
+    /// Sets the ratio multiplied to the limit when the service is overloaded.
+    ///
+    /// The default value is `0.9`.
+    ///
+    /// # Panics
+    /// This method panics if `backoff` is not in the range `(0, 1)`.
+    pub fn backoff(mut self, backoff: f64) -> Self {
+        assert!(
+            backoff > 0.0 && backoff < 1.0,
+            "the backoff ratio must be in the range (0, 1)"
+        );
+        self.aimd_mut().backoff = backoff;
+        self
+    }
+
+    /// Returns the current value of the limit.
+    pub fn current_limit(&self) -> usize {
+        self.limiter.lock().capacity()
+    }
+
+    /// Returns the number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.limiter.lock().in_flight
+    }
+}
+
+macro_rules! impl_modify_service {
+    ($($t:ty,)*) => {$(
+        impl<Ctx, Bd, S, B> ModifyService<Ctx, Request<Bd>, S> for $t
+        where
+            S: Service<Request<Bd>, Response = Response<B>>,
+            B: Default,
+        {
+            type Response = Response<B>;
+            type Error = S::Error;
+            type Service = LimitService<S>;
+            type ModifyError = std::io::Error;
+            type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+            fn modify_service(&self, inner: S, _: Ctx) -> Self::Future {
+                future::ok(LimitService {
+                    inner: Arc::new(Mutex::new(inner)),
+                    limiter: self.limiter.clone(),
+                })
+            }
+        }
+    )*};
+}
+
+impl_modify_service! {
+    ConcurrencyLimit,
+    AdaptiveLimit,
+}
+
+#[derive(Debug)]
+struct Aimd {
+    min_limit: usize,
+    max_limit: usize,
+    target_latency: Duration,
+    backoff: f64,
+}
+
+#[derive(Debug)]
+struct Limiter {
+    max_wait: Duration,
+    queue_size: usize,
+    aimd: Option<Aimd>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    in_flight: usize,
+    waiters: VecDeque<(usize, Task)>,
+    next_waiter_id: usize,
+}
+
+impl State {
+    fn capacity(&self) -> usize {
+        (self.limit as usize).max(1)
+    }
+
+    /// Wakes up the task at the head of the queue if there is a room for the request.
+    fn notify_head(&self) {
+        if self.in_flight < self.capacity() {
+            if let Some(&(_, ref task)) = self.waiters.front() {
+                task.notify();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Waiter {
+    id: Option<usize>,
+    deadline: Option<Delay>,
+}
+
+impl Limiter {
+    fn new(limit: f64, aimd: Option<Aimd>) -> Self {
+        Self {
+            max_wait: Duration::from_secs(0),
+            queue_size: usize::max_value(),
+            aimd,
+            state: Mutex::new(State {
+                limit,
+                in_flight: 0,
+                waiters: VecDeque::new(),
+                next_waiter_id: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Acquires a permit for the received request if there is a room for it
+    /// and no other request is waiting in the queue.
+    fn try_acquire(limiter: &Arc<Self>) -> Option<Permit> {
+        let mut state = limiter.lock();
+        if !state.waiters.is_empty() || state.in_flight >= state.capacity() {
+            return None;
+        }
+        state.in_flight += 1;
+        Some(Permit {
+            limiter: limiter.clone(),
+            start: Instant::now(),
+        })
+    }
+
+    /// Acquires a permit for the waiting request, waiting until there is a room for it.
+    ///
+    /// If the queue is full or the wait time exceeds the maximum, this method returns
+    /// `Ready(None)` without waiting and the request will be rejected.
+    fn poll_acquire(limiter: &Arc<Self>, waiter: &mut Waiter) -> Async<Option<Permit>> {
+        let mut state = limiter.lock();
+
+        // The waiting connections are resumed in FIFO order.
+        let is_head = match state.waiters.front() {
+            Some(&(id, _)) => waiter.id == Some(id),
+            None => true,
+        };
+        if is_head && state.in_flight < state.capacity() {
+            if waiter.id.take().is_some() {
+                state.waiters.pop_front();
+            }
+            waiter.deadline = None;
+            state.in_flight += 1;
+            state.notify_head();
+            return Async::Ready(Some(Permit {
+                limiter: limiter.clone(),
+                start: Instant::now(),
+            }));
+        }
+
+        if limiter.max_wait == Duration::from_secs(0) {
+            return Async::Ready(None);
+        }
+
+        match waiter.id {
+            Some(id) => {
+                if let Some(entry) = state.waiters.iter_mut().find(|entry| entry.0 == id) {
+                    entry.1 = task::current();
+                }
+            }
+            None => {
+                if state.waiters.len() >= limiter.queue_size {
+                    return Async::Ready(None);
+                }
+                let id = state.next_waiter_id;
+                state.next_waiter_id = state.next_waiter_id.wrapping_add(1);
+                state.waiters.push_back((id, task::current()));
+                waiter.id = Some(id);
+            }
+        }
+        drop(state);
+
+        let max_wait = limiter.max_wait;
+        let deadline = waiter
+            .deadline
+            .get_or_insert_with(|| Delay::new(Instant::now() + max_wait));
+        match deadline.poll() {
+            Ok(Async::NotReady) => Async::NotReady,
+            Ok(Async::Ready(())) | Err(..) => {
+                limiter.cancel(waiter);
+                Async::Ready(None)
+            }
+        }
+    }
+
+    /// Removes the waiter from the queue.
+    fn cancel(&self, waiter: &mut Waiter) {
+        waiter.deadline = None;
+        if let Some(id) = waiter.id.take() {
+            let mut state = self.lock();
+            if let Some(pos) = state.waiters.iter().position(|&(i, _)| i == id) {
+                state.waiters.remove(pos);
+                if pos == 0 {
+                    state.notify_head();
+                }
+            }
+        }
+    }
+
+    fn release(&self) {
+        let mut state = self.lock();
+        state.in_flight -= 1;
+        state.notify_head();
+    }
+
+    fn record(&self, latency: Duration, success: bool) {
+        if let Some(ref aimd) = self.aimd {
+            let mut state = self.lock();
+            if success && latency <= aimd.target_latency {
+                // Increase the limit only if it is actually reached,
+                // so that it does not grow infinitely under the light load.
+                if state.in_flight >= state.capacity() {
+                    state.limit = (state.limit + 1.0 / state.limit).min(aimd.max_limit as f64);
+                }
+            } else {
+                state.limit = (state.limit * aimd.backoff).max(aimd.min_limit as f64);
+            }
+            state.notify_head();
+        }
+    }
+}
+
+/// A permit for handling a request, released when dropped.
+#[derive(Debug)]
+struct Permit {
+    limiter: Arc<Limiter>,
+    start: Instant,
+}
+
+impl Permit {
+    fn complete(self, success: bool) {
+        self.limiter.record(self.start.elapsed(), success);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// The `Service` created by `ConcurrencyLimit` and `AdaptiveLimit`.
+///
+/// The permit is acquired when the request is received and held until the response
+/// is returned, so the connections waiting for the next request, such as idle
+/// keep-alive connections, do not occupy the limit.
+#[allow(missing_debug_implementations)]
+pub struct LimitService<S> {
+    // shared with the futures waiting in the queue, which call the service
+    // after acquiring the permit.
+    inner: Arc<Mutex<S>>,
+    limiter: Arc<Limiter>,
+}
+
+fn lock_service<S>(inner: &Mutex<S>) -> MutexGuard<'_, S> {
+    inner.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<S, Bd, B> Service<Request<Bd>> for LimitService<S>
+where
+    S: Service<Request<Bd>, Response = Response<B>>,
+    B: Default,
+{
+    type Response = Response<B>;
+    type Error = S::Error;
+    type Future = LimitFuture<S, Bd>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        lock_service(&self.inner).poll_ready()
+    }
+
+    fn call(&mut self, request: Request<Bd>) -> Self::Future {
+        let state = match Limiter::try_acquire(&self.limiter) {
+            Some(permit) => FutureState::Running {
+                future: lock_service(&self.inner).call(request),
+                permit,
+            },
+            None if self.limiter.max_wait == Duration::from_secs(0) => FutureState::Rejected,
+            None => FutureState::Waiting {
+                inner: self.inner.clone(),
+                request,
+                waiter: Waiter::default(),
+                permit: None,
+            },
+        };
+        LimitFuture {
+            limiter: self.limiter.clone(),
+            state,
+        }
+    }
+}
+
+/// The `Future` returned from `LimitService`.
+#[allow(missing_debug_implementations)]
+pub struct LimitFuture<S, Bd>
+where
+    S: Service<Request<Bd>>,
+{
+    limiter: Arc<Limiter>,
+    state: FutureState<S, Bd>,
+}
+
+enum FutureState<S, Bd>
+where
+    S: Service<Request<Bd>>,
+{
+    Waiting {
+        inner: Arc<Mutex<S>>,
+        request: Request<Bd>,
+        waiter: Waiter,
+        // acquired while the inner service is not ready.
+        permit: Option<Permit>,
+    },
+    Running {
+        future: S::Future,
+        permit: Permit,
+    },
+    Rejected,
+    Done,
+}
+
+impl<S, Bd, B> Future for LimitFuture<S, Bd>
+where
+    S: Service<Request<Bd>, Response = Response<B>>,
+    B: Default,
+{
+    type Item = Response<B>;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match mem::replace(&mut self.state, FutureState::Done) {
+                FutureState::Waiting {
+                    inner,
+                    request,
+                    mut waiter,
+                    permit,
+                } => {
+                    let permit = match permit {
+                        Some(permit) => permit,
+                        None => match Limiter::poll_acquire(&self.limiter, &mut waiter) {
+                            Async::Ready(Some(permit)) => permit,
+                            Async::Ready(None) => {
+                                self.state = FutureState::Rejected;
+                                continue;
+                            }
+                            Async::NotReady => {
+                                self.state = FutureState::Waiting {
+                                    inner,
+                                    request,
+                                    waiter,
+                                    permit: None,
+                                };
+                                return Ok(Async::NotReady);
+                            }
+                        },
+                    };
+
+                    let mut service = lock_service(&inner);
+                    match service.poll_ready() {
+                        Ok(Async::Ready(())) => {}
+                        Ok(Async::NotReady) => {
+                            drop(service);
+                            self.state = FutureState::Waiting {
+                                inner,
+                                request,
+                                waiter,
+                                permit: Some(permit),
+                            };
+                            return Ok(Async::NotReady);
+                        }
+                        Err(err) => {
+                            permit.complete(false);
+                            return Err(err);
+                        }
+                    }
+                    FutureState::Running {
+                        future: service.call(request),
+                        permit,
+                    }
+                }
+
+                FutureState::Running { mut future, permit } => {
+                    let result = match future.poll() {
+                        Ok(Async::NotReady) => {
+                            self.state = FutureState::Running { future, permit };
+                            return Ok(Async::NotReady);
+                        }
+                        result => result,
+                    };
+                    let success = match result {
+                        Ok(Async::Ready(ref response)) => !response.status().is_server_error(),
+                        _ => false,
+                    };
+                    permit.complete(success);
+                    return result;
+                }
+
+                FutureState::Rejected => {
+                    let mut response = Response::new(B::default());
+                    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                    return Ok(Async::Ready(response));
+                }
+
+                FutureState::Done => panic!("the future has already been completed"),
+            };
+        }
+    }
+}
+
+impl<S, Bd> Drop for LimitFuture<S, Bd>
+where
+    S: Service<Request<Bd>>,
+{
+    fn drop(&mut self) {
+        if let FutureState::Waiting { ref mut waiter, .. } = self.state {
+            self.limiter.cancel(waiter);
+        }
+    }
+}
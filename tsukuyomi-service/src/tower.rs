@@ -0,0 +1,317 @@
+//! Adapters for interoperating with the middleware stacks from [`tower`].
+//!
+//! * `from_layer` / `into_layer` convert between `tower_layer::Layer` and `ModifyService`.
+//! * `from_make_service` / `into_make_service` convert between the factories of services
+//!   in `tower` (i.e. `Service`s returning a `Service`) and `MakeService`.
+//!
+//! ```ignore
+//! use tsukuyomi_service::tower::from_layer;
+//!
+//! let app = App::create(...)?;
+//! Server::new(app.with_modify_service(from_layer(tower_layer)))
+//!     .run()
+//! ```
+//!
+//! [`tower`]: https://github.com/tower-rs/tower
+
+use {
+    crate::{MakeService, ModifyService, Service},
+    futures::{
+        future::{self, FutureResult},
+        try_ready, Async, Future, Poll,
+    },
+    std::marker::PhantomData,
+};
+
+#[doc(no_inline)]
+pub use tower_layer::Layer;
+
+/// The type of boxed errors returned from the services created by `IntoLayer`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Creates a `ModifyService` from the specified `Layer`.
+///
+/// The context value passed to `modify_service` is ignored.
+pub fn from_layer<L>(layer: L) -> FromLayer<L> {
+    FromLayer { layer }
+}
+
+/// A `ModifyService` created by `from_layer`.
+#[derive(Debug, Clone)]
+pub struct FromLayer<L> {
+    layer: L,
+}
+
+impl<L, Ctx, Request, S> ModifyService<Ctx, Request, S> for FromLayer<L>
+where
+    L: Layer<S, Request>,
+{
+    type Response = L::Response;
+    type Error = L::Error;
+    type Service = L::Service;
+    type ModifyError = L::LayerError;
+    type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+    #[inline]
+    fn modify_service(&self, input: S, _: Ctx) -> Self::Future {
+        future::result(self.layer.layer(input))
+    }
+}
+
+/// Creates a `Layer` from the specified `ModifyService` and the context value.
+///
+/// Since `ModifyService` creates a service asynchronously, the created service
+/// completes the modification in `poll_ready` and the error that occurs during
+/// the modification is reported from it.  The errors are boxed so that both of
+/// the modification and the modified service can report them.
+pub fn into_layer<M, Ctx>(modify_service: M, ctx: Ctx) -> IntoLayer<M, Ctx>
+where
+    Ctx: Clone,
+{
+    IntoLayer {
+        modify_service,
+        ctx,
+    }
+}
+
+/// A `Layer` created by `into_layer`.
+#[derive(Debug, Clone)]
+pub struct IntoLayer<M, Ctx> {
+    modify_service: M,
+    ctx: Ctx,
+}
+
+impl<M, Ctx, Request, S> Layer<S, Request> for IntoLayer<M, Ctx>
+where
+    M: ModifyService<Ctx, Request, S>,
+    M::Error: Into<BoxError>,
+    M::ModifyError: Into<BoxError>,
+    Ctx: Clone,
+{
+    type Response = M::Response;
+    type Error = BoxError;
+    type LayerError = M::ModifyError;
+    type Service = Modified<M::Future, M::Service>;
+
+    fn layer(&self, inner: S) -> Result<Self::Service, Self::LayerError> {
+        Ok(Modified {
+            state: ModifiedState::Modifying(
+                self.modify_service.modify_service(inner, self.ctx.clone()),
+            ),
+        })
+    }
+}
+
+/// The `Service` created by `IntoLayer`.
+#[allow(missing_debug_implementations)]
+pub struct Modified<F, S> {
+    state: ModifiedState<F, S>,
+}
+
+enum ModifiedState<F, S> {
+    Modifying(F),
+    Ready(S),
+}
+
+impl<F, S, Request> Service<Request> for Modified<F, S>
+where
+    F: Future<Item = S>,
+    F::Error: Into<BoxError>,
+    S: Service<Request>,
+    S::Error: Into<BoxError>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = ModifiedFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        loop {
+            self.state = match self.state {
+                ModifiedState::Modifying(ref mut future) => match future.poll() {
+                    Ok(Async::Ready(service)) => ModifiedState::Ready(service),
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(err) => return Err(err.into()),
+                },
+                ModifiedState::Ready(ref mut service) => {
+                    return service.poll_ready().map_err(Into::into)
+                }
+            };
+        }
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match self.state {
+            ModifiedState::Ready(ref mut service) => ModifiedFuture(service.call(request)),
+            ModifiedState::Modifying(..) => {
+                panic!("the service is not ready; `poll_ready` must be called before `call`")
+            }
+        }
+    }
+}
+
+/// The `Future` returned from `Modified`.
+#[allow(missing_debug_implementations)]
+pub struct ModifiedFuture<F>(F);
+
+impl<F> Future for ModifiedFuture<F>
+where
+    F: Future,
+    F::Error: Into<BoxError>,
+{
+    type Item = F::Item;
+    type Error = BoxError;
+
+    #[inline]
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll().map_err(Into::into)
+    }
+}
+
+/// Creates a `MakeService` from a factory of services in `tower`.
+///
+/// The factory is cloned for each call of `make_service`, and the clone is called
+/// after it becomes ready.
+/// The context value is passed to the factory as is.  Use `with_target` when the
+/// context value is passed by reference, as in `tsukuyomi_server::Server`.
+pub fn from_make_service<M>(make_service: M) -> FromMakeService<M> {
+    FromMakeService {
+        make_service,
+        target: (),
+    }
+}
+
+/// A `MakeService` created by `from_make_service`.
+#[derive(Debug, Clone)]
+pub struct FromMakeService<M, F = ()> {
+    make_service: M,
+    target: F,
+}
+
+impl<M> FromMakeService<M> {
+    /// Sets the function to create the value passed to the factory from
+    /// the reference to the context value.
+    pub fn with_target<F>(self, target: F) -> FromMakeService<M, F> {
+        FromMakeService {
+            make_service: self.make_service,
+            target,
+        }
+    }
+}
+
+impl<M, Ctx, Request, S> MakeService<Ctx, Request> for FromMakeService<M>
+where
+    M: Service<Ctx, Response = S> + Clone,
+    S: Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Service = S;
+    type MakeError = M::Error;
+    type Future = MakeServiceFuture<M, Ctx>;
+
+    fn make_service(&self, ctx: Ctx) -> Self::Future {
+        MakeServiceFuture {
+            make_service: self.make_service.clone(),
+            state: MakeServiceState::Pending(Some(ctx)),
+        }
+    }
+}
+
+impl<'a, M, F, Ctx, Target, Request, S> MakeService<&'a Ctx, Request> for FromMakeService<M, F>
+where
+    M: Service<Target, Response = S> + Clone,
+    S: Service<Request>,
+    F: Fn(&Ctx) -> Target,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Service = S;
+    type MakeError = M::Error;
+    type Future = MakeServiceFuture<M, Target>;
+
+    fn make_service(&self, ctx: &'a Ctx) -> Self::Future {
+        MakeServiceFuture {
+            make_service: self.make_service.clone(),
+            state: MakeServiceState::Pending(Some((self.target)(ctx))),
+        }
+    }
+}
+
+/// The `Future` returned from `FromMakeService`.
+#[allow(missing_debug_implementations)]
+pub struct MakeServiceFuture<M: Service<Target>, Target> {
+    make_service: M,
+    state: MakeServiceState<Target, M::Future>,
+}
+
+enum MakeServiceState<Target, F> {
+    Pending(Option<Target>),
+    Making(F),
+}
+
+impl<M, Target> Future for MakeServiceFuture<M, Target>
+where
+    M: Service<Target>,
+{
+    type Item = M::Response;
+    type Error = M::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match self.state {
+                MakeServiceState::Pending(ref mut target) => {
+                    try_ready!(self.make_service.poll_ready());
+                    let target = target.take().expect("the future has already been polled");
+                    MakeServiceState::Making(self.make_service.call(target))
+                }
+                MakeServiceState::Making(ref mut future) => return future.poll(),
+            };
+        }
+    }
+}
+
+/// Creates a factory of services in `tower` from the specified `MakeService`.
+///
+/// The returned value implements `tower_util::MakeService` through
+/// the implementation of `Service<Ctx>`, and it is always ready.
+pub fn into_make_service<M, Request>(make_service: M) -> IntoMakeService<M, Request> {
+    IntoMakeService {
+        make_service,
+        _marker: PhantomData,
+    }
+}
+
+/// A factory of services in `tower` created by `into_make_service`.
+#[derive(Debug)]
+pub struct IntoMakeService<M, Request> {
+    make_service: M,
+    _marker: PhantomData<fn(Request)>,
+}
+
+impl<M, Request> Clone for IntoMakeService<M, Request>
+where
+    M: Clone,
+{
+    fn clone(&self) -> Self {
+        into_make_service(self.make_service.clone())
+    }
+}
+
+impl<M, Ctx, Request> Service<Ctx> for IntoMakeService<M, Request>
+where
+    M: MakeService<Ctx, Request>,
+{
+    type Response = M::Service;
+    type Error = M::MakeError;
+    type Future = M::Future;
+
+    #[inline]
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    #[inline]
+    fn call(&mut self, ctx: Ctx) -> Self::Future {
+        self.make_service.make_service(ctx)
+    }
+}
@@ -1,4 +1,247 @@
+use {
+    futures::{future, sync::oneshot, Future},
+    http::{Request, Response, StatusCode},
+    std::time::{Duration, Instant},
+    tsukuyomi_service::{
+        limit::{adaptive_limit, concurrency_limit},
+        service_fn,
+        tower::{from_layer, into_layer},
+        ModifyService, Service,
+    },
+};
+
 #[test]
 fn test_version_sync() {
     version_sync::assert_html_root_url_updated!("src/lib.rs");
 }
+
+/// Creates a `Service` whose response is sent through the returned channel.
+fn pending_service() -> (
+    oneshot::Sender<Response<()>>,
+    impl Service<Request<()>, Response = Response<()>, Error = oneshot::Canceled>,
+) {
+    let (tx, rx) = oneshot::channel();
+    let mut rx = Some(rx);
+    let service = service_fn(move |_: Request<()>| rx.take().expect("called twice"));
+    (tx, service)
+}
+
+fn modify<M, S>(modify_service: &M, inner: S) -> M::Service
+where
+    M: ModifyService<(), Request<()>, S>,
+    M::ModifyError: std::fmt::Debug,
+{
+    modify_service
+        .modify_service(inner, ())
+        .wait()
+        .expect("failed to modify the service")
+}
+
+#[test]
+fn test_concurrency_limit() {
+    let limit = concurrency_limit(1);
+
+    future::lazy(move || {
+        let (tx1, inner1) = pending_service();
+        let (_tx2, inner2) = pending_service();
+        let mut service1 = modify(&limit, inner1);
+        let mut service2 = modify(&limit, inner2);
+
+        assert!(service1.poll_ready()?.is_ready());
+        let mut future1 = service1.call(Request::new(()));
+        assert!(future1.poll()?.is_not_ready());
+        assert_eq!(limit.in_flight(), 1);
+
+        // The request exceeding the limit is rejected immediately.
+        assert!(service2.poll_ready()?.is_ready());
+        let response = service2.call(Request::new(())).wait()?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        tx1.send(Response::new(()))
+            .expect("the receiver has been dropped");
+        let response = future1.wait()?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(limit.in_flight(), 0);
+
+        Ok::<(), oneshot::Canceled>(())
+    })
+    .wait()
+    .unwrap();
+}
+
+#[test]
+fn test_concurrency_limit_queue() {
+    let limit = concurrency_limit(1).max_wait(Duration::from_millis(50));
+    let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+    rt.block_on(
+        future::lazy(move || {
+            let (tx1, inner1) = pending_service();
+            let (tx2, inner2) = pending_service();
+            let (_tx3, inner3) = pending_service();
+            let mut service1 = modify(&limit, inner1);
+            let mut service2 = modify(&limit, inner2);
+            let mut service3 = modify(&limit, inner3);
+
+            assert!(service1.poll_ready()?.is_ready());
+            let mut future1 = service1.call(Request::new(()));
+            assert!(future1.poll()?.is_not_ready());
+
+            // The request waits until the in-flight request is completed.
+            assert!(service2.poll_ready()?.is_ready());
+            let mut future2 = service2.call(Request::new(()));
+            assert!(future2.poll()?.is_not_ready());
+            tx1.send(Response::new(()))
+                .expect("the receiver has been dropped");
+            assert_eq!(future1.wait()?.status(), StatusCode::OK);
+            assert!(future2.poll()?.is_not_ready());
+            assert_eq!(limit.in_flight(), 1);
+
+            // The request is rejected after waiting for the maximum duration.
+            let start = Instant::now();
+            assert!(service3.poll_ready()?.is_ready());
+            Ok::<_, oneshot::Canceled>(service3.call(Request::new(())).map(move |response| {
+                assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+                assert!(start.elapsed() >= Duration::from_millis(50));
+                drop((tx2, future2));
+            }))
+        })
+        .and_then(|future| future),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_concurrency_limit_idle_connections() {
+    let limit = concurrency_limit(1);
+
+    future::lazy(move || {
+        // The connections waiting for the next request do not occupy the limit.
+        let mut idle_services: Vec<_> = (0..3)
+            .map(|_| modify(&limit, pending_service().1))
+            .collect();
+        for service in &mut idle_services {
+            assert!(service.poll_ready()?.is_ready());
+        }
+        assert_eq!(limit.in_flight(), 0);
+
+        let (tx, inner) = pending_service();
+        let mut service = modify(&limit, inner);
+        assert!(service.poll_ready()?.is_ready());
+        let mut future = service.call(Request::new(()));
+        assert!(future.poll()?.is_not_ready());
+        assert_eq!(limit.in_flight(), 1);
+
+        tx.send(Response::new(()))
+            .expect("the receiver has been dropped");
+        assert_eq!(future.wait()?.status(), StatusCode::OK);
+        assert_eq!(limit.in_flight(), 0);
+
+        Ok::<(), oneshot::Canceled>(())
+    })
+    .wait()
+    .unwrap();
+}
+
+#[test]
+fn test_adaptive_limit() {
+    let limit = adaptive_limit()
+        .initial_limit(8)
+        .min_limit(2)
+        .target_latency(Duration::from_millis(50))
+        .backoff(0.5);
+
+    // Sends a request through a new connection, and completes it after `delay`.
+    let request = |status: StatusCode, delay: Duration| {
+        let (tx, inner) = pending_service();
+        let mut service = modify(&limit, inner);
+        assert!(service.poll_ready()?.is_ready());
+        let mut future = service.call(Request::new(()));
+        assert!(future.poll()?.is_not_ready());
+        std::thread::sleep(delay);
+        let mut response = Response::new(());
+        *response.status_mut() = status;
+        tx.send(response).expect("the receiver has been dropped");
+        future.wait()
+    };
+
+    future::lazy(|| {
+        assert_eq!(limit.current_limit(), 8);
+
+        // The limit is decreased by the server errors.
+        request(StatusCode::INTERNAL_SERVER_ERROR, Duration::from_secs(0))?;
+        assert_eq!(limit.current_limit(), 4);
+
+        // The limit is decreased by the latency exceeding the target.
+        request(StatusCode::OK, Duration::from_millis(60))?;
+        assert_eq!(limit.current_limit(), 2);
+
+        // The limit does not fall below the minimum.
+        request(StatusCode::SERVICE_UNAVAILABLE, Duration::from_secs(0))?;
+        assert_eq!(limit.current_limit(), 2);
+
+        // The limit is not increased while it is not reached.
+        request(StatusCode::OK, Duration::from_secs(0))?;
+        assert_eq!(limit.current_limit(), 2);
+
+        // The request exceeding the current limit is rejected.
+        let (tx1, inner1) = pending_service();
+        let (tx2, inner2) = pending_service();
+        let (_tx3, inner3) = pending_service();
+        let mut service1 = modify(&limit, inner1);
+        let mut service2 = modify(&limit, inner2);
+        let mut service3 = modify(&limit, inner3);
+        assert!(service1.poll_ready()?.is_ready());
+        assert!(service2.poll_ready()?.is_ready());
+        assert!(service3.poll_ready()?.is_ready());
+        let mut future1 = service1.call(Request::new(()));
+        let mut future2 = service2.call(Request::new(()));
+        assert!(future1.poll()?.is_not_ready());
+        assert!(future2.poll()?.is_not_ready());
+        assert_eq!(limit.in_flight(), 2);
+        let response = service3.call(Request::new(())).wait()?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The limit is increased by `1/limit` per fast response while it is reached:
+        // 2.0 -> 2.5 -> 2.9 -> 3.24...
+        tx1.send(Response::new(()))
+            .expect("the receiver has been dropped");
+        assert_eq!(future1.wait()?.status(), StatusCode::OK);
+        assert_eq!(limit.current_limit(), 2);
+        request(StatusCode::OK, Duration::from_secs(0))?;
+        assert_eq!(limit.current_limit(), 2);
+        request(StatusCode::OK, Duration::from_secs(0))?;
+        assert_eq!(limit.current_limit(), 3);
+
+        tx2.send(Response::new(()))
+            .expect("the receiver has been dropped");
+        assert_eq!(future2.wait()?.status(), StatusCode::OK);
+        assert_eq!(limit.in_flight(), 0);
+
+        Ok::<(), oneshot::Canceled>(())
+    })
+    .wait()
+    .unwrap();
+}
+
+#[test]
+fn test_layer_roundtrip() {
+    let modify_service = from_layer(into_layer(concurrency_limit(1), ()));
+
+    future::lazy(move || {
+        let (tx, inner) = pending_service();
+        let mut service = modify(&modify_service, inner);
+
+        assert!(service.poll_ready()?.is_ready());
+        let mut future = service.call(Request::new(()));
+        assert!(future.poll()?.is_not_ready());
+
+        tx.send(Response::new(()))
+            .expect("the receiver has been dropped");
+        assert_eq!(future.wait()?.status(), StatusCode::OK);
+
+        Ok::<(), tsukuyomi_service::tower::BoxError>(())
+    })
+    .wait()
+    .unwrap();
+}
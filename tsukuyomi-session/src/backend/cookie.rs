@@ -1,7 +1,9 @@
 use {
-    crate::{Backend, RawSession},
+    crate::{
+        expiry::{self, Expiry, Metadata, Stored},
+        Backend, RawSession,
+    },
     cookie::{Cookie, CookieBuilder},
-    serde_json,
     std::{borrow::Cow, collections::HashMap, fmt, sync::Arc},
     tsukuyomi::{
         error::{Error, Result},
@@ -74,6 +76,7 @@ impl CookieBackend {
                 security,
                 cookie_name: "tsukuyomi-session".into(),
                 builder: Box::new(|cookie| cookie),
+                expiry: Expiry::default(),
             }),
         }
     }
@@ -107,7 +110,18 @@ impl CookieBackend {
         self
     }
 
+    /// Sets the lifetime of sessions.
+    ///
+    /// Since the timestamps of the session are stored in the Cookie entry,
+    /// they can be modified by the client unless the entry is signed or encrypted.
+    pub fn expiry(mut self, expiry: Expiry) -> Self {
+        self.inner_mut().expiry = expiry;
+        self
+    }
+
     /// Sets the functions for modifying the saved Cookie entry.
+    ///
+    /// The attribute `Max-Age` is overwritten if the Cookie entry is persistent.
     pub fn builder(
         mut self,
         builder: impl Fn(CookieBuilder) -> CookieBuilder + Send + Sync + 'static,
@@ -121,6 +135,7 @@ struct CookieBackendInner {
     security: Security,
     cookie_name: Cow<'static, str>,
     builder: Box<dyn Fn(CookieBuilder) -> CookieBuilder + Send + Sync + 'static>,
+    expiry: Expiry,
 }
 
 #[cfg_attr(tarpaulin, skip)]
@@ -129,44 +144,52 @@ impl fmt::Debug for CookieBackendInner {
         f.debug_struct("CookieBackendInner")
             .field("security", &self.security)
             .field("cookie_name", &self.cookie_name)
+            .field("expiry", &self.expiry)
             .finish()
     }
 }
 
 impl CookieBackendInner {
-    fn deserialize(&self, s: &str) -> Result<HashMap<String, String>> {
-        serde_json::from_str(s).map_err(tsukuyomi::error::bad_request)
-    }
-
-    fn serialize(&self, map: &HashMap<String, String>) -> String {
-        serde_json::to_string(&map).expect("should be success")
-    }
-
-    fn read(&self, input: &mut Input<'_>) -> tsukuyomi::Result<Inner> {
+    fn read(&self, input: &mut Input<'_>) -> Result<(Inner, Metadata, bool)> {
+        let now = expiry::now();
         match self.security.get(&*self.cookie_name, input.cookies)? {
             Some(cookie) => {
-                let map = self.deserialize(cookie.value())?;
-                Ok(Inner::Some(map))
+                let Stored { data, mut meta } =
+                    Stored::from_json(cookie.value(), now) //
+                        .map_err(tsukuyomi::error::bad_request)?;
+                if self.expiry.is_expired(&meta, now) {
+                    return Ok((Inner::Empty, Metadata::new(now), true));
+                }
+                meta.touch(now);
+                Ok((Inner::Some(data), meta, false))
             }
-            None => Ok(Inner::Empty),
+            None => Ok((Inner::Empty, Metadata::new(now), false)),
         }
     }
 
-    fn write(&self, input: &mut Input<'_>, inner: Inner) -> tsukuyomi::Result<()> {
-        match inner {
+    fn remove(&self, input: &mut Input<'_>) -> Result<()> {
+        // The removal cookie needs to have the same path and domain as the original one.
+        let cookie = (self.builder)(Cookie::build(self.cookie_name.clone(), "")).finish();
+        input.cookies.jar()?.remove(cookie);
+        Ok(())
+    }
+
+    fn write(&self, input: &mut Input<'_>, session: CookieSession) -> Result<()> {
+        match session.inner {
+            Inner::Empty if session.expired => self.remove(input)?,
             Inner::Empty => {}
-            Inner::Some(map) => {
-                let value = self.serialize(&map);
-                let cookie =
+            // the access time is stored in the Cookie entry and the entry has to be
+            // re-issued at each access if the idle timeout is enabled.
+            Inner::Some(..) if !session.modified && !self.expiry.needs_touch() => {}
+            Inner::Some(data) => {
+                let meta = session.meta;
+                let value = Stored { data, meta }.to_json();
+                let mut cookie =
                     (self.builder)(Cookie::build(self.cookie_name.clone(), value)).finish();
+                self.expiry.apply(&meta, &mut cookie);
                 self.security.add(cookie, input.cookies)?;
             }
-            Inner::Clear => {
-                input
-                    .cookies
-                    .jar()?
-                    .remove(Cookie::named(self.cookie_name.clone()));
-            }
+            Inner::Clear => self.remove(input)?,
         }
 
         Ok(())
@@ -194,10 +217,16 @@ impl TryFuture for ReadSession {
     #[inline]
     fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
         let backend = self.0.take().expect("the future has already been polled");
-        backend
-            .inner
-            .read(input)
-            .map(|inner| CookieSession { inner, backend }.into())
+        backend.inner.read(input).map(|(inner, meta, expired)| {
+            CookieSession {
+                inner,
+                backend,
+                meta,
+                expired,
+                modified: false,
+            }
+            .into()
+        })
     }
 }
 
@@ -205,6 +234,9 @@ impl TryFuture for ReadSession {
 pub struct CookieSession {
     inner: Inner,
     backend: CookieBackend,
+    meta: Metadata,
+    expired: bool,
+    modified: bool,
 }
 
 #[derive(Debug)]
@@ -226,6 +258,7 @@ impl RawSession for CookieSession {
     }
 
     fn set(&mut self, name: &str, value: String) {
+        self.modified = true;
         match self.inner {
             Inner::Empty => {}
             Inner::Some(ref mut map) => {
@@ -249,7 +282,9 @@ impl RawSession for CookieSession {
 
     fn remove(&mut self, name: &str) {
         if let Inner::Some(ref mut map) = self.inner {
-            map.remove(name);
+            if map.remove(name).is_some() {
+                self.modified = true;
+            }
         }
     }
 
@@ -257,6 +292,28 @@ impl RawSession for CookieSession {
         self.inner = Inner::Clear;
     }
 
+    fn regenerate_id(&mut self) {
+        // The session data is stored in the Cookie entry itself and there is
+        // no server-side ID to be replaced.  The entry is re-issued at writing
+        // (with a fresh nonce, if encrypted).
+        self.modified = true;
+    }
+
+    fn destroy(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    fn set_persistent(&mut self, persistent: bool) {
+        if self.meta.persistent != Some(persistent) {
+            self.meta.persistent = Some(persistent);
+            self.modified = true;
+        }
+    }
+
     fn write(self) -> Self::WriteSession {
         WriteSession(Some(self))
     }
@@ -273,10 +330,7 @@ impl TryFuture for WriteSession {
     #[inline]
     fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
         let session = self.0.take().expect("the future has already been polled");
-        session
-            .backend
-            .inner
-            .write(input, session.inner)
-            .map(Into::into)
+        let backend = session.backend.clone();
+        backend.inner.write(input, session).map(Into::into)
     }
 }
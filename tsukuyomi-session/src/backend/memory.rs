@@ -0,0 +1,483 @@
+use {
+    crate::{
+        expiry::{self, Expiry, Metadata},
+        Backend, RawSession,
+    },
+    cookie::Cookie,
+    futures::{Future, Stream},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
+    },
+    tokio_timer::Interval,
+    tsukuyomi::{
+        error::{Error, Result},
+        future::{Async, Poll, TryFuture},
+        input::Input,
+    },
+    uuid::Uuid,
+};
+
+/// A `Backend` that stores the session data in the memory of the current process.
+///
+/// The session data is associated with a random ID stored in a Cookie entry, as
+/// `RedisBackend` does.  Since the data is not shared between processes, this backend
+/// is suitable for single-node deployments and testing.
+#[derive(Debug, Clone)]
+pub struct MemoryBackend {
+    inner: Arc<MemoryBackendInner>,
+}
+
+#[derive(Debug)]
+struct MemoryBackendInner {
+    store: Mutex<Store>,
+    cookie_name: Cow<'static, str>,
+    expiry: Expiry,
+    capacity: Option<usize>,
+    stats: Counters,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    entries: HashMap<Uuid, Entry>,
+    // the session IDs ordered by the last access, for LRU eviction.
+    lru: BTreeMap<u64, Uuid>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    data: HashMap<String, String>,
+    meta: Metadata,
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    expired: AtomicUsize,
+    evicted: AtomicUsize,
+}
+
+/// The statistics of `MemoryBackend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of sessions currently stored.
+    pub sessions: usize,
+    /// The number of reads that found a live session.
+    pub hits: usize,
+    /// The number of reads with an unknown or expired session ID.
+    pub misses: usize,
+    /// The number of sessions removed due to expiry.
+    pub expired: usize,
+    /// The number of sessions evicted due to the capacity limit.
+    pub evicted: usize,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBackend {
+    /// Creates a new `MemoryBackend`.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(MemoryBackendInner {
+                store: Mutex::new(Store::default()),
+                cookie_name: "session-id".into(),
+                expiry: Expiry::default(),
+                capacity: None,
+                stats: Counters::default(),
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut MemoryBackendInner {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the name of Cookie entry for storing the session ID.
+    ///
+    /// The default value is `"session-id"`.
+    pub fn cookie_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.inner_mut().cookie_name = name.into();
+        self
+    }
+
+    /// Sets the lifetime of sessions.
+    pub fn expiry(mut self, expiry: Expiry) -> Self {
+        self.inner_mut().expiry = expiry;
+        self
+    }
+
+    /// Sets the maximum number of sessions.
+    ///
+    /// When the number of sessions exceeds the limit, the least recently used
+    /// sessions are evicted.  By default, the number is not limited.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.inner_mut().capacity = Some(capacity);
+        self
+    }
+
+    /// Removes all expired sessions.
+    ///
+    /// The expired sessions are also removed lazily when they are accessed,
+    /// but this method should be called periodically to release the memory,
+    /// for example by using `sweeper`.
+    pub fn sweep(&self) {
+        let now = expiry::now();
+        let mut store = self.inner.store.lock().unwrap();
+        let expired: Vec<Uuid> = store
+            .entries
+            .iter()
+            .filter(|(_, entry)| self.inner.expiry.is_expired(&entry.meta, now))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in expired {
+            store.remove(id);
+            self.inner.stats.expired.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Creates a `Future` that calls `sweep` at the specified interval.
+    ///
+    /// The returned future never completes and needs to be spawned onto the Tokio runtime.
+    pub fn sweeper(&self, interval: Duration) -> impl Future<Item = (), Error = ()> + Send {
+        let backend = self.clone();
+        Interval::new(Instant::now() + interval, interval)
+            .map_err(|_| ())
+            .for_each(move |_| {
+                backend.sweep();
+                Ok(())
+            })
+    }
+
+    /// Returns the statistics of this backend.
+    pub fn stats(&self) -> Stats {
+        let sessions = self.inner.store.lock().unwrap().entries.len();
+        let stats = &self.inner.stats;
+        Stats {
+            sessions,
+            hits: stats.hits.load(Ordering::Relaxed),
+            misses: stats.misses.load(Ordering::Relaxed),
+            expired: stats.expired.load(Ordering::Relaxed),
+            evicted: stats.evicted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Store {
+    fn touch(&mut self, id: Uuid) {
+        self.clock += 1;
+        let tick = self.clock;
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.lru.remove(&entry.tick);
+            self.lru.insert(tick, id);
+            entry.tick = tick;
+        }
+    }
+
+    fn remove(&mut self, id: Uuid) -> Option<Entry> {
+        let entry = self.entries.remove(&id)?;
+        self.lru.remove(&entry.tick);
+        Some(entry)
+    }
+
+    fn pop_lru(&mut self) -> Option<Entry> {
+        let id = *self.lru.values().next()?;
+        self.remove(id)
+    }
+}
+
+impl MemoryBackendInner {
+    fn get_session_id(&self, input: &mut Input<'_>) -> Result<Option<Uuid>> {
+        match input.cookies.jar()?.get(&self.cookie_name) {
+            Some(cookie) => {
+                let session_id = cookie
+                    .value()
+                    .parse()
+                    .map_err(tsukuyomi::error::bad_request)?;
+                Ok(Some(session_id))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn add_cookie(&self, input: &mut Input<'_>, id: Uuid, meta: &Metadata) -> Result<()> {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), id.to_string());
+        self.expiry.apply(meta, &mut cookie);
+        input.cookies.jar()?.add(cookie);
+        Ok(())
+    }
+
+    fn load(&self, id: Uuid, now: u64) -> Option<(HashMap<String, String>, Metadata)> {
+        let mut store = self.store.lock().unwrap();
+
+        let expired = match store.entries.get(&id) {
+            Some(entry) => self.expiry.is_expired(&entry.meta, now),
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+        if expired {
+            store.remove(id);
+            self.stats.expired.fetch_add(1, Ordering::Relaxed);
+            self.stats.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        store.touch(id);
+        self.stats.hits.fetch_add(1, Ordering::Relaxed);
+        store.entries.get_mut(&id).map(|entry| {
+            entry.meta.touch(now);
+            (entry.data.clone(), entry.meta)
+        })
+    }
+
+    fn save(&self, id: Uuid, data: HashMap<String, String>, meta: Metadata) {
+        let mut store = self.store.lock().unwrap();
+
+        if let Some(entry) = store.entries.get_mut(&id) {
+            entry.data = data;
+            entry.meta = meta;
+        } else {
+            store.entries.insert(
+                id,
+                Entry {
+                    data,
+                    meta,
+                    tick: 0,
+                },
+            );
+        }
+        store.touch(id);
+
+        if let Some(capacity) = self.capacity {
+            while store.entries.len() > capacity {
+                if store.pop_lru().is_none() {
+                    break;
+                }
+                self.stats.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn rename(&self, old_id: Uuid, new_id: Uuid) {
+        let mut store = self.store.lock().unwrap();
+        if let Some(entry) = store.remove(old_id) {
+            let tick = entry.tick;
+            store.entries.insert(new_id, entry);
+            store.lru.insert(tick, new_id);
+        }
+    }
+
+    fn remove(&self, id: Uuid) {
+        self.store.lock().unwrap().remove(id);
+    }
+}
+
+impl Backend for MemoryBackend {
+    type Session = MemorySession;
+    type ReadError = Error;
+    type ReadSession = ReadSession;
+
+    fn read(&self) -> Self::ReadSession {
+        ReadSession(Some(self.clone()))
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct ReadSession(Option<MemoryBackend>);
+
+impl TryFuture for ReadSession {
+    type Ok = MemorySession;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        let backend = self.0.take().expect("the future has already been polled");
+
+        let now = expiry::now();
+        let session_id = backend.inner.get_session_id(input)?;
+        let loaded = session_id.and_then(|id| backend.inner.load(id, now));
+        let (inner, meta, session_id, expired) = match loaded {
+            Some((map, meta)) => (Inner::Some(map), meta, session_id, false),
+            // the session ID not associated with any data is discarded
+            // so that the client cannot choose the ID of the new session.
+            None => (Inner::Empty, Metadata::new(now), None, session_id.is_some()),
+        };
+
+        Ok(Async::Ready(MemorySession {
+            inner,
+            backend,
+            session_id,
+            regenerate_id: false,
+            meta,
+            expired,
+            modified: false,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct MemorySession {
+    inner: Inner,
+    backend: MemoryBackend,
+    session_id: Option<Uuid>,
+    regenerate_id: bool,
+    meta: Metadata,
+    expired: bool,
+    modified: bool,
+}
+
+#[derive(Debug)]
+enum Inner {
+    Empty,
+    Some(HashMap<String, String>),
+    Clear,
+}
+
+impl RawSession for MemorySession {
+    type WriteError = Error;
+    type WriteSession = WriteSession;
+
+    fn get(&self, name: &str) -> Option<&str> {
+        match self.inner {
+            Inner::Some(ref map) => map.get(name).map(|s| &**s),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.modified = true;
+        match self.inner {
+            Inner::Empty => {}
+            Inner::Some(ref mut map) => {
+                map.insert(name.to_owned(), value);
+                return;
+            }
+            Inner::Clear => return,
+        }
+
+        match std::mem::replace(&mut self.inner, Inner::Empty) {
+            Inner::Empty => {
+                self.inner = Inner::Some({
+                    let mut map = HashMap::new();
+                    map.insert(name.to_owned(), value);
+                    map
+                });
+            }
+            Inner::Some(..) | Inner::Clear => unreachable!(),
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Inner::Some(ref mut map) = self.inner {
+            if map.remove(name).is_some() {
+                self.modified = true;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn regenerate_id(&mut self) {
+        self.regenerate_id = true;
+        self.modified = true;
+    }
+
+    fn destroy(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    fn set_persistent(&mut self, persistent: bool) {
+        if self.meta.persistent != Some(persistent) {
+            self.meta.persistent = Some(persistent);
+            self.modified = true;
+        }
+    }
+
+    fn write(self) -> Self::WriteSession {
+        WriteSession(Some(self))
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct WriteSession(Option<MemorySession>);
+
+impl TryFuture for WriteSession {
+    type Ok = ();
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        let MemorySession {
+            inner,
+            backend,
+            session_id,
+            regenerate_id,
+            meta,
+            expired,
+            modified,
+        } = self.0.take().expect("the future has already been polled");
+        let backend = &backend.inner;
+
+        match inner {
+            Inner::Empty if expired => {
+                input
+                    .cookies
+                    .jar()?
+                    .remove(Cookie::named(backend.cookie_name.clone()));
+            }
+            Inner::Empty => {}
+            // The access has already been recorded at reading, and only
+            // the persistent Cookie entry needs to be refreshed.
+            Inner::Some(..) if !modified => {
+                if let Some(session_id) = session_id {
+                    if backend.expiry.needs_touch() && backend.expiry.is_persistent(&meta) {
+                        backend.add_cookie(input, session_id, &meta)?;
+                    }
+                }
+            }
+            Inner::Some(map) => {
+                let session_id = match session_id {
+                    Some(old_id) if regenerate_id => {
+                        let new_id = Uuid::new_v4();
+                        backend.rename(old_id, new_id);
+                        new_id
+                    }
+                    Some(session_id) => session_id,
+                    None => Uuid::new_v4(),
+                };
+                backend.save(session_id, map, meta);
+                backend.add_cookie(input, session_id, &meta)?;
+            }
+            Inner::Clear => {
+                if let Some(session_id) = session_id {
+                    backend.remove(session_id);
+                    input
+                        .cookies
+                        .jar()?
+                        .remove(Cookie::named(backend.cookie_name.clone()));
+                }
+            }
+        }
+
+        Ok(Async::Ready(()))
+    }
+}
@@ -1,7 +1,10 @@
 #![cfg(feature = "use-redis")]
 
 use {
-    crate::{Backend, RawSession},
+    crate::{
+        expiry::{self, Expiry, Metadata, Stored},
+        Backend, RawSession,
+    },
     cookie::Cookie,
     futures::try_ready,
     redis::{r#async::Connection, Client, RedisFuture},
@@ -16,6 +19,10 @@ use {
 };
 
 /// A `Backend` using Redis.
+///
+/// The session data is fetched from Redis when the session is read, only if the
+/// client sent a session ID.  Use `lazy_session` to defer the read until the
+/// handler actually accesses the session.
 #[derive(Debug, Clone)]
 pub struct RedisBackend {
     inner: Arc<RedisBackendInner>,
@@ -29,7 +36,7 @@ impl RedisBackend {
                 client,
                 key_prefix: "tsukuyomi-session".into(),
                 cookie_name: "session-id".into(),
-                timeout: None,
+                expiry: Expiry::default(),
             }),
         }
     }
@@ -54,11 +61,26 @@ impl RedisBackend {
         self
     }
 
+    /// Sets the lifetime of sessions.
+    ///
+    /// The remaining lifetime of the session is used as the TTL of the key in Redis.
+    /// If the session is not modified, the idle timeout is slid by `PEXPIRE`
+    /// instead of rewriting the session data.
+    pub fn expiry(mut self, expiry: Expiry) -> Self {
+        self.inner_mut().expiry = expiry;
+        self
+    }
+
     /// Sets the timeout to be used at storing the session data in Redis.
     ///
     /// By default, the timeout is not set.
+    #[deprecated(
+        since = "0.2.1",
+        note = "use `RedisBackend::expiry` with `Expiry::idle_timeout` instead."
+    )]
     pub fn timeout(mut self, timeout: Duration) -> Self {
-        self.inner_mut().timeout = Some(timeout);
+        let inner = self.inner_mut();
+        inner.expiry = inner.expiry.clone().idle_timeout(timeout);
         self
     }
 }
@@ -68,7 +90,7 @@ struct RedisBackendInner {
     client: Client,
     key_prefix: Cow<'static, str>,
     cookie_name: Cow<'static, str>,
-    timeout: Option<Duration>,
+    expiry: Expiry,
 }
 
 impl RedisBackendInner {
@@ -76,6 +98,13 @@ impl RedisBackendInner {
         format!("{}:{}", self.key_prefix, id)
     }
 
+    fn add_cookie(&self, input: &mut Input<'_>, id: Uuid, meta: &Metadata) -> Result<()> {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), id.to_string());
+        self.expiry.apply(meta, &mut cookie);
+        input.cookies.jar()?.add(cookie);
+        Ok(())
+    }
+
     fn get_session_id(&self, input: &mut Input<'_>) -> Result<Option<Uuid>> {
         match input.cookies.jar()?.get(&self.cookie_name) {
             Some(cookie) => {
@@ -107,8 +136,13 @@ impl Backend for RedisBackend {
 pub struct RedisSession {
     inner: Inner,
     backend: RedisBackend,
-    conn: Connection,
+    // `None` if the connection has not been established yet.
+    conn: Option<Connection>,
     session_id: Option<Uuid>,
+    regenerate_id: bool,
+    meta: Metadata,
+    expired: bool,
+    modified: bool,
 }
 
 #[derive(Debug)]
@@ -118,6 +152,21 @@ enum Inner {
     Clear,
 }
 
+impl RedisSession {
+    fn empty(backend: RedisBackend, conn: Option<Connection>, expired: bool) -> Self {
+        RedisSession {
+            inner: Inner::Empty,
+            backend,
+            conn,
+            session_id: None,
+            regenerate_id: false,
+            meta: Metadata::new(expiry::now()),
+            expired,
+            modified: false,
+        }
+    }
+}
+
 impl RawSession for RedisSession {
     type WriteError = Error;
     type WriteSession = WriteSession;
@@ -130,6 +179,7 @@ impl RawSession for RedisSession {
     }
 
     fn set(&mut self, name: &str, value: String) {
+        self.modified = true;
         match self.inner {
             Inner::Empty => {}
             Inner::Some(ref mut map) => {
@@ -153,7 +203,9 @@ impl RawSession for RedisSession {
 
     fn remove(&mut self, name: &str) {
         if let Inner::Some(ref mut map) = self.inner {
-            map.remove(name);
+            if map.remove(name).is_some() {
+                self.modified = true;
+            }
         }
     }
 
@@ -161,6 +213,26 @@ impl RawSession for RedisSession {
         self.inner = Inner::Clear;
     }
 
+    fn regenerate_id(&mut self) {
+        self.regenerate_id = true;
+        self.modified = true;
+    }
+
+    fn destroy(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    fn set_persistent(&mut self, persistent: bool) {
+        if self.meta.persistent != Some(persistent) {
+            self.meta.persistent = Some(persistent);
+            self.modified = true;
+        }
+    }
+
     fn write(self) -> Self::WriteSession {
         WriteSession::Init(Some(self))
     }
@@ -176,8 +248,8 @@ enum ReadSessionState {
     Init,
     Connecting {
         future: RedisFuture<Connection>,
-        key_name: Option<String>,
-        session_id: Option<Uuid>,
+        key_name: String,
+        session_id: Uuid,
     },
     Fetch {
         future: RedisFuture<(Connection, Option<String>)>,
@@ -196,40 +268,46 @@ impl TryFuture for ReadSession {
             let (conn, value) = match self.state {
                 Init => {
                     let backend = self.backend.as_ref().expect("unexpected condition");
-                    let session_id = backend.inner.get_session_id(input)?;
-                    let key_name = session_id
-                        .as_ref()
-                        .map(|session_id| backend.inner.generate_redis_key(session_id));
-                    self.state = ReadSessionState::Connecting {
-                        future: backend.inner.client.get_async_connection(),
-                        key_name,
-                        session_id,
-                    };
-                    continue;
+                    match backend.inner.get_session_id(input)? {
+                        Some(session_id) => {
+                            self.state = ReadSessionState::Connecting {
+                                future: backend.inner.client.get_async_connection(),
+                                key_name: backend.inner.generate_redis_key(&session_id),
+                                session_id,
+                            };
+                            continue;
+                        }
+                        None => {
+                            // The connection is established lazily at writing,
+                            // only if the new session is created.
+                            let backend = self
+                                .backend
+                                .take()
+                                .expect("the future has already been polled.");
+                            self.state = Done;
+                            return Ok(Async::Ready(RedisSession::empty(backend, None, false)));
+                        }
+                    }
                 }
                 Connecting { ref mut future, .. } => {
                     let conn = try_ready!(future
                         .poll()
                         .map_err(tsukuyomi::error::internal_server_error));
-                    (Some(conn), None)
-                }
-                Fetch { ref mut future, .. } => {
-                    let (conn, value) = try_ready!(future
-                        .poll()
-                        .map_err(tsukuyomi::error::internal_server_error));
-                    (Some(conn), value)
+                    (conn, None)
                 }
+                Fetch { ref mut future, .. } => try_ready!(future
+                    .poll()
+                    .map_err(tsukuyomi::error::internal_server_error)),
                 Done => panic!("unexpected state"),
             };
 
-            match (mem::replace(&mut self.state, Done), conn, value) {
+            match (mem::replace(&mut self.state, Done), value) {
                 (
                     Connecting {
-                        key_name: Some(key_name),
-                        session_id: Some(session_id),
+                        key_name,
+                        session_id,
                         ..
                     },
-                    Some(conn),
                     None,
                 ) => {
                     self.state = Fetch {
@@ -238,39 +316,38 @@ impl TryFuture for ReadSession {
                     };
                 }
 
-                (Fetch { session_id, .. }, Some(conn), Some(value)) => {
-                    let map = serde_json::from_str(&value)
+                (Fetch { session_id, .. }, Some(value)) => {
+                    let backend = self
+                        .backend
+                        .take()
+                        .expect("the future has already been polled.");
+                    let now = expiry::now();
+                    let Stored { data, mut meta } = Stored::from_json(&value, now)
                         .map_err(tsukuyomi::error::internal_server_error)?;
+                    // the idle timeout is handled by the TTL of the key.
+                    if backend.inner.expiry.is_expired_absolutely(&meta, now) {
+                        return Ok(Async::Ready(RedisSession::empty(backend, Some(conn), true)));
+                    }
+                    meta.touch(now);
                     return Ok(Async::Ready(RedisSession {
-                        inner: Inner::Some(map),
-                        backend: self
-                            .backend
-                            .take()
-                            .expect("the future has already been polled."),
-                        conn,
+                        inner: Inner::Some(data),
+                        backend,
+                        conn: Some(conn),
                         session_id: Some(session_id),
+                        regenerate_id: false,
+                        meta,
+                        expired: false,
+                        modified: false,
                     }));
                 }
 
-                (
-                    Connecting {
-                        session_id: None,
-                        key_name: None,
-                        ..
-                    },
-                    Some(conn),
-                    None,
-                )
-                | (Fetch { .. }, Some(conn), None) => {
-                    return Ok(Async::Ready(RedisSession {
-                        inner: Inner::Empty,
-                        backend: self
-                            .backend
-                            .take()
-                            .expect("the future has already been polled."),
-                        conn,
-                        session_id: None,
-                    }));
+                // the key has already been expired by Redis.
+                (Fetch { .. }, None) => {
+                    let backend = self
+                        .backend
+                        .take()
+                        .expect("the future has already been polled.");
+                    return Ok(Async::Ready(RedisSession::empty(backend, Some(conn), true)));
                 }
 
                 _ => unreachable!("unexpected condition"),
@@ -282,9 +359,108 @@ impl TryFuture for ReadSession {
 #[allow(missing_debug_implementations)]
 pub enum WriteSession {
     Init(Option<RedisSession>),
+    Connecting {
+        future: RedisFuture<Connection>,
+        pipe: Option<redis::Pipeline>,
+    },
     Op(RedisFuture<(Connection, ())>),
 }
 
+impl WriteSession {
+    /// Determines the commands to be sent to Redis, and updates the Cookie entry.
+    fn start(
+        session: RedisSession,
+        input: &mut Input<'_>,
+    ) -> Result<Option<(RedisBackend, Option<Connection>, redis::Pipeline)>> {
+        let RedisSession {
+            inner,
+            backend,
+            conn,
+            session_id,
+            regenerate_id,
+            meta,
+            expired,
+            modified,
+        } = session;
+
+        let mut pipe = redis::pipe();
+        match inner {
+            Inner::Empty => {
+                if expired {
+                    input
+                        .cookies
+                        .jar()?
+                        .remove(Cookie::named(backend.inner.cookie_name.clone()));
+                }
+                return Ok(None);
+            }
+
+            // the session is not modified, and only the TTL of the key is extended.
+            Inner::Some(..) if !modified => {
+                let session_id = match session_id {
+                    Some(session_id) if backend.inner.expiry.needs_touch() => session_id,
+                    _ => return Ok(None),
+                };
+                if backend.inner.expiry.is_persistent(&meta) {
+                    backend.inner.add_cookie(input, session_id, &meta)?;
+                }
+                if let Some(ttl) = backend.inner.expiry.ttl(&meta) {
+                    pipe.cmd("PEXPIRE")
+                        .arg(backend.inner.generate_redis_key(&session_id))
+                        .arg(expiry::as_millis(ttl))
+                        .ignore();
+                }
+            }
+
+            Inner::Some(value) => {
+                let (old_id, session_id) = match session_id {
+                    Some(old_id) if regenerate_id => (Some(old_id), Uuid::new_v4()),
+                    Some(session_id) => (None, session_id),
+                    None => (None, Uuid::new_v4()),
+                };
+                backend.inner.add_cookie(input, session_id, &meta)?;
+                let redis_key = backend.inner.generate_redis_key(&session_id);
+
+                let value = Stored { data: value, meta }.to_json();
+                pipe.atomic();
+                if let Some(old_id) = old_id {
+                    // The old key is removed so that the old session ID cannot be used
+                    // any more.  Unlike `RENAME`, `DEL` does not fail even if the old key
+                    // has expired after reading the session.
+                    pipe.cmd("DEL")
+                        .arg(backend.inner.generate_redis_key(&old_id))
+                        .ignore();
+                }
+                match backend.inner.expiry.ttl(&meta) {
+                    Some(ttl) => pipe
+                        .cmd("PSETEX")
+                        .arg(redis_key)
+                        .arg(expiry::as_millis(ttl))
+                        .arg(value)
+                        .ignore(),
+                    None => pipe.cmd("SET").arg(redis_key).arg(value).ignore(),
+                };
+            }
+
+            Inner::Clear => {
+                let session_id = match session_id {
+                    Some(session_id) => session_id,
+                    None => return Ok(None),
+                };
+                input
+                    .cookies
+                    .jar()?
+                    .remove(Cookie::named(backend.inner.cookie_name.clone()));
+                pipe.cmd("DEL")
+                    .arg(backend.inner.generate_redis_key(&session_id))
+                    .ignore();
+            }
+        }
+
+        Ok(Some((backend, conn, pipe)))
+    }
+}
+
 impl TryFuture for WriteSession {
     type Ok = ();
     type Error = Error;
@@ -293,60 +469,26 @@ impl TryFuture for WriteSession {
         loop {
             *self = match self {
                 WriteSession::Init(ref mut session) => {
-                    let RedisSession {
-                        inner,
-                        backend,
-                        conn,
-                        session_id,
-                    } = session.take().unwrap();
-
-                    match inner {
-                        Inner::Empty => return Ok(Async::Ready(())),
-
-                        Inner::Some(value) => {
-                            let session_id = session_id.unwrap_or_else(Uuid::new_v4);
-                            match input.cookies.jar() {
-                                Ok(jar) => jar.add(Cookie::new(
-                                    backend.inner.cookie_name.clone(),
-                                    session_id.to_string(),
-                                )),
-                                Err(err) => return Err(err),
-                            }
-                            let redis_key = backend.inner.generate_redis_key(&session_id);
-
-                            let value = serde_json::to_string(&value).expect("should be successed");
-                            let op = match backend.inner.timeout {
-                                Some(timeout) => redis::cmd("SETEX")
-                                    .arg(redis_key)
-                                    .arg(timeout.as_secs())
-                                    .arg(value)
-                                    .query_async(conn),
-                                None => redis::cmd("SET")
-                                    .arg(redis_key)
-                                    .arg(value)
-                                    .query_async(conn),
-                            };
-                            WriteSession::Op(op)
-                        }
-
-                        Inner::Clear => {
-                            let session_id = if let Some(session_id) = session_id {
-                                session_id
-                            } else {
-                                return Ok(Async::Ready(()));
-                            };
-                            match input.cookies.jar() {
-                                Ok(jar) => {
-                                    jar.remove(Cookie::named(backend.inner.cookie_name.clone()))
-                                }
-                                Err(err) => return Err(err),
-                            }
-                            let redis_key = backend.inner.generate_redis_key(&session_id);
-                            let op = redis::cmd("DEL").arg(redis_key).query_async(conn);
-                            WriteSession::Op(op)
-                        }
+                    let session = session.take().expect("the future has already been polled");
+                    match WriteSession::start(session, input)? {
+                        Some((_, Some(conn), pipe)) => WriteSession::Op(pipe.query_async(conn)),
+                        Some((backend, None, pipe)) => WriteSession::Connecting {
+                            future: backend.inner.client.get_async_connection(),
+                            pipe: Some(pipe),
+                        },
+                        None => return Ok(Async::Ready(())),
                     }
                 }
+                WriteSession::Connecting {
+                    ref mut future,
+                    ref mut pipe,
+                } => {
+                    let conn = try_ready!(future
+                        .poll()
+                        .map_err(tsukuyomi::error::internal_server_error));
+                    let pipe = pipe.take().expect("the future has already been polled");
+                    WriteSession::Op(pipe.query_async(conn))
+                }
                 WriteSession::Op(ref mut op) => {
                     return op
                         .poll()
@@ -0,0 +1,427 @@
+use {
+    crate::{
+        expiry::{self, Expiry, Metadata, Stored},
+        store::SessionStore,
+        Backend, RawSession,
+    },
+    cookie::Cookie,
+    futures::Future,
+    std::{borrow::Cow, collections::HashMap, fmt, sync::Arc},
+    tsukuyomi::{
+        error::{Error, Result},
+        future::{Async, Poll, TryFuture},
+        input::Input,
+    },
+    uuid::Uuid,
+};
+
+/// A `Backend` that stores the session data into a `SessionStore`.
+///
+/// The session data is associated with a random ID stored in a Cookie entry, as
+/// `RedisBackend` does.  As with `RedisBackend`, use `lazy_session` to defer
+/// loading the session until the handler actually accesses it.
+pub struct StoreBackend<S> {
+    inner: Arc<StoreBackendInner<S>>,
+}
+
+struct StoreBackendInner<S> {
+    store: S,
+    cookie_name: Cow<'static, str>,
+    expiry: Expiry,
+}
+
+impl<S> fmt::Debug for StoreBackend<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreBackend")
+            .field("store", &self.inner.store)
+            .field("cookie_name", &self.inner.cookie_name)
+            .field("expiry", &self.inner.expiry)
+            .finish()
+    }
+}
+
+impl<S> Clone for StoreBackend<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> StoreBackend<S>
+where
+    S: SessionStore,
+{
+    /// Creates a `StoreBackend` using the specified `SessionStore`.
+    pub fn new(store: S) -> Self {
+        Self {
+            inner: Arc::new(StoreBackendInner {
+                store,
+                cookie_name: "session-id".into(),
+                expiry: Expiry::default(),
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut StoreBackendInner<S> {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the name of Cookie entry for storing the session ID.
+    ///
+    /// The default value is `"session-id"`.
+    pub fn cookie_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.inner_mut().cookie_name = name.into();
+        self
+    }
+
+    /// Sets the lifetime of sessions.
+    ///
+    /// The remaining lifetime of the session is passed to the store as the TTL.
+    /// If the session is not modified, the idle timeout is slid by `SessionStore::touch`
+    /// instead of rewriting the session data.
+    pub fn expiry(mut self, expiry: Expiry) -> Self {
+        self.inner_mut().expiry = expiry;
+        self
+    }
+
+    /// Returns the reference to the underlying `SessionStore`.
+    pub fn store(&self) -> &S {
+        &self.inner.store
+    }
+}
+
+impl<S> StoreBackendInner<S> {
+    fn get_session_id(&self, input: &mut Input<'_>) -> Result<Option<Uuid>> {
+        match input.cookies.jar()?.get(&self.cookie_name) {
+            Some(cookie) => {
+                let session_id = cookie
+                    .value()
+                    .parse()
+                    .map_err(tsukuyomi::error::bad_request)?;
+                Ok(Some(session_id))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn add_cookie(&self, input: &mut Input<'_>, id: Uuid, meta: &Metadata) -> Result<()> {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), id.to_string());
+        self.expiry.apply(meta, &mut cookie);
+        input.cookies.jar()?.add(cookie);
+        Ok(())
+    }
+
+    fn remove_cookie(&self, input: &mut Input<'_>) -> Result<()> {
+        input
+            .cookies
+            .jar()?
+            .remove(Cookie::named(self.cookie_name.clone()));
+        Ok(())
+    }
+}
+
+impl<S> Backend for StoreBackend<S>
+where
+    S: SessionStore,
+{
+    type Session = StoreSession<S>;
+    type ReadError = Error;
+    type ReadSession = ReadSession<S>;
+
+    fn read(&self) -> Self::ReadSession {
+        ReadSession {
+            backend: Some(self.clone()),
+            load: None,
+        }
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct ReadSession<S: SessionStore> {
+    backend: Option<StoreBackend<S>>,
+    load: Option<(S::Load, Uuid)>,
+}
+
+impl<S> TryFuture for ReadSession<S>
+where
+    S: SessionStore,
+{
+    type Ok = StoreSession<S>;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        loop {
+            let stored = match self.load {
+                Some((ref mut load, session_id)) => {
+                    let value = futures::try_ready!(load
+                        .poll()
+                        .map_err(tsukuyomi::error::internal_server_error));
+                    value.map(|value| (value, session_id))
+                }
+                None => {
+                    let backend = self
+                        .backend
+                        .as_ref()
+                        .expect("the future has already been polled");
+                    if let Some(session_id) = backend.inner.get_session_id(input)? {
+                        let load = backend.inner.store.load(&session_id.to_string());
+                        self.load = Some((load, session_id));
+                        continue;
+                    }
+                    None
+                }
+            };
+            // the client sent a session ID, but the associated data is missing or expired.
+            let expired = self.load.is_some();
+
+            let backend = self
+                .backend
+                .take()
+                .expect("the future has already been polled");
+            let now = expiry::now();
+
+            let loaded = match stored {
+                Some((value, session_id)) => {
+                    let Stored { data, mut meta } = Stored::from_json(&value, now)
+                        .map_err(tsukuyomi::error::internal_server_error)?;
+                    // the idle timeout is handled by the TTL in the store.
+                    if backend.inner.expiry.is_expired_absolutely(&meta, now) {
+                        None
+                    } else {
+                        meta.touch(now);
+                        Some((data, meta, session_id))
+                    }
+                }
+                None => None,
+            };
+
+            return Ok(Async::Ready(match loaded {
+                Some((data, meta, session_id)) => StoreSession {
+                    inner: Inner::Some(data),
+                    backend,
+                    session_id: Some(session_id),
+                    regenerate_id: false,
+                    meta,
+                    expired: false,
+                    modified: false,
+                },
+                // the session ID not associated with any data is discarded
+                // so that the client cannot choose the ID of the new session.
+                None => StoreSession {
+                    inner: Inner::Empty,
+                    backend,
+                    session_id: None,
+                    regenerate_id: false,
+                    meta: Metadata::new(now),
+                    expired,
+                    modified: false,
+                },
+            }));
+        }
+    }
+}
+
+/// The session created by `StoreBackend`.
+#[allow(missing_debug_implementations)]
+pub struct StoreSession<S> {
+    inner: Inner,
+    backend: StoreBackend<S>,
+    session_id: Option<Uuid>,
+    regenerate_id: bool,
+    meta: Metadata,
+    expired: bool,
+    modified: bool,
+}
+
+#[derive(Debug)]
+enum Inner {
+    Empty,
+    Some(HashMap<String, String>),
+    Clear,
+}
+
+impl<S> RawSession for StoreSession<S>
+where
+    S: SessionStore,
+{
+    type WriteError = Error;
+    type WriteSession = WriteSession<S>;
+
+    fn get(&self, name: &str) -> Option<&str> {
+        match self.inner {
+            Inner::Some(ref map) => map.get(name).map(|s| &**s),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.modified = true;
+        match self.inner {
+            Inner::Empty => {}
+            Inner::Some(ref mut map) => {
+                map.insert(name.to_owned(), value);
+                return;
+            }
+            Inner::Clear => return,
+        }
+
+        match std::mem::replace(&mut self.inner, Inner::Empty) {
+            Inner::Empty => {
+                self.inner = Inner::Some({
+                    let mut map = HashMap::new();
+                    map.insert(name.to_owned(), value);
+                    map
+                });
+            }
+            Inner::Some(..) | Inner::Clear => unreachable!(),
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Inner::Some(ref mut map) = self.inner {
+            if map.remove(name).is_some() {
+                self.modified = true;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn regenerate_id(&mut self) {
+        self.regenerate_id = true;
+        self.modified = true;
+    }
+
+    fn destroy(&mut self) {
+        self.inner = Inner::Clear;
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    fn set_persistent(&mut self, persistent: bool) {
+        if self.meta.persistent != Some(persistent) {
+            self.meta.persistent = Some(persistent);
+            self.modified = true;
+        }
+    }
+
+    fn write(self) -> Self::WriteSession {
+        WriteSession {
+            session: Some(self),
+            delete: None,
+            save: None,
+            touch: None,
+        }
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct WriteSession<S: SessionStore> {
+    session: Option<StoreSession<S>>,
+    delete: Option<S::Delete>,
+    save: Option<S::Save>,
+    touch: Option<S::Touch>,
+}
+
+impl<S> WriteSession<S>
+where
+    S: SessionStore,
+{
+    fn start(&mut self, input: &mut Input<'_>) -> Result<()> {
+        let StoreSession {
+            inner,
+            backend,
+            session_id,
+            regenerate_id,
+            meta,
+            expired,
+            modified,
+        } = match self.session.take() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        let backend = &backend.inner;
+
+        match inner {
+            Inner::Empty if expired => backend.remove_cookie(input)?,
+            Inner::Empty => {}
+            // the session is not modified, and only its TTL is extended.
+            Inner::Some(..) if !modified => {
+                if let (Some(session_id), true) = (session_id, backend.expiry.needs_touch()) {
+                    if backend.expiry.is_persistent(&meta) {
+                        backend.add_cookie(input, session_id, &meta)?;
+                    }
+                    let ttl = backend.expiry.ttl(&meta);
+                    self.touch = Some(backend.store.touch(&session_id.to_string(), ttl));
+                }
+            }
+            Inner::Some(data) => {
+                let session_id = match session_id {
+                    Some(old_id) if regenerate_id => {
+                        self.delete = Some(backend.store.delete(&old_id.to_string()));
+                        Uuid::new_v4()
+                    }
+                    Some(session_id) => session_id,
+                    None => Uuid::new_v4(),
+                };
+
+                backend.add_cookie(input, session_id, &meta)?;
+
+                let ttl = backend.expiry.ttl(&meta);
+                let value = Stored { data, meta }.to_json();
+                self.save = Some(backend.store.save(&session_id.to_string(), value, ttl));
+            }
+            Inner::Clear => {
+                if let Some(session_id) = session_id {
+                    backend.remove_cookie(input)?;
+                    self.delete = Some(backend.store.delete(&session_id.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> TryFuture for WriteSession<S>
+where
+    S: SessionStore,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        self.start(input)?;
+
+        if let Some(ref mut delete) = self.delete {
+            futures::try_ready!(delete
+                .poll()
+                .map_err(tsukuyomi::error::internal_server_error));
+        }
+        self.delete = None;
+
+        if let Some(ref mut save) = self.save {
+            futures::try_ready!(save.poll().map_err(tsukuyomi::error::internal_server_error));
+        }
+        self.save = None;
+
+        if let Some(ref mut touch) = self.touch {
+            futures::try_ready!(touch
+                .poll()
+                .map_err(tsukuyomi::error::internal_server_error));
+        }
+        self.touch = None;
+
+        Ok(Async::Ready(()))
+    }
+}
@@ -0,0 +1,195 @@
+//! The lifetime management of sessions.
+
+use {
+    cookie::Cookie,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// The configuration of the lifetime of sessions, shared by all backends.
+///
+/// The session expires when it is not accessed within the idle timeout,
+/// or when the absolute timeout has elapsed since its creation.  Each access
+/// to the session slides the idle timeout.
+///
+/// The session is written back to the backend only when it has been modified,
+/// or when the idle timeout needs to be slid.  Hence, without an idle timeout,
+/// the requests that only read the session do not cause any writes.
+///
+/// The Cookie entry is issued as a *persistent* cookie (with `Max-Age` set to
+/// the remaining lifetime of the session) or as a *browser-session* cookie,
+/// which can be chosen for each session by `Session::set_persistent`.
+/// Note that a persistent cookie requires at least one of the timeouts.
+#[derive(Debug, Clone, Default)]
+pub struct Expiry {
+    idle_timeout: Option<Duration>,
+    absolute_timeout: Option<Duration>,
+    persistent: bool,
+}
+
+impl Expiry {
+    /// Creates an `Expiry` without any timeouts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the duration after which the session expires if it is not accessed.
+    ///
+    /// By default, the sessions do not expire by idleness.
+    pub fn idle_timeout(self, timeout: Duration) -> Self {
+        Self {
+            idle_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Sets the duration after which the session expires since its creation,
+    /// regardless of the access.
+    ///
+    /// By default, the lifetime of sessions is not limited.
+    pub fn absolute_timeout(self, timeout: Duration) -> Self {
+        Self {
+            absolute_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Sets whether to issue persistent cookies for the sessions that
+    /// do not choose it explicitly.
+    ///
+    /// The default value is `false`.
+    pub fn persistent(self, persistent: bool) -> Self {
+        Self { persistent, ..self }
+    }
+
+    pub(crate) fn is_expired(&self, meta: &Metadata, now: u64) -> bool {
+        self.remaining(meta, now)
+            .map_or(false, |remaining| remaining == 0)
+    }
+
+    /// Checks only the absolute timeout of the session.
+    ///
+    /// This is used by the backends that slide the idle timeout by updating
+    /// the TTL in the storage, in which the stored access time may be outdated.
+    pub(crate) fn is_expired_absolutely(&self, meta: &Metadata, now: u64) -> bool {
+        self.absolute_timeout
+            .map_or(false, |timeout| meta.created_at + as_millis(timeout) <= now)
+    }
+
+    /// Returns whether an access to the session needs to be recorded in the
+    /// backend even if the session data is not modified.
+    pub(crate) fn needs_touch(&self) -> bool {
+        self.idle_timeout.is_some()
+    }
+
+    /// Returns whether the Cookie entry of the session is persistent.
+    pub(crate) fn is_persistent(&self, meta: &Metadata) -> bool {
+        meta.persistent.unwrap_or(self.persistent)
+    }
+
+    /// Returns the remaining lifetime of the session in milliseconds, assuming
+    /// that it has been accessed at `now`.
+    fn remaining(&self, meta: &Metadata, now: u64) -> Option<u64> {
+        let idle = self
+            .idle_timeout
+            .map(|timeout| (meta.accessed_at + as_millis(timeout)).saturating_sub(now));
+        let absolute = self
+            .absolute_timeout
+            .map(|timeout| (meta.created_at + as_millis(timeout)).saturating_sub(now));
+        match (idle, absolute) {
+            (Some(idle), Some(absolute)) => Some(idle.min(absolute)),
+            (idle, absolute) => idle.or(absolute),
+        }
+    }
+
+    /// Returns the TTL of the session data to be stored in the backend.
+    pub(crate) fn ttl(&self, meta: &Metadata) -> Option<Duration> {
+        self.remaining(meta, meta.accessed_at)
+            .map(Duration::from_millis)
+    }
+
+    /// Applies the lifetime of the session to the Cookie entry.
+    pub(crate) fn apply(&self, meta: &Metadata, cookie: &mut Cookie<'static>) {
+        if self.is_persistent(meta) {
+            if let Some(ttl) = self.remaining(meta, meta.accessed_at) {
+                // rounded up since Max-Age=0 removes the Cookie entry immediately.
+                cookie.set_max_age(time::Duration::seconds(((ttl + 999) / 1000) as i64));
+            }
+        }
+    }
+}
+
+pub(crate) fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+/// Returns the current time as the milliseconds since the UNIX epoch.
+pub(crate) fn now() -> u64 {
+    as_millis(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("the system time is before the UNIX epoch"),
+    )
+}
+
+/// The timestamps of a session, stored along with the session data.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct Metadata {
+    #[serde(rename = "created")]
+    pub(crate) created_at: u64,
+    #[serde(rename = "accessed")]
+    pub(crate) accessed_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) persistent: Option<bool>,
+}
+
+impl Metadata {
+    pub(crate) fn new(now: u64) -> Self {
+        Self {
+            created_at: now,
+            accessed_at: now,
+            persistent: None,
+        }
+    }
+
+    pub(crate) fn touch(&mut self, now: u64) {
+        self.accessed_at = now;
+    }
+}
+
+/// The serialized representation of the session data used by
+/// the cookie and Redis backends.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Stored {
+    pub(crate) data: HashMap<String, String>,
+    pub(crate) meta: Metadata,
+}
+
+impl Stored {
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("should be success")
+    }
+
+    /// Parses the stored session data.
+    ///
+    /// The data stored without the metadata by the previous versions
+    /// is regarded as created at `now`.
+    pub(crate) fn from_json(s: &str, now: u64) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Current(Stored),
+            Legacy(HashMap<String, String>),
+        }
+        Ok(match serde_json::from_str(s)? {
+            Repr::Current(stored) => stored,
+            Repr::Legacy(data) => Stored {
+                data,
+                meta: Metadata::new(now),
+            },
+        })
+    }
+}
@@ -0,0 +1,91 @@
+//! Flash messages, which are stored in the session and live until the next request.
+//!
+//! The flash messages are typically used with the post-redirect-get pattern:
+//! the handler of `POST` adds a message with `Session::flash` and redirects,
+//! and the handler of the subsequent `GET` takes it by `Session::take_flashes`
+//! and renders it.
+//!
+//! `FlashMessage` implements `Display` and can be rendered directly in the
+//! templates, e.g. with Askama:
+//!
+//! ```html
+//! {% for message in flashes %}
+//!   <div class="alert alert-{{ message.level() }}">{{ message }}</div>
+//! {% endfor %}
+//! ```
+
+use {
+    serde::{Deserialize, Serialize},
+    std::fmt,
+};
+
+/// The name of session field for storing the flash messages.
+pub(crate) const FLASH_KEY: &str = "_flash";
+
+/// The level of a flash message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// A message for developers.
+    Debug,
+    /// An informational message.
+    Info,
+    /// A message notifying that an operation has succeeded.
+    Success,
+    /// A message notifying a problem that does not prevent the operation.
+    Warning,
+    /// A message notifying that an operation has failed.
+    Error,
+}
+
+impl Level {
+    /// Returns the lowercase name of this level, e.g. `"warning"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Success => "success",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A flash message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    level: Level,
+    message: String,
+}
+
+impl FlashMessage {
+    /// Creates a new `FlashMessage`.
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the level of this message.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the content of this message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for FlashMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -12,18 +12,31 @@
 #![forbid(clippy::unimplemented)]
 
 pub mod backend;
+pub mod expiry;
+pub mod flash;
+pub mod store;
 mod util;
 
 use {
+    crate::flash::{FlashMessage, Level, FLASH_KEY},
     serde::{de::DeserializeOwned, ser::Serialize},
+    std::any::Any,
     tsukuyomi::{
         error::Error, //
         extractor::Extractor,
         future::{MaybeDone, TryFuture},
+        input::{
+            localmap::{local_key, LocalData},
+            Input,
+        },
         responder::Responder,
+        util::Never,
     },
 };
 
+/// The name of session field for storing the typed session data.
+const SCHEMA_KEY: &str = "_schema";
+
 /// A trait representing the session backend.
 pub trait Backend {
     /// The type of session which will be crated by `ReadSession`.
@@ -98,11 +111,94 @@ pub trait RawSession {
     /// Mark the session data as *cleared*.
     fn clear(&mut self);
 
+    /// Marks the session ID to be replaced with a new one at writing, keeping the session data.
+    ///
+    /// The default implementation does nothing, which is suitable for the backends
+    /// that do not issue session IDs.
+    fn regenerate_id(&mut self) {}
+
+    /// Marks the session as *destroyed*.
+    ///
+    /// At writing, the session data stored in the backend is removed and the Cookie
+    /// entry is expired.  The modifications after calling this method are ignored.
+    ///
+    /// The default implementation only clears the session data.
+    fn destroy(&mut self) {
+        self.clear();
+    }
+
+    /// Returns `true` if the client sent a session that has expired or no longer exists.
+    ///
+    /// The default implementation always returns `false`.
+    fn is_expired(&self) -> bool {
+        false
+    }
+
+    /// Sets whether to issue the Cookie entry of this session as a persistent cookie.
+    ///
+    /// The default implementation ignores the choice.
+    fn set_persistent(&mut self, persistent: bool) {
+        let _ = persistent;
+    }
+
     /// Consumes itself and creates a `TryFuture` to write the modification of session data.
+    ///
+    /// The implementors should avoid accessing the storage and issuing the Cookie entry
+    /// when the session has not been modified, unless the access needs to be recorded
+    /// for sliding the idle timeout.
     fn write(self) -> Self::WriteSession;
 }
 
+/// A session read by a modifier and shared with the extractor `session` in the same request.
+struct SharedSession(Box<dyn Any + Send>);
+
+impl LocalData for SharedSession {
+    local_key! {
+        const KEY: Self;
+    }
+}
+
+/// Stores a session into the request-local data, so that the extractor `session`
+/// reuses it instead of reading the backend again.
+///
+/// This is used by the modifiers which need to access the session before the
+/// handler (e.g. CSRF protection).  Otherwise, the backends issuing the session IDs
+/// would start two sessions for a client that has no session yet.  The session
+/// not taken by the handler should be taken back by `take_shared` and written.
+pub fn share<S>(input: &mut Input<'_>, session: S)
+where
+    S: RawSession + Send + 'static,
+{
+    SharedSession(Box::new(session)).insert_into(input.locals);
+}
+
+/// Takes the session stored by `share` back from the request-local data.
+///
+/// The return value is `None` if the session has already been taken by the
+/// extractor `session`, or if the stored session is not the type of `S`.
+pub fn take_shared<S>(input: &mut Input<'_>) -> Option<S>
+where
+    S: RawSession + Send + 'static,
+{
+    let SharedSession(session) = SharedSession::take_from(input.locals)?;
+    match session.downcast::<S>() {
+        Ok(session) => Some(*session),
+        Err(session) => {
+            SharedSession(session).insert_into(input.locals);
+            None
+        }
+    }
+}
+
 /// Create an `Extractor` which returns a `Session`.
+///
+/// The session is read from the backend before calling the handler, which may
+/// require a round trip to the storage if the client sent a session ID.  If the
+/// handler accesses the session only under some conditions, consider using
+/// `lazy_session` instead.
+///
+/// If a session has been stored by `share` in the current request, it is
+/// returned instead of reading the backend.
 pub fn session<B>(
     backend: B,
 ) -> impl Extractor<
@@ -112,6 +208,7 @@ pub fn session<B>(
 >
 where
     B: Backend,
+    B::Session: Send + 'static,
 {
     tsukuyomi::extractor::extract(move || self::impl_extractor::SessionExtract {
         read_session: backend.read(),
@@ -122,7 +219,7 @@ mod impl_extractor {
     use {
         super::{RawSession, Session},
         tsukuyomi::{
-            future::{Poll, TryFuture},
+            future::{Async, Poll, TryFuture},
             input::Input,
         },
     };
@@ -135,13 +232,16 @@ mod impl_extractor {
     impl<Fut> TryFuture for SessionExtract<Fut>
     where
         Fut: TryFuture,
-        Fut::Ok: RawSession,
+        Fut::Ok: RawSession + Send + 'static,
     {
         type Ok = (Session<Fut::Ok>,);
         type Error = Fut::Error;
 
         #[inline]
         fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+            if let Some(raw) = super::take_shared(input) {
+                return Ok(Async::Ready((Session { raw },)));
+            }
             self.read_session
                 .poll_ready(input)
                 .map(|x| x.map(|raw| (Session { raw },)))
@@ -149,6 +249,144 @@ mod impl_extractor {
     }
 }
 
+/// Create an `Extractor` which returns a `LazySession`.
+///
+/// Unlike `session`, the extraction does not access the backend at all.
+pub fn lazy_session<B>(
+    backend: B,
+) -> impl Extractor<
+    Output = (LazySession<B>,),
+    Error = Never,
+    Extract = impl TryFuture<Ok = (LazySession<B>,), Error = Never>,
+>
+where
+    B: Backend + Clone,
+    B::Session: Send + 'static,
+{
+    tsukuyomi::extractor::value(LazySession { backend })
+}
+
+/// A session which is read from the backend only when it is accessed.
+///
+/// The requests that the handler does not access the session cost neither
+/// the round trip to the storage nor the `Set-Cookie` header.
+///
+/// ```ignore
+/// endpoint::get()
+///     .extract(tsukuyomi_session::lazy_session(backend))
+///     .call(|session: LazySession<_>, params: Params| {
+///         if params.public {
+///             Either::Left(render_public_page())
+///         } else {
+///             Either::Right(session.with(|session| {
+///                 let user: Option<String> = session.get("user")?;
+///                 Ok(render_private_page(user))
+///             }))
+///         }
+///     })
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazySession<B> {
+    backend: B,
+}
+
+impl<B> LazySession<B>
+where
+    B: Backend,
+    B::Session: Send + 'static,
+{
+    /// Reads the session, applies the specified function to it and writes it back.
+    ///
+    /// The returned value is a `Responder` that responds the output of the function.
+    /// As with `Session::finish`, the session is written back to the backend only if
+    /// it has been modified, or if the idle timeout of the session needs to be slid.
+    pub fn with<F, T>(
+        self,
+        f: F,
+    ) -> impl Responder<
+        Response = T::Response,
+        Error = Error,
+        Respond = self::impl_lazy::LazyRespond<B::ReadSession, F, T>, // private
+    >
+    where
+        F: FnOnce(&mut Session<B::Session>) -> tsukuyomi::Result<T>,
+        T: Responder,
+    {
+        tsukuyomi::responder::respond(self::impl_lazy::LazyRespond {
+            state: self::impl_lazy::State::Read(self.backend.read(), Some(f)),
+        })
+    }
+}
+
+mod impl_lazy {
+    use {
+        super::{impl_responder::SessionRespond, RawSession, Session},
+        tsukuyomi::{
+            error::Error,
+            future::{Async, MaybeDone, Poll, TryFuture},
+            input::Input,
+            responder::Responder,
+        },
+    };
+
+    #[allow(missing_debug_implementations)]
+    pub struct LazyRespond<R, F, T>
+    where
+        R: TryFuture,
+        R::Ok: RawSession,
+        T: Responder,
+    {
+        pub(super) state: State<R, F, T>,
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub enum State<R, F, T>
+    where
+        R: TryFuture,
+        R::Ok: RawSession,
+        T: Responder,
+    {
+        Read(R, Option<F>),
+        Respond(SessionRespond<<R::Ok as RawSession>::WriteSession, T::Respond>),
+    }
+
+    impl<R, F, T> TryFuture for LazyRespond<R, F, T>
+    where
+        R: TryFuture,
+        R::Ok: RawSession + Send + 'static,
+        F: FnOnce(&mut Session<R::Ok>) -> tsukuyomi::Result<T>,
+        T: Responder,
+    {
+        type Ok = T::Response;
+        type Error = Error;
+
+        fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+            loop {
+                self.state = match self.state {
+                    State::Read(ref mut read_session, ref mut f) => {
+                        let raw = match super::take_shared(input) {
+                            Some(raw) => raw,
+                            None => match read_session.poll_ready(input) {
+                                Ok(Async::Ready(raw)) => raw,
+                                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                                Err(err) => return Err(err.into()),
+                            },
+                        };
+                        let mut session = Session { raw };
+                        let f = f.take().expect("the future has already been polled");
+                        let output = f(&mut session)?;
+                        State::Respond(SessionRespond {
+                            write_session: MaybeDone::Pending(session.raw.write()),
+                            respond: MaybeDone::Pending(output.respond()),
+                        })
+                    }
+                    State::Respond(ref mut respond) => return respond.poll_ready(input),
+                };
+            }
+        }
+    }
+}
+
 /// An interface of session values.
 #[derive(Debug)]
 pub struct Session<S: RawSession> {
@@ -178,13 +416,17 @@ where
     }
 
     /// Sets a field to this session with serializing the specified value into a string.
+    ///
+    /// If the field already has the same value, the session is not marked as modified.
     pub fn set<T>(&mut self, name: &str, value: T) -> tsukuyomi::error::Result<()>
     where
         T: Serialize,
     {
         let value = serde_json::to_string(&value) //
             .map_err(tsukuyomi::error::internal_server_error)?;
-        self.raw.set(name, value);
+        if self.raw.get(name) != Some(&*value) {
+            self.raw.set(name, value);
+        }
         Ok(())
     }
 
@@ -193,12 +435,110 @@ where
         self.raw.remove(name);
     }
 
+    /// Loads the typed session data as a value of the specified type.
+    ///
+    /// The type is typically a struct deriving `Deserialize`, stored as a whole
+    /// in the reserved field `"_schema"` of this session.  If the session has not
+    /// been saved yet, the value is deserialized from an empty map, so the fields
+    /// should be declared as `Option<T>` or with `#[serde(default)]`.
+    ///
+    /// ```ignore
+    /// #[derive(Debug, Default, Serialize, Deserialize)]
+    /// struct UserSession {
+    ///     user_id: Option<u64>,
+    ///     #[serde(default)]
+    ///     cart: Vec<u64>,
+    /// }
+    ///
+    /// let mut data: UserSession = session.load()?;
+    /// data.cart.push(item_id);
+    /// session.save(&data)?;
+    /// ```
+    pub fn load<T>(&self) -> tsukuyomi::error::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str(self.raw.get(SCHEMA_KEY).unwrap_or("{}"))
+            .map_err(tsukuyomi::error::internal_server_error)
+    }
+
+    /// Saves the value of the specified type as the typed session data.
+    ///
+    /// The value replaces the previously saved one as a whole, so the fields
+    /// removed from the type (e.g. in an earlier version of the application)
+    /// do not remain in the session.  If the value is not changed from the
+    /// saved one, the session is not marked as modified.
+    pub fn save<T>(&mut self, value: &T) -> tsukuyomi::error::Result<()>
+    where
+        T: Serialize,
+    {
+        self.set(SCHEMA_KEY, value)
+    }
+
+    /// Adds a flash message to this session.
+    ///
+    /// The message remains in the session until taken by `take_flashes`,
+    /// typically in the next request.
+    pub fn flash(
+        &mut self,
+        level: Level,
+        message: impl Into<String>,
+    ) -> tsukuyomi::error::Result<()> {
+        let mut flashes: Vec<FlashMessage> = self.get(FLASH_KEY)?.unwrap_or_default();
+        flashes.push(FlashMessage::new(level, message));
+        self.set(FLASH_KEY, flashes)
+    }
+
+    /// Takes the flash messages stored in this session.
+    pub fn take_flashes(&mut self) -> tsukuyomi::error::Result<Vec<FlashMessage>> {
+        let flashes = self.get(FLASH_KEY)?.unwrap_or_default();
+        self.raw.remove(FLASH_KEY);
+        Ok(flashes)
+    }
+
     /// Marks this session cleared.
     pub fn clear(&mut self) {
         self.raw.clear();
     }
 
+    /// Replaces the ID of this session with a new one, keeping the session data.
+    ///
+    /// This method should be called when the privilege level of the client
+    /// changes (e.g. after login) to prevent the session fixation attacks.
+    pub fn regenerate_id(&mut self) {
+        self.raw.regenerate_id();
+    }
+
+    /// Destroys this session.
+    ///
+    /// The session data stored in the backend is removed and the Cookie entry
+    /// is expired.  This method should be called at logout or after changing
+    /// the credentials.
+    pub fn destroy(&mut self) {
+        self.raw.destroy();
+    }
+
+    /// Returns `true` if the client sent a session that has expired or no longer exists.
+    ///
+    /// In that case, this session is empty and a new session is started
+    /// when any field is set.
+    pub fn is_expired(&self) -> bool {
+        self.raw.is_expired()
+    }
+
+    /// Sets whether to issue the Cookie entry of this session as a persistent cookie
+    /// (e.g. "remember me"), or as a browser-session cookie.
+    ///
+    /// The choice is kept in the session for the subsequent requests.  If not set,
+    /// the default value configured in `Expiry` is used.
+    pub fn set_persistent(&mut self, persistent: bool) {
+        self.raw.set_persistent(persistent);
+    }
+
     /// Finalize the current session with the specified output.
+    ///
+    /// The session is written back to the backend only if it has been modified,
+    /// or if the idle timeout of the session needs to be slid.
     pub fn finish<T>(
         self,
         output: T,
@@ -0,0 +1,244 @@
+use {
+    super::SessionStore,
+    crate::expiry,
+    futures::{Async, Future, Poll},
+    serde::{Deserialize, Serialize},
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    },
+    tokio_threadpool::blocking as poll_blocking,
+    uuid::Uuid,
+};
+
+/// A `SessionStore` that saves the session data as files in a directory.
+///
+/// Each session is stored in the file named `<session-id>.json`.  The file
+/// operations are executed in the blocking sections of Tokio's thread pool,
+/// and hence this store cannot be used on the single-threaded runtime.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: Arc<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    value: String,
+}
+
+impl Entry {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= now)
+    }
+}
+
+impl FileStore {
+    /// Creates a `FileStore` that saves the files in the specified directory.
+    ///
+    /// The directory is created at the first write if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Arc::new(dir.into()),
+        }
+    }
+
+    /// Removes all files of the expired sessions.
+    ///
+    /// This method blocks the current thread, and should be called periodically
+    /// in a dedicated thread or a blocking section.
+    pub fn sweep(&self) -> io::Result<()> {
+        let entries = match fs::read_dir(&*self.dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let now = expiry::now();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            if let Some(entry) = read_entry(&path)? {
+                if entry.is_expired(now) {
+                    remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> io::Result<PathBuf> {
+        // The session ID is used as a file name and must not contain path separators.
+        let is_valid = !id.is_empty()
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !is_valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid session ID",
+            ));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn op(&self, id: &str, f: impl FnOnce(PathBuf) -> Op) -> FileOp {
+        FileOp {
+            op: Some(self.path(id).map(f)),
+            dir: self.dir.clone(),
+        }
+    }
+}
+
+impl SessionStore for FileStore {
+    type Error = io::Error;
+    type Load = LoadFile;
+    type Save = FileOp;
+    type Delete = FileOp;
+    type Touch = FileOp;
+
+    fn load(&self, id: &str) -> Self::Load {
+        LoadFile(Some(self.path(id)))
+    }
+
+    fn save(&self, id: &str, value: String, ttl: Option<Duration>) -> Self::Save {
+        self.op(id, |path| Op::Save(path, value, ttl))
+    }
+
+    fn delete(&self, id: &str) -> Self::Delete {
+        self.op(id, Op::Delete)
+    }
+
+    fn touch(&self, id: &str, ttl: Option<Duration>) -> Self::Touch {
+        self.op(id, |path| Op::Touch(path, ttl))
+    }
+}
+
+fn blocking_io<T>(f: impl FnOnce() -> io::Result<T>) -> Poll<T, io::Error> {
+    match poll_blocking(f) {
+        Ok(Async::Ready(ready)) => ready.map(Async::Ready),
+        Ok(Async::NotReady) => Ok(Async::NotReady),
+        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+    }
+}
+
+fn expires_at(ttl: Option<Duration>) -> Option<u64> {
+    ttl.map(|ttl| expiry::now() + expiry::as_millis(ttl))
+}
+
+fn read_entry(path: &Path) -> io::Result<Option<Entry>> {
+    match fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn write_entry(dir: &Path, path: &Path, entry: &Entry) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    // write to a temporary file and rename it, so that the readers never see
+    // the partially written file.
+    let tmp_path = path.with_extension(format!("json.{}.tmp", Uuid::new_v4().to_simple()));
+    let content = serde_json::to_vec(entry).expect("should be success");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path).map_err(|err| {
+        let _ = fs::remove_file(&tmp_path);
+        err
+    })
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct LoadFile(Option<io::Result<PathBuf>>);
+
+impl Future for LoadFile {
+    type Item = Option<String>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let path = match self.0 {
+            Some(Ok(ref path)) => path,
+            Some(Err(..)) => return Err(self.0.take().unwrap().unwrap_err()),
+            None => panic!("the future has already been polled"),
+        };
+
+        let value = futures::try_ready!(blocking_io(|| match read_entry(path)? {
+            Some(ref entry) if entry.is_expired(expiry::now()) => {
+                remove_file(path)?;
+                Ok(None)
+            }
+            Some(entry) => Ok(Some(entry.value)),
+            None => Ok(None),
+        }));
+        self.0 = None;
+        Ok(Async::Ready(value))
+    }
+}
+
+enum Op {
+    Save(PathBuf, String, Option<Duration>),
+    Delete(PathBuf),
+    Touch(PathBuf, Option<Duration>),
+}
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct FileOp {
+    op: Option<io::Result<Op>>,
+    dir: Arc<PathBuf>,
+}
+
+impl Future for FileOp {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let dir = &*self.dir;
+        let op = match self.op {
+            Some(Ok(ref op)) => op,
+            Some(Err(..)) => return Err(self.op.take().unwrap().unwrap_err()),
+            None => panic!("the future has already been polled"),
+        };
+
+        futures::try_ready!(blocking_io(|| match *op {
+            Op::Save(ref path, ref value, ttl) => write_entry(
+                dir,
+                path,
+                &Entry {
+                    expires_at: expires_at(ttl),
+                    value: value.clone(),
+                },
+            ),
+            Op::Delete(ref path) => remove_file(path),
+            Op::Touch(ref path, ttl) => match read_entry(path)? {
+                Some(ref entry) if entry.is_expired(expiry::now()) => remove_file(path),
+                Some(entry) => write_entry(
+                    dir,
+                    path,
+                    &Entry {
+                        expires_at: expires_at(ttl),
+                        ..entry
+                    },
+                ),
+                None => Ok(()),
+            },
+        }));
+        self.op = None;
+        Ok(Async::Ready(()))
+    }
+}
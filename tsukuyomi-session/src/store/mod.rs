@@ -0,0 +1,79 @@
+//! The storage of session data keyed by session IDs.
+//!
+//! `SessionStore` abstracts the persistence layer (e.g. SQL databases or the filesystem),
+//! and `backend::StoreBackend` adapts it into a `Backend` that manages the session IDs
+//! with Cookie entries.
+//!
+//! ```ignore
+//! use futures::future::{self, FutureResult};
+//!
+//! struct SqliteStore {
+//!     pool: r2d2::Pool<SqliteConnectionManager>,
+//! }
+//!
+//! impl SessionStore for SqliteStore {
+//!     type Error = failure::Error;
+//!     type Load = FutureResult<Option<String>, Self::Error>;
+//!     type Save = FutureResult<(), Self::Error>;
+//!     type Delete = FutureResult<(), Self::Error>;
+//!     type Touch = FutureResult<(), Self::Error>;
+//!
+//!     fn load(&self, id: &str) -> Self::Load {
+//!         future::result(self.select_unexpired(id))
+//!     }
+//!
+//!     fn save(&self, id: &str, value: String, ttl: Option<Duration>) -> Self::Save {
+//!         future::result(self.upsert(id, &value, ttl))
+//!     }
+//!
+//!     // ...
+//! }
+//!
+//! let backend = StoreBackend::new(SqliteStore { pool })
+//!     .expiry(Expiry::new().idle_timeout(Duration::from_secs(30 * 60)));
+//! ```
+
+mod file;
+
+pub use self::file::FileStore;
+
+use {
+    futures::Future,
+    std::{fmt, time::Duration},
+};
+
+/// A trait representing the storage of serialized session data.
+///
+/// The stored values must not be returned by `load` after their TTL has elapsed.
+pub trait SessionStore {
+    /// The error type returned from the operations of this store.
+    type Error: fmt::Debug + fmt::Display + Send + 'static;
+    /// The type of `Future` returned from `load`.
+    type Load: Future<Item = Option<String>, Error = Self::Error>;
+    /// The type of `Future` returned from `save`.
+    type Save: Future<Item = (), Error = Self::Error>;
+    /// The type of `Future` returned from `delete`.
+    type Delete: Future<Item = (), Error = Self::Error>;
+    /// The type of `Future` returned from `touch`.
+    type Touch: Future<Item = (), Error = Self::Error>;
+
+    /// Loads the value associated with the specified session ID, if exists.
+    fn load(&self, id: &str) -> Self::Load;
+
+    /// Stores the value with the specified session ID.
+    ///
+    /// If `ttl` is `None`, the value is stored without the expiration.
+    fn save(&self, id: &str, value: String, ttl: Option<Duration>) -> Self::Save;
+
+    /// Removes the value associated with the specified session ID.
+    ///
+    /// The missing value should not be regarded as an error.
+    fn delete(&self, id: &str) -> Self::Delete;
+
+    /// Resets the TTL of the value associated with the specified session ID
+    /// without modifying it.
+    ///
+    /// This is called at the requests that access the session without modifying it,
+    /// in order to slide the idle timeout.
+    fn touch(&self, id: &str, ttl: Option<Duration>) -> Self::Touch;
+}
@@ -1,10 +1,20 @@
 use {
     http::Request,
-    tsukuyomi::{config::prelude::*, App},
+    std::collections::HashMap,
+    tsukuyomi::{
+        config::prelude::*,
+        future::{Async, Poll, TryFuture},
+        input::Input,
+        App,
+    },
+    tsukuyomi_server::test::ResponseExt,
     tsukuyomi_session::{
-        backend::CookieBackend, //
-        session,
-        Session,
+        backend::{CookieBackend, MemoryBackend, StoreBackend},
+        expiry::Expiry,
+        flash::{FlashMessage, Level},
+        lazy_session, session,
+        store::FileStore,
+        LazySession, RawSession, Session,
     },
 };
 
@@ -58,8 +68,9 @@ fn smoketest() -> tsukuyomi_server::Result<()> {
     let response = session.perform(Request::put("/counter"))?;
     assert!(response.headers().contains_key("set-cookie"));
 
+    // the session is not written back if it is not modified.
     let response = session.perform(Request::get("/counter"))?;
-    assert!(response.headers().contains_key("set-cookie"));
+    assert!(!response.headers().contains_key("set-cookie"));
     assert_eq!(response.body().to_utf8()?, "Some(1)");
 
     let response = session.perform(Request::put("/counter"))?;
@@ -75,3 +86,504 @@ fn smoketest() -> tsukuyomi_server::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn memory_backend() -> tsukuyomi_server::Result<()> {
+    let backend = MemoryBackend::new()
+        .expiry(Expiry::new().idle_timeout(std::time::Duration::from_millis(200)))
+        .capacity(1);
+    let session = std::sync::Arc::new(session(backend.clone()));
+
+    let app = App::create(path!("/counter").to(chain![
+        endpoint::get() //
+            .extract(session.clone())
+            .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                let counter: Option<i64> = session.get("counter")?;
+                Ok(session.finish(format!("{:?}", counter)))
+            }),
+        endpoint::put() //
+            .extract(session.clone())
+            .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                let counter: i64 = session.get("counter")?.unwrap_or_default();
+                session.set("counter", counter + 1)?;
+                Ok(session.finish(format!("{}", counter)))
+            }),
+        endpoint::delete() //
+            .extract(session)
+            .call(|mut session: Session<_>| {
+                session.clear();
+                session.finish("cleared")
+            }),
+    ]))?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let session_id1 = {
+        let mut client = server.new_session()?.save_cookies(true);
+        client.perform(Request::put("/counter"))?;
+        assert_eq!(client.perform("/counter")?.body().to_utf8()?, "Some(1)");
+        client
+            .cookie("session-id")
+            .expect("missing session id")
+            .to_owned()
+    };
+    assert_eq!(backend.stats().sessions, 1);
+    assert_eq!(backend.stats().hits, 1);
+
+    // the oldest session is evicted when exceeding the capacity.
+    let session_id2 = {
+        let mut client = server.new_session()?.save_cookies(true);
+        client.perform(Request::put("/counter"))?;
+        client
+            .cookie("session-id")
+            .expect("missing session id")
+            .to_owned()
+    };
+    assert_eq!(backend.stats().sessions, 1);
+    assert_eq!(backend.stats().evicted, 1);
+    let response = server.perform(
+        Request::get("/counter").header("cookie", format!("session-id={}", session_id1)),
+    )?;
+    assert_eq!(response.body().to_utf8()?, "None");
+
+    // the idle session expires.
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    backend.sweep();
+    assert_eq!(backend.stats().sessions, 0);
+    assert_eq!(backend.stats().expired, 1);
+    let response = server.perform(
+        Request::get("/counter").header("cookie", format!("session-id={}", session_id2)),
+    )?;
+    assert_eq!(response.body().to_utf8()?, "None");
+
+    let mut client = server.new_session()?.save_cookies(true);
+    client.perform(Request::put("/counter"))?;
+    client.perform(Request::delete("/counter"))?;
+    assert!(client.cookie("session-id").is_none());
+    assert_eq!(backend.stats().sessions, 0);
+
+    Ok(())
+}
+
+#[test]
+fn regenerate_id_and_destroy() -> tsukuyomi_server::Result<()> {
+    let backend = MemoryBackend::new();
+    let session = std::sync::Arc::new(session(backend.clone()));
+
+    let app = App::create(chain![
+        path!("/login").to(endpoint::post() //
+            .extract(session.clone())
+            .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                session.set("user", "alice")?;
+                session.regenerate_id();
+                Ok(session.finish("logged in"))
+            })),
+        path!("/user").to(endpoint::get() //
+            .extract(session.clone())
+            .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                let user: Option<String> = session.get("user")?;
+                Ok(session.finish(format!("{:?}", user)))
+            })),
+        path!("/logout").to(endpoint::post() //
+            .extract(session)
+            .call(|mut session: Session<_>| {
+                session.destroy();
+                session.finish("logged out")
+            })),
+    ])?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let (first_id, second_id) = {
+        let mut client = server.new_session()?.save_cookies(true);
+
+        client.perform(Request::post("/login"))?;
+        let first_id = client.cookie("session-id").unwrap().to_owned();
+
+        client.perform(Request::post("/login"))?;
+        let second_id = client.cookie("session-id").unwrap().to_owned();
+        assert_ne!(first_id, second_id);
+        assert_eq!(
+            client.perform("/user")?.body().to_utf8()?,
+            "Some(\"alice\")"
+        );
+
+        (first_id, second_id)
+    };
+    assert_eq!(backend.stats().sessions, 1);
+
+    // the old session ID is no longer valid.
+    let response = server
+        .perform(Request::get("/user").header("cookie", format!("session-id={}", first_id)))?;
+    assert_eq!(response.body().to_utf8()?, "None");
+
+    let response = server
+        .perform(Request::post("/logout").header("cookie", format!("session-id={}", second_id)))?;
+    assert!(response.headers().contains_key("set-cookie"));
+    assert_eq!(backend.stats().sessions, 0);
+
+    Ok(())
+}
+
+#[test]
+fn expiry() -> tsukuyomi_server::Result<()> {
+    let backend = CookieBackend::plain().cookie_name("session").expiry(
+        Expiry::new()
+            .idle_timeout(std::time::Duration::from_secs(60 * 60))
+            .absolute_timeout(std::time::Duration::from_secs(24 * 60 * 60)),
+    );
+    let session = std::sync::Arc::new(session(backend));
+
+    let app = App::create(chain![
+        path!("/login/:remember").to(endpoint::post() //
+            .extract(session.clone())
+            .call_async(
+                |remember: bool, mut session: Session<_>| -> tsukuyomi::Result<_> {
+                    session.set("user", "alice")?;
+                    session.set_persistent(remember);
+                    Ok(session.finish("logged in"))
+                }
+            )),
+        path!("/user").to(endpoint::get() //
+            .extract(session)
+            .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                let user: Option<String> = session.get("user")?;
+                let expired = session.is_expired();
+                Ok(session.finish(format!("{:?} {}", user, expired)))
+            })),
+    ])?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(Request::post("/login/true"))?;
+    let set_cookie = response.header("set-cookie")?.to_str()?.to_owned();
+    assert!(set_cookie.contains("Max-Age=3600"), "{}", set_cookie);
+
+    let response = server.perform(Request::post("/login/false"))?;
+    let set_cookie = response.header("set-cookie")?.to_str()?.to_owned();
+    assert!(!set_cookie.contains("Max-Age"), "{}", set_cookie);
+
+    let response = server.perform(Request::get("/user").header(
+        "cookie",
+        r#"session={"data":{"user":"\"alice\""},"meta":{"created":0,"accessed":0}}"#,
+    ))?;
+    assert_eq!(response.body().to_utf8()?, "None true");
+    let set_cookie = response.header("set-cookie")?.to_str()?.to_owned();
+    assert!(set_cookie.starts_with("session=;"), "{}", set_cookie);
+
+    Ok(())
+}
+
+#[test]
+fn write_only_if_modified_or_sliding() -> tsukuyomi_server::Result<()> {
+    macro_rules! app {
+        ($backend:expr) => {{
+            let session = std::sync::Arc::new(session($backend));
+            App::create(path!("/user").to(chain![
+                endpoint::get() //
+                    .extract(session.clone())
+                    .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                        let user: Option<String> = session.get("user")?;
+                        Ok(session.finish(format!("{:?}", user)))
+                    }),
+                endpoint::put() //
+                    .extract(session)
+                    .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                        session.set("user", "alice")?;
+                        Ok(session.finish("updated"))
+                    }),
+            ]))?
+        }};
+    }
+
+    // without the idle timeout, reading the session does not cause any writes.
+    let mut server = tsukuyomi_server::test::server(app!(MemoryBackend::new()))?;
+    let mut client = server.new_session()?.save_cookies(true);
+    let response = client.perform(Request::put("/user"))?;
+    assert!(response.headers().contains_key("set-cookie"));
+    let response = client.perform("/user")?;
+    assert!(!response.headers().contains_key("set-cookie"));
+    assert_eq!(response.body().to_utf8()?, "Some(\"alice\")");
+
+    // the Cookie entry storing the access time is re-issued to slide the idle timeout.
+    let backend = CookieBackend::plain()
+        .cookie_name("session")
+        .expiry(Expiry::new().idle_timeout(std::time::Duration::from_secs(60)));
+    let mut server = tsukuyomi_server::test::server(app!(backend))?;
+    let mut client = server.new_session()?.save_cookies(true);
+    client.perform(Request::put("/user"))?;
+    let response = client.perform("/user")?;
+    assert!(response.headers().contains_key("set-cookie"));
+    assert_eq!(response.body().to_utf8()?, "Some(\"alice\")");
+
+    Ok(())
+}
+
+#[test]
+fn typed_schema_and_flash() -> tsukuyomi_server::Result<()> {
+    #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    struct Cart {
+        user: Option<String>,
+        #[serde(default)]
+        items: Vec<u32>,
+    }
+
+    // the schema used by an earlier version of the application.
+    #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    struct OldCart {
+        user: Option<String>,
+        coupon: Option<String>,
+    }
+
+    let backend = CookieBackend::plain().cookie_name("session");
+    let session = std::sync::Arc::new(session(backend));
+
+    let app = App::create(chain![
+        path!("/cart").to(chain![
+            endpoint::get() //
+                .extract(session.clone())
+                .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                    let cart: Cart = session.load()?;
+                    let flashes: Vec<String> = session
+                        .take_flashes()?
+                        .into_iter()
+                        .map(|flash: FlashMessage| format!("{}:{}", flash.level(), flash))
+                        .collect();
+                    Ok(session.finish(format!("{:?} {:?} {:?}", cart.user, cart.items, flashes)))
+                }),
+            endpoint::post() //
+                .extract(session.clone())
+                .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                    let mut cart: Cart = session.load()?;
+                    cart.user = Some("alice".into());
+                    cart.items.push(42);
+                    session.save(&cart)?;
+                    session.flash(Level::Success, "added")?;
+                    Ok(session.finish("added"))
+                }),
+            endpoint::put() //
+                .extract(session.clone())
+                .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                    let cart: Cart = session.load()?;
+                    session.save(&cart)?;
+                    Ok(session.finish("unchanged"))
+                }),
+        ]),
+        path!("/old").to(chain![
+            endpoint::get() //
+                .extract(session.clone())
+                .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                    let cart: OldCart = session.load()?;
+                    Ok(session.finish(format!("{:?} {:?}", cart.user, cart.coupon)))
+                }),
+            endpoint::post() //
+                .extract(session.clone())
+                .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                    session.save(&OldCart {
+                        user: Some("bob".into()),
+                        coupon: Some("XMAS".into()),
+                    })?;
+                    Ok(session.finish("saved"))
+                }),
+        ]),
+        path!("/logout").to(endpoint::post() //
+            .extract(session)
+            .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                session.save(&Cart::default())?;
+                Ok(session.finish("logged out"))
+            })),
+    ])?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    assert_eq!(client.perform("/cart")?.body().to_utf8()?, "None [] []");
+
+    // the fields of the earlier schema are dropped when the new schema is saved.
+    client.perform(Request::post("/old"))?;
+    assert_eq!(
+        client.perform("/old")?.body().to_utf8()?,
+        "Some(\"bob\") Some(\"XMAS\")"
+    );
+    client.perform(Request::post("/cart"))?;
+    assert_eq!(
+        client.perform("/old")?.body().to_utf8()?,
+        "Some(\"alice\") None"
+    );
+    client.perform("/cart")?;
+    client.perform(Request::post("/logout"))?;
+
+    client.perform(Request::post("/cart"))?;
+    assert_eq!(
+        client.perform("/cart")?.body().to_utf8()?,
+        "Some(\"alice\") [42] [\"success:added\"]"
+    );
+    // the flash messages live only until the next request.
+    assert_eq!(
+        client.perform("/cart")?.body().to_utf8()?,
+        "Some(\"alice\") [42] []"
+    );
+
+    // saving the unchanged value does not rewrite the session.
+    let response = client.perform(Request::put("/cart"))?;
+    assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key("set-cookie"));
+
+    client.perform(Request::post("/logout"))?;
+    assert_eq!(client.perform("/cart")?.body().to_utf8()?, "None [] []");
+
+    Ok(())
+}
+
+#[test]
+fn lazy_session_is_read_only_when_accessed() -> tsukuyomi_server::Result<()> {
+    let backend = MemoryBackend::new();
+    let session = std::sync::Arc::new(lazy_session(backend.clone()));
+
+    let app = App::create(chain![
+        path!("/public").to(endpoint::get() //
+            .extract(session.clone())
+            .call(|_session: LazySession<_>| "public")),
+        path!("/counter").to(endpoint::put() //
+            .extract(session)
+            .call(|session: LazySession<_>| {
+                session.with(|session| {
+                    let counter: i64 = session.get("counter")?.unwrap_or_default();
+                    session.set("counter", counter + 1)?;
+                    Ok(format!("{}", counter + 1))
+                })
+            })),
+    ])?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    assert_eq!(
+        client.perform(Request::put("/counter"))?.body().to_utf8()?,
+        "1"
+    );
+    assert_eq!(
+        client.perform(Request::put("/counter"))?.body().to_utf8()?,
+        "2"
+    );
+    let stats = backend.stats();
+    assert_eq!(stats.hits, 1);
+
+    // the handler that does not access the session costs no read from the backend.
+    let response = client.perform("/public")?;
+    assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key("set-cookie"));
+    assert_eq!(backend.stats(), stats);
+
+    Ok(())
+}
+
+#[test]
+fn file_store() -> tsukuyomi_server::Result<()> {
+    let dir = std::env::temp_dir().join(format!(
+        "tsukuyomi-session-test-{}",
+        uuid::Uuid::new_v4().to_simple()
+    ));
+    let backend = StoreBackend::new(FileStore::new(&dir))
+        .expiry(Expiry::new().idle_timeout(std::time::Duration::from_secs(60)));
+    let session = std::sync::Arc::new(session(backend));
+
+    let app = App::create(path!("/counter").to(chain![
+        endpoint::get() //
+            .extract(session.clone())
+            .call_async(|session: Session<_>| -> tsukuyomi::Result<_> {
+                let counter: Option<i64> = session.get("counter")?;
+                Ok(session.finish(format!("{:?}", counter)))
+            }),
+        endpoint::put() //
+            .extract(session.clone())
+            .call_async(|mut session: Session<_>| -> tsukuyomi::Result<_> {
+                let counter: i64 = session.get("counter")?.unwrap_or_default();
+                session.set("counter", counter + 1)?;
+                Ok(session.finish(format!("{}", counter)))
+            }),
+        endpoint::delete() //
+            .extract(session)
+            .call(|mut session: Session<_>| {
+                session.destroy();
+                session.finish("destroyed")
+            }),
+    ]))?;
+
+    let mut server = tsukuyomi_server::test::server(app)?;
+    let mut client = server.new_session()?.save_cookies(true);
+
+    assert_eq!(client.perform("/counter")?.body().to_utf8()?, "None");
+    assert!(client.cookie("session-id").is_none());
+
+    client.perform(Request::put("/counter"))?;
+    client.perform(Request::put("/counter"))?;
+    assert_eq!(client.perform("/counter")?.body().to_utf8()?, "Some(2)");
+
+    let session_id = client.cookie("session-id").unwrap().to_owned();
+    let path = dir.join(format!("{}.json", session_id));
+    assert!(path.exists());
+
+    client.perform(Request::delete("/counter"))?;
+    assert!(client.cookie("session-id").is_none());
+    assert!(!path.exists());
+
+    std::fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
+
+/// A backend implemented outside of this crate, which provides only the required methods.
+#[derive(Default)]
+struct MapSession(HashMap<String, String>);
+
+struct WriteMapSession;
+
+impl TryFuture for WriteMapSession {
+    type Ok = ();
+    type Error = tsukuyomi::Error;
+
+    fn poll_ready(&mut self, _: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+impl RawSession for MapSession {
+    type WriteError = tsukuyomi::Error;
+    type WriteSession = WriteMapSession;
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.0.insert(name.to_owned(), value);
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn write(self) -> Self::WriteSession {
+        WriteMapSession
+    }
+}
+
+#[test]
+fn raw_session_default_methods() {
+    let mut session = MapSession::default();
+    session.set("foo", "bar".into());
+
+    session.set_persistent(true);
+    session.regenerate_id();
+    assert_eq!(session.get("foo"), Some("bar"));
+    assert!(!session.is_expired());
+
+    // the session data is cleared by default.
+    session.destroy();
+    assert_eq!(session.get("foo"), None);
+}
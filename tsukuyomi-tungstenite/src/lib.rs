@@ -29,6 +29,7 @@ pub type WebSocketStream = tokio_tungstenite::WebSocketStream<UpgradedIo>;
 pub struct Ws<F> {
     on_upgrade: F,
     config: Option<WebSocketConfig>,
+    protocols: Vec<&'static str>,
 }
 
 impl<F, R> Ws<F>
@@ -42,6 +43,7 @@ where
         Self {
             on_upgrade,
             config: None,
+            protocols: vec![],
         }
     }
 
@@ -52,6 +54,18 @@ where
             ..self
         }
     }
+
+    /// Sets the subprotocols supported by the server, in order of preference.
+    ///
+    /// The first one of them requested by the client in `Sec-WebSocket-Protocol`
+    /// is selected and returned in the handshake response.  If the client does
+    /// not request any of them, the header is not returned.
+    pub fn protocols(self, protocols: impl IntoIterator<Item = &'static str>) -> Self {
+        Self {
+            protocols: protocols.into_iter().collect(),
+            ..self
+        }
+    }
 }
 
 impl<F, R> Responder for Ws<F>
@@ -78,6 +92,7 @@ mod imp {
                 CONNECTION, //
                 SEC_WEBSOCKET_ACCEPT,
                 SEC_WEBSOCKET_KEY,
+                SEC_WEBSOCKET_PROTOCOL,
                 SEC_WEBSOCKET_VERSION,
                 UPGRADE,
             },
@@ -110,10 +125,14 @@ mod imp {
         type Error = tsukuyomi::Error;
 
         fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
-            let Ws { on_upgrade, config } =
-                self.0.take().expect("the future has already been polled");
+            let Ws {
+                on_upgrade,
+                config,
+                protocols,
+            } = self.0.take().expect("the future has already been polled");
 
             let accept_hash = handshake(input)?;
+            let protocol = select_protocol(input, &protocols);
 
             let body = RequestBody::take_from(input.locals) //
                 .ok_or_else(|| {
@@ -134,11 +153,16 @@ mod imp {
                 .spawn(Box::new(task))
                 .map_err(tsukuyomi::error::internal_server_error)?;
 
-            Ok(Response::builder()
+            let mut response = Response::builder();
+            response
                 .status(StatusCode::SWITCHING_PROTOCOLS)
                 .header(UPGRADE, "websocket")
                 .header(CONNECTION, "upgrade")
-                .header(SEC_WEBSOCKET_ACCEPT, &*accept_hash)
+                .header(SEC_WEBSOCKET_ACCEPT, &*accept_hash);
+            if let Some(protocol) = protocol {
+                response.header(SEC_WEBSOCKET_PROTOCOL, protocol);
+            }
+            Ok(response
                 .body(())
                 .expect("should be a valid response")
                 .into())
@@ -212,8 +236,23 @@ mod imp {
             })?,
         };
 
-        // TODO: Sec-WebSocket-Protocol, Sec-WebSocket-Extension
+        // TODO: Sec-WebSocket-Extension
 
         Ok(accept_hash)
     }
+
+    fn select_protocol(input: &Input<'_>, protocols: &[&'static str]) -> Option<&'static str> {
+        let requested = input
+            .request
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(',').map(|s| s.trim()))
+            .collect::<Vec<_>>();
+        protocols
+            .iter()
+            .find(|protocol| requested.contains(protocol))
+            .cloned()
+    }
 }
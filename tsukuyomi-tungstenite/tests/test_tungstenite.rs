@@ -10,6 +10,7 @@ use {
             HOST,
             SEC_WEBSOCKET_ACCEPT,
             SEC_WEBSOCKET_KEY,
+            SEC_WEBSOCKET_PROTOCOL,
             SEC_WEBSOCKET_VERSION,
             UPGRADE,
         },
@@ -55,4 +56,41 @@ fn test_handshake() -> tsukuyomi_server::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_handshake_with_protocols() -> tsukuyomi_server::Result<()> {
+    let app =
+        App::create(
+            path!("/ws") //
+                .to(endpoint::get()
+                    .reply(Ws::new(|_| Ok(())).protocols(vec!["graphql-ws", "chat"]))),
+        )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(
+        Request::get("/ws")
+            .header(HOST, "localhost:4000")
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .header(SEC_WEBSOCKET_PROTOCOL, "mqtt, chat"),
+    )?;
+    assert_eq!(response.status(), 101);
+    assert_eq!(response.header(SEC_WEBSOCKET_PROTOCOL)?, "chat");
+
+    let response = server.perform(
+        Request::get("/ws")
+            .header(HOST, "localhost:4000")
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .header(SEC_WEBSOCKET_PROTOCOL, "mqtt"),
+    )?;
+    assert_eq!(response.status(), 101);
+    assert!(!response.headers().contains_key(SEC_WEBSOCKET_PROTOCOL));
+
+    Ok(())
+}
+
 // TODO: add check whether the task to handle upgraded connection is spawned
@@ -26,6 +26,21 @@ use {
     tsukuyomi_service::{MakeService, Service},
 };
 
+/// The route matched to the request.
+///
+/// The value of this type is inserted into the extension map of the response
+/// returned from `AppService`, so that the service-level middlewares can refer to it.
+/// It is not inserted if the request is handled by a default handler or no route matches.
+#[derive(Debug, Clone)]
+pub struct MatchedRoute(Uri);
+
+impl MatchedRoute {
+    /// Returns the pattern of the matched route, e.g. `"/users/:id"`.
+    pub fn pattern(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
 /// The main type representing an HTTP application.
 #[derive(Debug, Clone)]
 pub struct AppBase<C: Concurrency = self::config::ThreadSafe> {
@@ -37,6 +52,9 @@ where
     C: Concurrency,
 {
     /// Converts itself into a `MakeService` with the specified `ModifyService`.
+    ///
+    /// Multiple modifiers can be combined by `chain!`, in the order from inner to outer
+    /// as in the case of `ModifyHandler`s.
     pub fn with_modify_service<M>(
         self,
         modify_service: M,
@@ -67,7 +85,13 @@ where
 }
 
 mod with_modify_service {
-    use {super::*, tsukuyomi_service::ModifyService};
+    use {
+        super::*,
+        crate::util::Chain,
+        futures01::{Future, Poll},
+        std::marker::PhantomData,
+        tsukuyomi_service::ModifyService,
+    };
 
     #[derive(Debug)]
     pub struct WithModifyService<C: Concurrency, M> {
@@ -94,6 +118,66 @@ mod with_modify_service {
             self.modify_service.modify_service(service, ctx)
         }
     }
+
+    impl<I, O, Ctx, Req, S> ModifyService<Ctx, Req, S> for Chain<I, O>
+    where
+        I: ModifyService<Ctx, Req, S>,
+        O: ModifyService<Ctx, Req, I::Service> + Clone,
+        I::ModifyError: Into<O::ModifyError>,
+        Ctx: Clone,
+    {
+        type Response = O::Response;
+        type Error = O::Error;
+        type Service = O::Service;
+        type ModifyError = O::ModifyError;
+        type Future = ChainFuture<I::Future, O, O::Future, Ctx, Req>; // private
+
+        fn modify_service(&self, input: S, ctx: Ctx) -> Self::Future {
+            ChainFuture {
+                state: ChainState::First(
+                    self.left.modify_service(input, ctx.clone()),
+                    Some((self.right.clone(), ctx)),
+                ),
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct ChainFuture<F1, M, F2, Ctx, Req> {
+        state: ChainState<F1, M, F2, Ctx>,
+        _marker: PhantomData<fn(Req)>,
+    }
+
+    enum ChainState<F1, M, F2, Ctx> {
+        First(F1, Option<(M, Ctx)>),
+        Second(F2),
+    }
+
+    impl<F1, M, F2, Ctx, Req> Future for ChainFuture<F1, M, F2, Ctx, Req>
+    where
+        F1: Future,
+        M: ModifyService<Ctx, Req, F1::Item, Future = F2>,
+        F2: Future<Item = M::Service, Error = M::ModifyError>,
+        F1::Error: Into<M::ModifyError>,
+    {
+        type Item = F2::Item;
+        type Error = F2::Error;
+
+        fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+            loop {
+                self.state = match self.state {
+                    ChainState::First(ref mut inner, ref mut outer) => {
+                        let service = futures01::try_ready!(inner.poll().map_err(Into::into));
+                        let (outer, ctx) =
+                            outer.take().expect("the future has already been polled");
+                        ChainState::Second(outer.modify_service(service, ctx))
+                    }
+                    ChainState::Second(ref mut outer) => return outer.poll(),
+                };
+            }
+        }
+    }
 }
 
 pub type App = AppBase<self::config::ThreadSafe>;
@@ -1,5 +1,5 @@
 use {
-    super::{config::Concurrency, recognizer::Captures, AppInner, Endpoint},
+    super::{config::Concurrency, recognizer::Captures, AppInner, Endpoint, MatchedRoute},
     crate::{
         input::{
             body::RequestBody,
@@ -7,6 +7,7 @@ use {
             param::Params,
             Cookies, Input,
         },
+        modifiers::RequestId,
         output::ResponseBody,
         util::Never,
     },
@@ -57,6 +58,9 @@ where
 
         let mut locals = LocalMap::default();
         RequestBody::from(body).insert_into(&mut locals);
+        if let Some(request_id) = parts.extensions.get::<RequestId>() {
+            request_id.clone().insert_into(&mut locals);
+        }
 
         AppFuture {
             request: Request::from_parts(parts, ()),
@@ -108,6 +112,7 @@ macro_rules! input {
             params: {
                 &if let Some(ref endpoint) = $self.endpoint {
                     Some(Params {
+                        pattern: endpoint.uri.as_str(),
                         path: $self.request.uri().path(),
                         names: endpoint.uri.capture_names(),
                         captures: $self.captures.as_ref(),
@@ -162,6 +167,16 @@ impl<C: Concurrency> AppFuture<C> {
             }
         }
 
+        // expose the request-local values to the service-level middlewares.
+        if let Some(ref endpoint) = self.endpoint {
+            output
+                .extensions_mut()
+                .insert(MatchedRoute(endpoint.uri.clone()));
+        }
+        if let Some(request_id) = RequestId::get(&self.locals) {
+            output.extensions_mut().insert(request_id.clone());
+        }
+
         // append the value of Content-Length to the response header if missing.
         if let Some(len) = output.body().content_length() {
             output
@@ -5,10 +5,12 @@ use {
         extractor::Extractor,
         generic::{Combine, Func},
         handler::AllowedMethods,
+        input::body::RequestBody,
         util::{Chain, Never, TryInto},
     },
     futures01::IntoFuture,
-    http::Method,
+    http::{Method, Request},
+    tsukuyomi_service::Service,
 };
 
 pub fn any() -> Builder {
@@ -182,6 +184,43 @@ where
     {
         self.call(move || output.clone())
     }
+
+    /// Creates an `Endpoint` that forwards the request to the specified `Service`.
+    ///
+    /// The service is cloned for each request, and called after it becomes ready.
+    /// The request passed to the service does not contain the extensions of
+    /// the original request.
+    pub fn call_service<T, S>(
+        self,
+        service: S,
+    ) -> impl Endpoint<
+        T,
+        Output = S::Response,
+        Error = Error,
+        Future = self::call_service::CallServiceFuture<E, S>, // private
+    >
+    where
+        S: Service<Request<RequestBody>> + Clone,
+        S::Error: Into<Error>,
+    {
+        let apply_fn = {
+            let allowed_methods = self.allowed_methods.clone();
+            let extractor = self.extractor;
+            move |args: T, cx: &mut ApplyContext<'_, '_>| {
+                if allowed_methods
+                    .as_ref()
+                    .map_or(false, |methods| !methods.contains(cx.method()))
+                {
+                    return Err((args, ApplyError::method_not_allowed()));
+                }
+                Ok(self::call_service::CallServiceFuture {
+                    state: self::call_service::State::Extract(extractor.extract()),
+                    service: service.clone(),
+                })
+            }
+        };
+        crate::endpoint::endpoint(apply_fn, self.allowed_methods)
+    }
 }
 
 /// A shortcut to `endpoint::any().call(f)`
@@ -235,6 +274,23 @@ where
     any().reply(output)
 }
 
+/// A shortcut to `endpoint::any().call_service(service)`.
+#[inline]
+pub fn call_service<T, S>(
+    service: S,
+) -> impl Endpoint<
+    T,
+    Output = S::Response,
+    Error = Error,
+    Future = self::call_service::CallServiceFuture<(), S>, // private
+>
+where
+    S: Service<Request<RequestBody>> + Clone,
+    S::Error: Into<Error>,
+{
+    any().call_service(service)
+}
+
 mod call {
     use crate::{
         extractor::Extractor,
@@ -324,3 +380,72 @@ mod call_async {
         }
     }
 }
+
+mod call_service {
+    use {
+        crate::{
+            error::Error,
+            extractor::Extractor,
+            future::{Async, Poll, TryFuture},
+            input::{body::RequestBody, localmap::LocalData, Input},
+        },
+        futures01::Future,
+        http::Request,
+        tsukuyomi_service::Service,
+    };
+
+    #[allow(missing_debug_implementations)]
+    pub(super) enum State<E, F> {
+        Extract(E),
+        Ready,
+        Call(F),
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct CallServiceFuture<E: Extractor, S: Service<Request<RequestBody>>> {
+        pub(super) state: State<E::Extract, S::Future>,
+        pub(super) service: S,
+    }
+
+    impl<E, S> TryFuture for CallServiceFuture<E, S>
+    where
+        E: Extractor<Output = ()>,
+        S: Service<Request<RequestBody>>,
+        S::Error: Into<Error>,
+    {
+        type Ok = S::Response;
+        type Error = Error;
+
+        fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+            loop {
+                self.state = match self.state {
+                    State::Extract(ref mut extract) => {
+                        futures01::try_ready!(extract.poll_ready(input).map_err(Into::into));
+                        State::Ready
+                    }
+                    State::Ready => {
+                        futures01::try_ready!(self.service.poll_ready().map_err(Into::into));
+                        let body = RequestBody::take_from(input.locals).ok_or_else(|| {
+                            crate::error::internal_server_error(
+                                "The instance of raw RequestBody has already stolen.",
+                            )
+                        })?;
+                        let mut request = Request::new(body);
+                        *request.method_mut() = input.request.method().clone();
+                        *request.uri_mut() = input.request.uri().clone();
+                        *request.version_mut() = input.request.version();
+                        *request.headers_mut() = input.request.headers().clone();
+                        State::Call(self.service.call(request))
+                    }
+                    State::Call(ref mut call) => {
+                        return match call.poll() {
+                            Ok(Async::Ready(response)) => Ok(Async::Ready(response)),
+                            Ok(Async::NotReady) => Ok(Async::NotReady),
+                            Err(err) => Err(err.into()),
+                        };
+                    }
+                };
+            }
+        }
+    }
+}
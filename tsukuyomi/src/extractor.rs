@@ -1,5 +1,6 @@
 //! Definition of `Extractor` and its implementors.
 
+pub mod auth;
 pub mod body;
 pub mod ext;
 pub mod header;
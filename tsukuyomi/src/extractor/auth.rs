@@ -0,0 +1,445 @@
+//! Extractors for HTTP authentication using `Authorization` header field.
+//!
+//! The supported schemes are `Basic` ([RFC 7617]) and `Bearer` ([RFC 6750]).
+//! Each extractor passes the credentials to the verifier function and
+//! returns the principal resolved by it:
+//!
+//! ```ignore
+//! let endpoint = endpoint::get()
+//!     .extract(auth::basic(|credentials: BasicCredentials| {
+//!         users.verify(credentials.username(), credentials.password()) // -> impl Future<Item = Option<User>>
+//!     }).realm("admin"))
+//!     .call(|user: User| format!("Hello, {}", user.name));
+//! ```
+//!
+//! [RFC 7617]: https://tools.ietf.org/html/rfc7617
+//! [RFC 6750]: https://tools.ietf.org/html/rfc6750
+
+use {
+    super::Extractor,
+    crate::{
+        error::{Error, HttpError},
+        future::{Async, Poll, TryFuture},
+        input::Input,
+    },
+    futures01::{Future, IntoFuture},
+    http::{
+        header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE},
+        Request, Response, StatusCode,
+    },
+    std::{borrow::Cow, fmt, sync::Arc},
+};
+
+const DEFAULT_REALM: &str = "Restricted";
+
+/// The credentials sent with `Basic` scheme.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BasicCredentials {
+    username: String,
+    password: String,
+}
+
+impl fmt::Debug for BasicCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicCredentials")
+            .field("username", &self.username)
+            .field("password", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl BasicCredentials {
+    /// Returns the user ID.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Returns the password.
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    fn parse(credentials: &str) -> Option<Self> {
+        let decoded = base64::decode(credentials).ok()?;
+
+        // RFC 7617 recommends UTF-8, but some clients still send the credentials
+        // encoded in ISO-8859-1.
+        let decoded = match String::from_utf8(decoded) {
+            Ok(decoded) => decoded,
+            Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+        };
+
+        let pos = decoded.find(':')?;
+        let (username, password) = (&decoded[..pos], &decoded[pos + 1..]);
+        if username
+            .chars()
+            .chain(password.chars())
+            .any(char::is_control)
+        {
+            return None;
+        }
+
+        Some(BasicCredentials {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        })
+    }
+}
+
+/// The access token sent with `Bearer` scheme.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BearerToken(String);
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BearerToken").field(&"[REDACTED]").finish()
+    }
+}
+
+impl BearerToken {
+    /// Returns the string representation of the token.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn parse(token: &str) -> Option<Self> {
+        // token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+        let is_token68_char = |b: u8| b.is_ascii_alphanumeric() || b"-._~+/".contains(&b);
+        let body = token.trim_end_matches('=');
+        if body.is_empty() || !body.bytes().all(is_token68_char) {
+            return None;
+        }
+        Some(BearerToken(token.to_owned()))
+    }
+}
+
+impl AsRef<str> for BearerToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The error codes used in the `Bearer` challenge, defined in RFC 6750.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BearerErrorCode {
+    /// The request is malformed (`400 Bad Request`).
+    InvalidRequest,
+    /// The access token is expired, revoked or invalid (`401 Unauthorized`).
+    InvalidToken,
+    /// The access token does not have the required scope (`403 Forbidden`).
+    InsufficientScope,
+}
+
+impl BearerErrorCode {
+    /// Returns the string representation of this code.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BearerErrorCode::InvalidRequest => "invalid_request",
+            BearerErrorCode::InvalidToken => "invalid_token",
+            BearerErrorCode::InsufficientScope => "insufficient_scope",
+        }
+    }
+
+    fn status(self) -> StatusCode {
+        match self {
+            BearerErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            BearerErrorCode::InvalidToken => StatusCode::UNAUTHORIZED,
+            BearerErrorCode::InsufficientScope => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// An error representing the failure of authentication.
+///
+/// The error is converted into a response with `WWW-Authenticate` header field
+/// containing the challenge to the client.
+#[derive(Debug)]
+pub struct AuthError {
+    status: StatusCode,
+    scheme: &'static str,
+    params: Vec<(&'static str, String)>,
+    message: Cow<'static, str>,
+}
+
+impl AuthError {
+    /// Creates an `AuthError` with the `Basic` challenge.
+    pub fn basic(realm: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            scheme: "Basic",
+            params: vec![("realm", realm.into()), ("charset", "UTF-8".into())],
+            message: "authentication required".into(),
+        }
+    }
+
+    /// Creates an `AuthError` with the `Bearer` challenge without error code.
+    ///
+    /// This error should be used when the request does not contain any credentials.
+    pub fn bearer(realm: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            scheme: "Bearer",
+            params: vec![("realm", realm.into())],
+            message: "authentication required".into(),
+        }
+    }
+
+    /// Creates an `AuthError` with the `Bearer` challenge containing the specified error code.
+    ///
+    /// The status code of the response is determined by the error code.
+    pub fn bearer_error(realm: impl Into<String>, code: BearerErrorCode) -> Self {
+        Self {
+            status: code.status(),
+            scheme: "Bearer",
+            params: vec![("realm", realm.into()), ("error", code.as_str().into())],
+            message: code.as_str().into(),
+        }
+    }
+
+    /// Appends the human-readable description of the error to the challenge.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        let description = description.into();
+        self.message = description.clone().into();
+        self.params.push(("error_description", description));
+        self
+    }
+
+    /// Appends the scope required to access the resource to the challenge.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.params.push(("scope", scope.into()));
+        self
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the value of `WWW-Authenticate` header field.
+    pub fn challenge(&self) -> String {
+        let mut challenge = self.scheme.to_owned();
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            challenge += if i == 0 { " " } else { ", " };
+            challenge += name;
+            challenge += "=\"";
+            for ch in value.chars() {
+                if ch == '"' || ch == '\\' {
+                    challenge.push('\\');
+                }
+                challenge.push(ch);
+            }
+            challenge.push('"');
+        }
+        challenge
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&*self.message)
+    }
+}
+
+impl HttpError for AuthError {
+    type Body = String;
+
+    fn into_response(self, _: &Request<()>) -> Response<Self::Body> {
+        let challenge = HeaderValue::from_str(&self.challenge())
+            .unwrap_or_else(|_| HeaderValue::from_static(self.scheme));
+        let mut response = Response::new(self.message.into_owned());
+        *response.status_mut() = self.status;
+        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        response
+    }
+}
+
+/// Returns the credentials in `Authorization` if its scheme matches the specified one.
+fn authorization<'a>(input: &'a Input<'_>, scheme: &str) -> Option<&'a str> {
+    let value = input.request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let value = value.trim();
+    let pos = value.find(' ')?;
+    if value[..pos].eq_ignore_ascii_case(scheme) {
+        Some(value[pos + 1..].trim_start())
+    } else {
+        None
+    }
+}
+
+fn parse_basic(input: &mut Input<'_>, realm: &str) -> Result<BasicCredentials, AuthError> {
+    authorization(input, "Basic")
+        .and_then(BasicCredentials::parse)
+        .ok_or_else(|| AuthError::basic(realm))
+}
+
+fn reject_basic(realm: &str) -> AuthError {
+    AuthError::basic(realm)
+}
+
+fn parse_bearer(input: &mut Input<'_>, realm: &str) -> Result<BearerToken, AuthError> {
+    let token = authorization(input, "Bearer").ok_or_else(|| AuthError::bearer(realm))?;
+    BearerToken::parse(token).ok_or_else(|| {
+        AuthError::bearer_error(realm, BearerErrorCode::InvalidRequest)
+            .description("malformed access token")
+    })
+}
+
+fn reject_bearer(realm: &str) -> AuthError {
+    AuthError::bearer_error(realm, BearerErrorCode::InvalidToken)
+        .description("the access token is invalid")
+}
+
+/// Creates an `Extractor` that authenticates the request using `Basic` scheme.
+///
+/// The function `verify` returns a future that resolves to the principal,
+/// or `None` if the credentials are invalid.
+pub fn basic<F, R, P>(verify: F) -> Basic<F>
+where
+    F: Fn(BasicCredentials) -> R + Clone,
+    R: IntoFuture<Item = Option<P>>,
+    R::Error: Into<Error>,
+{
+    Basic {
+        verify,
+        realm: DEFAULT_REALM.into(),
+    }
+}
+
+/// An `Extractor` created by `basic`.
+#[derive(Debug, Clone)]
+pub struct Basic<F> {
+    verify: F,
+    realm: Arc<str>,
+}
+
+impl<F> Basic<F> {
+    /// Sets the realm included in the challenge.
+    ///
+    /// The default value is `"Restricted"`.
+    pub fn realm(self, realm: impl Into<String>) -> Self {
+        Self {
+            realm: realm.into().into(),
+            ..self
+        }
+    }
+}
+
+impl<F, R, P> Extractor for Basic<F>
+where
+    F: Fn(BasicCredentials) -> R + Clone,
+    R: IntoFuture<Item = Option<P>>,
+    R::Error: Into<Error>,
+{
+    type Output = (P,);
+    type Error = Error;
+    type Extract = Authenticate<F, R, BasicCredentials>;
+
+    fn extract(&self) -> Self::Extract {
+        Authenticate {
+            state: State::Init,
+            verify: self.verify.clone(),
+            realm: self.realm.clone(),
+            parse: parse_basic,
+            reject: reject_basic,
+        }
+    }
+}
+
+/// Creates an `Extractor` that authenticates the request using `Bearer` scheme.
+///
+/// The function `verify` returns a future that resolves to the principal,
+/// or `None` if the token is invalid.  To reject the token with another error
+/// code, the future can fail with an `AuthError`.
+pub fn bearer<F, R, P>(verify: F) -> Bearer<F>
+where
+    F: Fn(BearerToken) -> R + Clone,
+    R: IntoFuture<Item = Option<P>>,
+    R::Error: Into<Error>,
+{
+    Bearer {
+        verify,
+        realm: DEFAULT_REALM.into(),
+    }
+}
+
+/// An `Extractor` created by `bearer`.
+#[derive(Debug, Clone)]
+pub struct Bearer<F> {
+    verify: F,
+    realm: Arc<str>,
+}
+
+impl<F> Bearer<F> {
+    /// Sets the realm included in the challenge.
+    ///
+    /// The default value is `"Restricted"`.
+    pub fn realm(self, realm: impl Into<String>) -> Self {
+        Self {
+            realm: realm.into().into(),
+            ..self
+        }
+    }
+}
+
+impl<F, R, P> Extractor for Bearer<F>
+where
+    F: Fn(BearerToken) -> R + Clone,
+    R: IntoFuture<Item = Option<P>>,
+    R::Error: Into<Error>,
+{
+    type Output = (P,);
+    type Error = Error;
+    type Extract = Authenticate<F, R, BearerToken>;
+
+    fn extract(&self) -> Self::Extract {
+        Authenticate {
+            state: State::Init,
+            verify: self.verify.clone(),
+            realm: self.realm.clone(),
+            parse: parse_bearer,
+            reject: reject_bearer,
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct Authenticate<F, R: IntoFuture, C> {
+    state: State<R::Future>,
+    verify: F,
+    realm: Arc<str>,
+    parse: fn(&mut Input<'_>, &str) -> Result<C, AuthError>,
+    reject: fn(&str) -> AuthError,
+}
+
+enum State<Fut> {
+    Init,
+    Verify(Fut),
+}
+
+impl<F, R, C, P> TryFuture for Authenticate<F, R, C>
+where
+    F: Fn(C) -> R,
+    R: IntoFuture<Item = Option<P>>,
+    R::Error: Into<Error>,
+{
+    type Ok = (P,);
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        loop {
+            self.state = match self.state {
+                State::Init => {
+                    let credentials = (self.parse)(input, &self.realm)?;
+                    State::Verify((self.verify)(credentials).into_future())
+                }
+                State::Verify(ref mut future) => {
+                    return match future.poll() {
+                        Ok(Async::Ready(Some(principal))) => Ok(Async::Ready((principal,))),
+                        Ok(Async::Ready(None)) => Err((self.reject)(&self.realm).into()),
+                        Ok(Async::NotReady) => Ok(Async::NotReady),
+                        Err(err) => Err(err.into()),
+                    };
+                }
+            };
+        }
+    }
+}
@@ -9,12 +9,18 @@ use {
 /// A proxy object for accessing extracted parameters.
 #[derive(Debug)]
 pub struct Params<'input> {
+    pub(crate) pattern: &'input str,
     pub(crate) path: &'input str,
     pub(crate) names: Option<&'input CaptureNames>,
     pub(crate) captures: Option<&'input Captures>,
 }
 
 impl<'input> Params<'input> {
+    /// Returns the pattern of route matched to the request path, e.g. `"/users/:id"`.
+    pub fn pattern(&self) -> &str {
+        self.pattern
+    }
+
     /// Returns `true` if the extracted paramater exists.
     pub fn is_empty(&self) -> bool {
         self.captures.map_or(true, |captures| {
@@ -1,6 +1,90 @@
-//! A set of built-in `ModifyHandler`s.
+//! A set of built-in `ModifyHandler`s and `ModifyService`s.
 
-pub use self::{default_options::DefaultOptions, map_output::MapOutput};
+mod access_log;
+mod https_redirect;
+mod request_id;
+mod security_headers;
+
+pub use self::{
+    access_log::{AccessLog, LogField, LogFormat},
+    default_options::DefaultOptions,
+    https_redirect::HttpsRedirect,
+    map_output::MapOutput,
+    request_id::{RequestId, SetRequestId},
+    security_headers::{CspNonce, FrameOptions, Hsts, SecurityHeaders},
+    timeout::Timeout,
+};
+
+/// Creates a `ModifyService` that records an access log entry for each request.
+///
+/// The log entries are emitted through the `log` crate, after the response body
+/// has been sent to the client (or the transmission has been aborted).
+/// This modifier wraps the whole `AppService` by `App::with_modify_service`, so that
+/// the requests which do not match any route are also recorded:
+///
+/// ```
+/// # use tsukuyomi::{App, config::prelude::*, modifiers::access_log};
+/// # fn main() -> tsukuyomi::app::Result<()> {
+/// let app = App::create(path!("/").to(endpoint::reply("Hello")))?;
+/// let service = app.with_modify_service(access_log());
+/// # drop(service);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The peer address is taken from the value of `SocketAddr` stored in the extension map
+/// of the request, which is typically inserted by a `ModifyService` at the server side.
+pub fn access_log() -> AccessLog {
+    AccessLog::default()
+}
+
+/// Creates a modifier that assigns a `RequestId` to each request.
+///
+/// The ID is taken from the header field `X-Request-Id` of the incoming request if it is valid,
+/// or generated as a random UUID otherwise. The assigned ID is stored in `Input::locals`
+/// and echoed in the same header field of the response, including error responses.
+///
+/// The returned value can be used both as a `ModifyHandler` and a `ModifyService`.
+/// When it is applied to the whole `AppService`, the ID is also assigned to the requests
+/// which do not match any route. It should be applied inside of `access_log` so that
+/// the logger can record the ID:
+///
+/// ```
+/// # use tsukuyomi::{App, config::prelude::*, modifiers::{access_log, request_id}};
+/// # fn main() -> tsukuyomi::app::Result<()> {
+/// let app = App::create(path!("/").to(endpoint::reply("Hello")))?;
+/// let service = app.with_modify_service(chain![request_id(), access_log()]);
+/// # drop(service);
+/// # Ok(())
+/// # }
+/// ```
+pub fn request_id() -> SetRequestId {
+    SetRequestId::default()
+}
+
+/// Creates a `ModifyHandler` that adds the security-related header fields to the responses.
+///
+/// By default, the following header fields are added:
+///
+/// * `Strict-Transport-Security: max-age=31536000`
+/// * `X-Content-Type-Options: nosniff`
+/// * `X-Frame-Options: DENY`
+/// * `Referrer-Policy: strict-origin-when-cross-origin`
+///
+/// `Content-Security-Policy` and `Permissions-Policy` are added when configured.
+/// The header fields are written into `Input::response_headers`, so they are
+/// applied to error responses as well.
+pub fn security_headers() -> SecurityHeaders {
+    SecurityHeaders::default()
+}
+
+/// Creates a `ModifyHandler` that redirects all requests to the HTTPS URL.
+///
+/// This modifier is intended to be applied to the application served by the plaintext
+/// listener, typically with a fallback route such as `path!("*")`.
+pub fn https_redirect() -> HttpsRedirect {
+    HttpsRedirect::default()
+}
 
 /// Creates a `ModifyHandler` that overwrites the handling when receiving `OPTIONS`.
 pub fn default_options() -> DefaultOptions {
@@ -186,3 +270,166 @@ mod map_output {
         }
     }
 }
+
+/// Creates a `ModifyHandler` that limits the duration of handling each request.
+///
+/// The deadline covers the whole process of the handler, including the extraction
+/// and the reading of the request body. When the deadline expires, the inner handle is
+/// dropped and the request is aborted with `503 Service Unavailable` by default.
+///
+/// The deadline is shared through `Input::locals`, so that a `timeout` applied to an inner
+/// scope or a route replaces the one applied to the outer scope, rather than racing with it.
+/// This allows a route to have a longer timeout than the rest of the scope.
+///
+/// The timer works on both the multi-threaded and the current-thread runtime provided by Tokio.
+pub fn timeout(duration: std::time::Duration) -> Timeout {
+    self::timeout::Timeout {
+        duration,
+        status: http::StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+mod timeout {
+    use {
+        crate::{
+            error::Error,
+            future::{Async, Poll, TryFuture},
+            handler::{AllowedMethods, Handler, ModifyHandler},
+            input::{
+                localmap::{local_key, LocalData},
+                Input,
+            },
+        },
+        futures01::Future,
+        http::StatusCode,
+        std::time::{Duration, Instant},
+        tokio_timer::Delay,
+    };
+
+    #[derive(Debug, Clone)]
+    pub struct Timeout {
+        pub(super) duration: Duration,
+        pub(super) status: StatusCode,
+    }
+
+    impl Timeout {
+        /// Sets the status code of the response returned when the deadline expires.
+        ///
+        /// The default value is `503 Service Unavailable`. Typically, `504 Gateway Timeout`
+        /// is used when the handler waits for the upstream servers.
+        pub fn status(self, status: StatusCode) -> Self {
+            Self { status, ..self }
+        }
+    }
+
+    impl<H> ModifyHandler<H> for Timeout
+    where
+        H: Handler,
+    {
+        type Output = H::Output;
+        type Handler = TimeoutHandler<H>; // private
+
+        fn modify(&self, inner: H) -> Self::Handler {
+            TimeoutHandler {
+                inner,
+                duration: self.duration,
+                status: self.status,
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct TimeoutHandler<H> {
+        inner: H,
+        duration: Duration,
+        status: StatusCode,
+    }
+
+    impl<H> Handler for TimeoutHandler<H>
+    where
+        H: Handler,
+    {
+        type Output = H::Output;
+        type Error = Error;
+        type Handle = HandleTimeout<H::Handle>;
+
+        fn handle(&self) -> Self::Handle {
+            HandleTimeout {
+                inner: Some(self.inner.handle()),
+                deadline: Some(Deadline {
+                    delay: Delay::new(Instant::now() + self.duration),
+                    status: self.status,
+                }),
+            }
+        }
+
+        fn allowed_methods(&self) -> Option<&AllowedMethods> {
+            self.inner.allowed_methods()
+        }
+    }
+
+    /// The deadline of the current request, set by the innermost `timeout`.
+    #[allow(missing_debug_implementations)]
+    struct Deadline {
+        delay: Delay,
+        status: StatusCode,
+    }
+
+    impl LocalData for Deadline {
+        local_key! {
+            const KEY: Self;
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct HandleTimeout<H> {
+        inner: Option<H>,
+        // moved into `Input::locals` at the first polling.
+        deadline: Option<Deadline>,
+    }
+
+    impl<H> TryFuture for HandleTimeout<H>
+    where
+        H: TryFuture,
+    {
+        type Ok = H::Ok;
+        type Error = Error;
+
+        fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+            // The outer handle is polled first, so the deadline set here
+            // is overwritten by the inner ones.
+            if let Some(deadline) = self.deadline.take() {
+                deadline.insert_into(input.locals);
+            }
+
+            {
+                let inner = self
+                    .inner
+                    .as_mut()
+                    .expect("the future has already been polled");
+                if let Async::Ready(output) = inner.poll_ready(input).map_err(Into::into)? {
+                    return Ok(Async::Ready(output));
+                }
+            }
+
+            let deadline = match Deadline::get_mut(input.locals) {
+                Some(deadline) => deadline,
+                None => return Ok(Async::NotReady),
+            };
+            match deadline.delay.poll() {
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Ok(Async::Ready(())) => {
+                    drop(self.inner.take());
+                    Err(crate::error::custom(
+                        deadline.status,
+                        "the request handling has timed out",
+                    ))
+                }
+                Err(err) => {
+                    drop(self.inner.take());
+                    Err(crate::error::internal_server_error(err))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use {
+    super::RequestId,
+    crate::{app::MatchedRoute, output::ResponseBody},
+    futures01::{
+        future::{self, FutureResult},
+        Async, Future, Poll,
+    },
+    http::{header, Request, Response},
+    serde_json::{Map, Value},
+    std::{borrow::Cow, io, net::SocketAddr, sync::Arc, time::Instant},
+    tsukuyomi_service::{ModifyService, Service},
+};
+
+/// The format of access log lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format used by Apache and NGINX.
+    Common,
+    /// The Combined Log Format, which appends `Referer` and `User-Agent` to the Common Log Format.
+    Combined,
+    /// A JSON object per line, containing the fields specified by `AccessLog::fields`.
+    Json,
+}
+
+/// A field of access log entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogField {
+    /// The time when the request was received.
+    Time,
+    /// The request method.
+    Method,
+    /// The request path, including the query string.
+    Path,
+    /// The pattern of route matched to the request path.
+    Route,
+    /// The status code of response.
+    Status,
+    /// The elapsed time until the response body is sent, in milliseconds.
+    Latency,
+    /// The number of bytes in the sent response body.
+    BytesSent,
+    /// The IP address of the peer, without the port number as in the other formats.
+    ///
+    /// The address is taken from the value of `SocketAddr` in the extension map of
+    /// the request, which is not inserted by default. When the application is served
+    /// by `tsukuyomi-server`, it is inserted by `tsukuyomi_server::peer::with_peer_addr`
+    /// applied outside of `AccessLog`:
+    ///
+    /// ```ignore
+    /// let service = app.with_modify_service(chain![
+    ///     access_log(),
+    ///     tsukuyomi_server::peer::with_peer_addr(),
+    /// ]);
+    /// ```
+    PeerAddr,
+    /// The identifier of the request, assigned by `SetRequestId`.
+    RequestId,
+    /// The value of `User-Agent` header.
+    UserAgent,
+}
+
+impl LogField {
+    fn name(self) -> &'static str {
+        match self {
+            LogField::Time => "time",
+            LogField::Method => "method",
+            LogField::Path => "path",
+            LogField::Route => "route",
+            LogField::Status => "status",
+            LogField::Latency => "latency_ms",
+            LogField::BytesSent => "bytes_sent",
+            LogField::PeerAddr => "peer_addr",
+            LogField::RequestId => "request_id",
+            LogField::UserAgent => "user_agent",
+        }
+    }
+}
+
+/// A `ModifyService` that records an access log entry for each request.
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+    inner: Arc<Config>,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    target: &'static str,
+    format: LogFormat,
+    fields: Vec<LogField>,
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Config {
+                target: "tsukuyomi::access_log",
+                format: LogFormat::Combined,
+                fields: vec![
+                    LogField::Time,
+                    LogField::Method,
+                    LogField::Path,
+                    LogField::Route,
+                    LogField::Status,
+                    LogField::Latency,
+                    LogField::BytesSent,
+                    LogField::PeerAddr,
+                    LogField::RequestId,
+                    LogField::UserAgent,
+                ],
+            }),
+        }
+    }
+}
+
+impl AccessLog {
+    fn inner_mut(&mut self) -> &mut Config {
+        Arc::get_mut(&mut self.inner).expect("the instance has already been shared")
+    }
+
+    /// Sets the target name passed to the logger.
+    ///
+    /// The default value is `"tsukuyomi::access_log"`.
+    pub fn target(mut self, target: &'static str) -> Self {
+        self.inner_mut().target = target;
+        self
+    }
+
+    /// Sets the format of log lines.
+    ///
+    /// The default value is `LogFormat::Combined`.
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.inner_mut().format = format;
+        self
+    }
+
+    /// Sets the list of fields recorded in the JSON format.
+    ///
+    /// By default, all of the fields are recorded.
+    pub fn fields(mut self, fields: impl IntoIterator<Item = LogField>) -> Self {
+        self.inner_mut().fields = fields.into_iter().collect();
+        self
+    }
+}
+
+impl<Ctx, Bd, S> ModifyService<Ctx, Request<Bd>, S> for AccessLog
+where
+    S: Service<Request<Bd>, Response = Response<ResponseBody>>,
+{
+    type Response = Response<ResponseBody>;
+    type Error = S::Error;
+    type Service = AccessLogService<S>; // private
+    type ModifyError = io::Error;
+    type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+    fn modify_service(&self, inner: S, _: Ctx) -> Self::Future {
+        future::ok(AccessLogService {
+            inner,
+            config: self.inner.clone(),
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct AccessLogService<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+impl<S, Bd> Service<Request<Bd>> for AccessLogService<S>
+where
+    S: Service<Request<Bd>, Response = Response<ResponseBody>>,
+{
+    type Response = Response<ResponseBody>;
+    type Error = S::Error;
+    type Future = AccessLogFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, request: Request<Bd>) -> Self::Future {
+        let entry = Entry::new(&request, Instant::now(), time::now());
+        AccessLogFuture {
+            inner: self.inner.call(request),
+            config: self.config.clone(),
+            entry: Some(entry),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct AccessLogFuture<F> {
+    inner: F,
+    config: Arc<Config>,
+    entry: Option<Entry>,
+}
+
+impl<F> Future for AccessLogFuture<F>
+where
+    F: Future<Item = Response<ResponseBody>>,
+{
+    type Item = Response<ResponseBody>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let response = futures01::try_ready!(self.inner.poll());
+
+        let mut entry = self
+            .entry
+            .take()
+            .expect("the future has already been polled");
+        entry.status = response.status().as_u16();
+        entry.route = response
+            .extensions()
+            .get::<MatchedRoute>()
+            .map(|route| route.pattern().to_owned());
+        entry.request_id = response
+            .extensions()
+            .get::<RequestId>()
+            .map(|request_id| request_id.as_str().to_owned());
+
+        let config = self.config.clone();
+        Ok(Async::Ready(response.map(move |body| {
+            body.on_complete(move |bytes_sent| {
+                entry.latency = entry.start.elapsed();
+                entry.bytes_sent = bytes_sent;
+                log::info!(target: config.target, "{}", entry.format(&config));
+            })
+        })))
+    }
+}
+
+/// The collected information about a request and its response.
+struct Entry {
+    time: time::Tm,
+    start: Instant,
+    method: String,
+    path: String,
+    version: http::Version,
+    route: Option<String>,
+    status: u16,
+    peer_addr: Option<SocketAddr>,
+    request_id: Option<String>,
+    user_agent: Option<String>,
+    referer: Option<String>,
+    latency: std::time::Duration,
+    bytes_sent: u64,
+}
+
+impl Entry {
+    fn new<Bd>(request: &Request<Bd>, start: Instant, time: time::Tm) -> Self {
+        let header_str = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        Self {
+            time,
+            start,
+            method: request.method().as_str().to_owned(),
+            path: request
+                .uri()
+                .path_and_query()
+                .map_or_else(|| "/".into(), |p| p.as_str().to_owned()),
+            version: request.version(),
+            route: None,
+            status: 0,
+            peer_addr: request.extensions().get::<SocketAddr>().cloned(),
+            request_id: None,
+            user_agent: header_str(header::USER_AGENT.as_str()),
+            referer: header_str(header::REFERER.as_str()),
+            latency: Default::default(),
+            bytes_sent: 0,
+        }
+    }
+
+    fn format(&self, config: &Config) -> String {
+        match config.format {
+            LogFormat::Common => self.format_common(),
+            LogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                self.format_common(),
+                self.referer.as_ref().map_or("-".into(), |s| escape(s)),
+                self.user_agent.as_ref().map_or("-".into(), |s| escape(s)),
+            ),
+            LogFormat::Json => self.format_json(&config.fields),
+        }
+    }
+
+    fn format_common(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {:?}\" {} {}",
+            self.peer_ip().unwrap_or_else(|| "-".into()),
+            time::strftime("%d/%b/%Y:%H:%M:%S %z", &self.time).unwrap_or_default(),
+            self.method,
+            escape(&self.path),
+            self.version,
+            self.status,
+            match self.bytes_sent {
+                0 => "-".into(),
+                n => n.to_string(),
+            },
+        )
+    }
+
+    fn format_json(&self, fields: &[LogField]) -> String {
+        let mut map = Map::new();
+        for &field in fields {
+            let value = match field {
+                LogField::Time => self.time.rfc3339().to_string().into(),
+                LogField::Method => self.method.clone().into(),
+                LogField::Path => self.path.clone().into(),
+                LogField::Route => optional(&self.route),
+                LogField::Status => self.status.into(),
+                LogField::Latency => {
+                    let latency = self.latency.as_secs() as f64 * 1e3
+                        + f64::from(self.latency.subsec_nanos()) * 1e-6;
+                    latency.into()
+                }
+                LogField::BytesSent => self.bytes_sent.into(),
+                LogField::PeerAddr => optional(&self.peer_ip()),
+                LogField::RequestId => optional(&self.request_id),
+                LogField::UserAgent => optional(&self.user_agent),
+            };
+            map.insert(field.name().into(), value);
+        }
+        Value::Object(map).to_string()
+    }
+
+    fn peer_ip(&self) -> Option<String> {
+        self.peer_addr.map(|addr| addr.ip().to_string())
+    }
+}
+
+fn optional(value: &Option<String>) -> Value {
+    value.clone().map_or(Value::Null, Value::String)
+}
+
+/// Escapes the value written in a quoted field of the Common Log Format.
+///
+/// As in Apache and NGINX, the quotes and backslashes are escaped with a backslash
+/// and the control characters are written as `\xHH`, so that the value cannot
+/// terminate the field or the log line.
+fn escape(value: &str) -> Cow<'_, str> {
+    let needs_escape = |b: u8| b == b'"' || b == b'\\' || b < 0x20 || b == 0x7f;
+    if !value.bytes().any(needs_escape) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                escaped.push_str(&format!("\\x{:02X}", c as u32));
+            }
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -0,0 +1,169 @@
+use {
+    crate::{
+        error::Error,
+        future::{Poll, TryFuture},
+        handler::{AllowedMethods, Handler, ModifyHandler},
+        input::Input,
+    },
+    http::{
+        header::{HeaderValue, HOST, LOCATION},
+        Request, Response, StatusCode,
+    },
+};
+
+/// A `ModifyHandler` that redirects the plaintext requests to HTTPS.
+#[derive(Debug, Clone)]
+pub struct HttpsRedirect {
+    status: StatusCode,
+    port: Option<u16>,
+    trust_forwarded_proto: bool,
+}
+
+impl Default for HttpsRedirect {
+    fn default() -> Self {
+        Self {
+            status: StatusCode::PERMANENT_REDIRECT,
+            port: None,
+            trust_forwarded_proto: false,
+        }
+    }
+}
+
+impl HttpsRedirect {
+    /// Sets the status code of the redirect responses.
+    ///
+    /// The default value is `308 Permanent Redirect`, which preserves the request method.
+    pub fn status(self, status: StatusCode) -> Self {
+        Self { status, ..self }
+    }
+
+    /// Sets the port number of the HTTPS listener.
+    ///
+    /// If omitted, the redirect location does not contain the port number (i.e. 443).
+    pub fn port(self, port: u16) -> Self {
+        Self {
+            port: Some(port),
+            ..self
+        }
+    }
+
+    /// Sets whether to trust the header field `X-Forwarded-Proto` or not.
+    ///
+    /// If `true`, the requests with `X-Forwarded-Proto: https` are regarded
+    /// as secure and passed to the inner handler.  This should be enabled only
+    /// when the server is behind a reverse proxy terminating TLS.
+    /// The default value is `false`.
+    pub fn trust_forwarded_proto(self, trust: bool) -> Self {
+        Self {
+            trust_forwarded_proto: trust,
+            ..self
+        }
+    }
+
+    fn is_secure(&self, request: &Request<()>) -> bool {
+        if request.uri().scheme_part().map(|s| s.as_str()) == Some("https") {
+            return true;
+        }
+        self.trust_forwarded_proto
+            && request
+                .headers()
+                .get("x-forwarded-proto")
+                .and_then(|h| h.to_str().ok())
+                .map_or(false, |proto| proto.eq_ignore_ascii_case("https"))
+    }
+
+    fn location(&self, request: &Request<()>) -> Option<HeaderValue> {
+        let authority = match request.uri().authority_part() {
+            Some(authority) => authority.as_str(),
+            None => request.headers().get(HOST)?.to_str().ok()?,
+        };
+        let host = authority.parse::<http::uri::Authority>().ok()?;
+
+        let mut location = format!("https://{}", host.host());
+        if let Some(port) = self.port.filter(|&port| port != 443) {
+            location += &format!(":{}", port);
+        }
+        location += request
+            .uri()
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str());
+
+        HeaderValue::from_str(&location).ok()
+    }
+
+    fn redirect(&self, request: &Request<()>) -> Error {
+        match self.location(request) {
+            Some(location) => {
+                let mut response = Response::new("redirecting to HTTPS");
+                *response.status_mut() = self.status;
+                response.headers_mut().insert(LOCATION, location);
+                crate::error::error_response(response)
+            }
+            None => crate::error::bad_request("missing or invalid host"),
+        }
+    }
+}
+
+impl<H> ModifyHandler<H> for HttpsRedirect
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Handler = HttpsRedirectHandler<H>; // private
+
+    fn modify(&self, inner: H) -> Self::Handler {
+        HttpsRedirectHandler {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HttpsRedirectHandler<H> {
+    inner: H,
+    config: HttpsRedirect,
+}
+
+impl<H> Handler for HttpsRedirectHandler<H>
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Error = Error;
+    type Handle = HandleHttpsRedirect<H::Handle>;
+
+    fn handle(&self) -> Self::Handle {
+        HandleHttpsRedirect {
+            inner: self.inner.handle(),
+            config: Some(self.config.clone()),
+        }
+    }
+
+    fn allowed_methods(&self) -> Option<&AllowedMethods> {
+        self.inner.allowed_methods()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HandleHttpsRedirect<H> {
+    inner: H,
+    config: Option<HttpsRedirect>,
+}
+
+impl<H> TryFuture for HandleHttpsRedirect<H>
+where
+    H: TryFuture,
+{
+    type Ok = H::Ok;
+    type Error = Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        if let Some(config) = self.config.take() {
+            if !config.is_secure(input.request) {
+                return Err(config.redirect(input.request));
+            }
+        }
+        self.inner.poll_ready(input).map_err(Into::into)
+    }
+}
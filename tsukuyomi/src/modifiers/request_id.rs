@@ -0,0 +1,278 @@
+use {
+    crate::{
+        future::{Async, Poll, TryFuture},
+        handler::{AllowedMethods, Handler, ModifyHandler},
+        input::{
+            localmap::{local_key, LocalData},
+            Input,
+        },
+    },
+    futures01::{
+        future::{self, FutureResult},
+        Future,
+    },
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue},
+        Request, Response,
+    },
+    std::{fmt, io},
+    tsukuyomi_service::{ModifyService, Service},
+    uuid::Uuid,
+};
+
+/// The maximum length of request ID accepted from the client.
+const MAX_REQUEST_ID_LEN: usize = 200;
+
+/// The identifier of a request, stored in `Input::locals`.
+///
+/// The value can be extracted by `extractor::local::clone(&RequestId::KEY)`.
+/// When the ID is assigned at the service level, the value is also stored in
+/// the extension maps of the request and the response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Generates a new request ID using UUID v4.
+    pub fn generate() -> Self {
+        RequestId(Uuid::new_v4().to_hyphenated().to_string())
+    }
+
+    /// Returns the string representation of this ID.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn to_header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.0).expect("should be a valid header value")
+    }
+
+    fn from_header_value(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        if value.is_empty()
+            || value.len() > MAX_REQUEST_ID_LEN
+            || !value.bytes().all(|b| b.is_ascii_graphic())
+        {
+            return None;
+        }
+        Some(RequestId(value.to_owned()))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for RequestId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl LocalData for RequestId {
+    local_key! {
+        /// The local key to manage the ID of the current request.
+        const KEY: Self;
+    }
+}
+
+/// A `ModifyHandler` and `ModifyService` that assigns an identifier to each request.
+#[derive(Debug, Clone)]
+pub struct SetRequestId {
+    header_name: HeaderName,
+    trust_incoming: bool,
+}
+
+impl Default for SetRequestId {
+    fn default() -> Self {
+        Self {
+            header_name: HeaderName::from_static("x-request-id"),
+            trust_incoming: true,
+        }
+    }
+}
+
+impl SetRequestId {
+    /// Sets the name of header field used for receiving and echoing the request ID.
+    ///
+    /// The default value is `X-Request-Id`.
+    pub fn header_name(self, header_name: HeaderName) -> Self {
+        Self {
+            header_name,
+            ..self
+        }
+    }
+
+    /// Sets whether to reuse the request ID sent from the client or not.
+    ///
+    /// If `false`, a new ID is always generated. The default value is `true`.
+    pub fn trust_incoming(self, trust_incoming: bool) -> Self {
+        Self {
+            trust_incoming,
+            ..self
+        }
+    }
+
+    fn assign(&self, headers: &HeaderMap) -> RequestId {
+        let incoming = if self.trust_incoming {
+            headers
+                .get(&self.header_name)
+                .and_then(RequestId::from_header_value)
+        } else {
+            None
+        };
+        incoming.unwrap_or_else(RequestId::generate)
+    }
+}
+
+impl<H> ModifyHandler<H> for SetRequestId
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Handler = SetRequestIdHandler<H>; // private
+
+    fn modify(&self, inner: H) -> Self::Handler {
+        SetRequestIdHandler {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SetRequestIdHandler<H> {
+    inner: H,
+    config: SetRequestId,
+}
+
+impl<H> Handler for SetRequestIdHandler<H>
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Error = H::Error;
+    type Handle = HandleSetRequestId<H::Handle>;
+
+    fn handle(&self) -> Self::Handle {
+        HandleSetRequestId {
+            inner: self.inner.handle(),
+            config: Some(self.config.clone()),
+        }
+    }
+
+    fn allowed_methods(&self) -> Option<&AllowedMethods> {
+        self.inner.allowed_methods()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HandleSetRequestId<H> {
+    inner: H,
+    config: Option<SetRequestId>,
+}
+
+impl<H> TryFuture for HandleSetRequestId<H>
+where
+    H: TryFuture,
+{
+    type Ok = H::Ok;
+    type Error = H::Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        if let Some(config) = self.config.take() {
+            // reuse the ID assigned at the service level, if any.
+            let request_id = RequestId::get(&*input.locals).cloned().unwrap_or_else(|| {
+                let request_id = config.assign(input.request.headers());
+                request_id.clone().insert_into(input.locals);
+                request_id
+            });
+
+            // The ID is echoed even if the handler returns an error,
+            // since the supplemental response headers are applied to error responses as well.
+            input
+                .response_headers
+                .get_or_insert_with(HeaderMap::new)
+                .insert(config.header_name, request_id.to_header_value());
+        }
+
+        self.inner.poll_ready(input)
+    }
+}
+
+impl<Ctx, Bd, S, ResBd> ModifyService<Ctx, Request<Bd>, S> for SetRequestId
+where
+    S: Service<Request<Bd>, Response = Response<ResBd>>,
+{
+    type Response = Response<ResBd>;
+    type Error = S::Error;
+    type Service = SetRequestIdService<S>; // private
+    type ModifyError = io::Error;
+    type Future = FutureResult<Self::Service, Self::ModifyError>;
+
+    fn modify_service(&self, inner: S, _: Ctx) -> Self::Future {
+        future::ok(SetRequestIdService {
+            inner,
+            config: self.clone(),
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SetRequestIdService<S> {
+    inner: S,
+    config: SetRequestId,
+}
+
+impl<S, Bd, ResBd> Service<Request<Bd>> for SetRequestIdService<S>
+where
+    S: Service<Request<Bd>, Response = Response<ResBd>>,
+{
+    type Response = Response<ResBd>;
+    type Error = S::Error;
+    type Future = SetRequestIdFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut request: Request<Bd>) -> Self::Future {
+        // `AppService` copies the ID into `Input::locals`.
+        let request_id = self.config.assign(request.headers());
+        request.extensions_mut().insert(request_id.clone());
+        SetRequestIdFuture {
+            inner: self.inner.call(request),
+            header_name: self.config.header_name.clone(),
+            request_id: Some(request_id),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SetRequestIdFuture<F> {
+    inner: F,
+    header_name: HeaderName,
+    request_id: Option<RequestId>,
+}
+
+impl<F, ResBd> Future for SetRequestIdFuture<F>
+where
+    F: Future<Item = Response<ResBd>>,
+{
+    type Item = Response<ResBd>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut response = futures01::try_ready!(self.inner.poll());
+        let request_id = self
+            .request_id
+            .take()
+            .expect("the future has already been polled");
+        response
+            .headers_mut()
+            .insert(self.header_name.clone(), request_id.to_header_value());
+        response.extensions_mut().insert(request_id);
+        Ok(Async::Ready(response))
+    }
+}
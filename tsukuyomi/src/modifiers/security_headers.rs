@@ -0,0 +1,357 @@
+use {
+    crate::{
+        future::{Poll, TryFuture},
+        handler::{AllowedMethods, Handler, ModifyHandler},
+        input::{
+            localmap::{local_key, LocalData},
+            Input,
+        },
+    },
+    http::header::{
+        HeaderMap, HeaderName, HeaderValue, CONTENT_SECURITY_POLICY,
+        CONTENT_SECURITY_POLICY_REPORT_ONLY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+        X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+    std::{fmt, sync::Arc, time::Duration},
+    uuid::Uuid,
+};
+
+/// The placeholder in the policy of `Content-Security-Policy` replaced with the nonce.
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// The nonce of `Content-Security-Policy` generated for the current request.
+///
+/// The value can be extracted by `extractor::local::clone(&CspNonce::KEY)`
+/// and used in the templates, e.g. `<script nonce="{{ nonce }}">`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        CspNonce(base64::encode(Uuid::new_v4().as_bytes()))
+    }
+
+    /// Returns the string representation of this nonce.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl LocalData for CspNonce {
+    local_key! {
+        /// The local key to manage the CSP nonce of the current request.
+        const KEY: Self;
+    }
+}
+
+/// The configuration of `Strict-Transport-Security`.
+#[derive(Debug, Clone)]
+pub struct Hsts {
+    max_age: Duration,
+    include_subdomains: bool,
+    preload: bool,
+}
+
+impl Hsts {
+    /// Creates an `Hsts` with the specified `max-age`.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+
+    /// Sets whether to add the directive `includeSubDomains`.
+    pub fn include_subdomains(self, include_subdomains: bool) -> Self {
+        Self {
+            include_subdomains,
+            ..self
+        }
+    }
+
+    /// Sets whether to add the directive `preload`.
+    pub fn preload(self, preload: bool) -> Self {
+        Self { preload, ..self }
+    }
+
+    fn to_header_value(&self) -> HeaderValue {
+        let mut value = format!("max-age={}", self.max_age.as_secs());
+        if self.include_subdomains {
+            value += "; includeSubDomains";
+        }
+        if self.preload {
+            value += "; preload";
+        }
+        HeaderValue::from_str(&value).expect("should be a valid header value")
+    }
+}
+
+/// The value of `X-Frame-Options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// The page cannot be displayed in a frame.
+    Deny,
+    /// The page can only be displayed in a frame on the same origin.
+    SameOrigin,
+}
+
+impl FrameOptions {
+    fn as_str(self) -> &'static str {
+        match self {
+            FrameOptions::Deny => "DENY",
+            FrameOptions::SameOrigin => "SAMEORIGIN",
+        }
+    }
+
+    fn frame_ancestors(self) -> &'static str {
+        match self {
+            FrameOptions::Deny => "frame-ancestors 'none'",
+            FrameOptions::SameOrigin => "frame-ancestors 'self'",
+        }
+    }
+}
+
+/// A `ModifyHandler` that adds the security-related header fields to the responses.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    inner: Arc<Config>,
+}
+
+#[derive(Debug)]
+struct Config {
+    hsts: Option<Hsts>,
+    csp: Option<String>,
+    csp_report_only: bool,
+    nosniff: bool,
+    frame_options: Option<FrameOptions>,
+    referrer_policy: Option<HeaderValue>,
+    permissions_policy: Option<HeaderValue>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Config {
+                hsts: Some(Hsts::new(Duration::from_secs(365 * 24 * 60 * 60))),
+                csp: None,
+                csp_report_only: false,
+                nosniff: true,
+                frame_options: Some(FrameOptions::Deny),
+                referrer_policy: Some(HeaderValue::from_static("strict-origin-when-cross-origin")),
+                permissions_policy: None,
+            }),
+        }
+    }
+}
+
+impl SecurityHeaders {
+    fn inner_mut(&mut self) -> &mut Config {
+        Arc::get_mut(&mut self.inner).expect("the value has already been shared")
+    }
+
+    /// Sets the configuration of `Strict-Transport-Security`.
+    ///
+    /// The default value is `max-age=31536000`.  If `None` is given,
+    /// the header field is not added.
+    pub fn hsts(mut self, hsts: Option<Hsts>) -> Self {
+        self.inner_mut().hsts = hsts;
+        self
+    }
+
+    /// Sets the policy of `Content-Security-Policy`.
+    ///
+    /// If the policy contains the placeholder `{nonce}`, it is replaced with
+    /// `'nonce-<value>'` using the random value generated for each request.
+    /// The value is stored in `Input::locals` as `CspNonce`.
+    ///
+    /// If `X-Frame-Options` is enabled and the policy does not have the directive
+    /// `frame-ancestors`, the equivalent directive is appended to the policy.
+    pub fn content_security_policy(mut self, policy: impl Into<String>) -> Self {
+        self.inner_mut().csp = Some(policy.into());
+        self
+    }
+
+    /// Sets whether to send the policy with `Content-Security-Policy-Report-Only`
+    /// instead of `Content-Security-Policy`.
+    ///
+    /// The default value is `false`.
+    pub fn content_security_policy_report_only(mut self, report_only: bool) -> Self {
+        self.inner_mut().csp_report_only = report_only;
+        self
+    }
+
+    /// Sets whether to add `X-Content-Type-Options: nosniff`.
+    ///
+    /// The default value is `true`.
+    pub fn nosniff(mut self, enabled: bool) -> Self {
+        self.inner_mut().nosniff = enabled;
+        self
+    }
+
+    /// Sets the value of `X-Frame-Options`.
+    ///
+    /// The default value is `DENY`.  If `None` is given, the header field is not added.
+    pub fn frame_options(mut self, frame_options: Option<FrameOptions>) -> Self {
+        self.inner_mut().frame_options = frame_options;
+        self
+    }
+
+    /// Sets the value of `Referrer-Policy`.
+    ///
+    /// The default value is `strict-origin-when-cross-origin`.
+    /// If `None` is given, the header field is not added.
+    pub fn referrer_policy(mut self, policy: Option<HeaderValue>) -> Self {
+        self.inner_mut().referrer_policy = policy;
+        self
+    }
+
+    /// Sets the value of `Permissions-Policy`, e.g. `geolocation=(), camera=()`.
+    ///
+    /// The header field is not added by default.
+    pub fn permissions_policy(mut self, policy: HeaderValue) -> Self {
+        self.inner_mut().permissions_policy = Some(policy);
+        self
+    }
+}
+
+impl Config {
+    fn content_security_policy(&self, input: &mut Input<'_>) -> Option<HeaderValue> {
+        let policy = self.csp.as_ref()?;
+
+        let mut policy = if policy.contains(NONCE_PLACEHOLDER) {
+            let nonce = CspNonce::get(&*input.locals).cloned().unwrap_or_else(|| {
+                let nonce = CspNonce::generate();
+                nonce.clone().insert_into(input.locals);
+                nonce
+            });
+            policy.replace(NONCE_PLACEHOLDER, &format!("'nonce-{}'", nonce))
+        } else {
+            policy.clone()
+        };
+
+        if let Some(frame_options) = self.frame_options {
+            if !policy.contains("frame-ancestors") {
+                if !policy.trim_end().is_empty() && !policy.trim_end().ends_with(';') {
+                    policy.push(';');
+                }
+                policy.push(' ');
+                policy += frame_options.frame_ancestors();
+            }
+        }
+
+        // The policy is validated here since it may contain the placeholder at configuration.
+        match HeaderValue::from_str(policy.trim()) {
+            Ok(value) => Some(value),
+            Err(..) => {
+                log::warn!("invalid Content-Security-Policy: {:?}", policy);
+                None
+            }
+        }
+    }
+
+    fn insert_headers(&self, input: &mut Input<'_>) {
+        let csp = self.content_security_policy(input);
+        let headers = input.response_headers.get_or_insert_with(HeaderMap::new);
+
+        if let Some(ref hsts) = self.hsts {
+            headers.insert(STRICT_TRANSPORT_SECURITY, hsts.to_header_value());
+        }
+        if let Some(csp) = csp {
+            let name = if self.csp_report_only {
+                CONTENT_SECURITY_POLICY_REPORT_ONLY
+            } else {
+                CONTENT_SECURITY_POLICY
+            };
+            headers.insert(name, csp);
+        }
+        if self.nosniff {
+            headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        }
+        if let Some(frame_options) = self.frame_options {
+            headers.insert(
+                X_FRAME_OPTIONS,
+                HeaderValue::from_static(frame_options.as_str()),
+            );
+        }
+        if let Some(ref policy) = self.referrer_policy {
+            headers.insert(REFERRER_POLICY, policy.clone());
+        }
+        if let Some(ref policy) = self.permissions_policy {
+            headers.insert(
+                HeaderName::from_static("permissions-policy"),
+                policy.clone(),
+            );
+        }
+    }
+}
+
+impl<H> ModifyHandler<H> for SecurityHeaders
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Handler = SecurityHeadersHandler<H>; // private
+
+    fn modify(&self, inner: H) -> Self::Handler {
+        SecurityHeadersHandler {
+            inner,
+            config: self.inner.clone(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct SecurityHeadersHandler<H> {
+    inner: H,
+    config: Arc<Config>,
+}
+
+impl<H> Handler for SecurityHeadersHandler<H>
+where
+    H: Handler,
+{
+    type Output = H::Output;
+    type Error = H::Error;
+    type Handle = HandleSecurityHeaders<H::Handle>;
+
+    fn handle(&self) -> Self::Handle {
+        HandleSecurityHeaders {
+            inner: self.inner.handle(),
+            config: Some(self.config.clone()),
+        }
+    }
+
+    fn allowed_methods(&self) -> Option<&AllowedMethods> {
+        self.inner.allowed_methods()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+pub struct HandleSecurityHeaders<H> {
+    inner: H,
+    config: Option<Arc<Config>>,
+}
+
+impl<H> TryFuture for HandleSecurityHeaders<H>
+where
+    H: TryFuture,
+{
+    type Ok = H::Ok;
+    type Error = H::Error;
+
+    fn poll_ready(&mut self, input: &mut Input<'_>) -> Poll<Self::Ok, Self::Error> {
+        if let Some(config) = self.config.take() {
+            // The nonce must be available before calling the inner handler,
+            // and the header fields are applied to error responses as well.
+            config.insert_headers(input);
+        }
+        self.inner.poll_ready(input)
+    }
+}
@@ -7,10 +7,11 @@ pub use tsukuyomi_macros::IntoResponse;
 use {
     crate::{error::Error, input::body::RequestBody, util::Never},
     bytes::{Buf, Bytes, IntoBuf},
-    futures01::{Poll, Stream},
+    futures01::{Async, Poll, Stream},
     http::{header::HeaderMap, Request, Response, StatusCode},
     hyper::body::{Body, Payload},
     serde::Serialize,
+    std::fmt,
 };
 
 // the private API for custom derive.
@@ -27,7 +28,19 @@ pub mod internal {
 
 /// A type representing the message body in an HTTP response.
 #[derive(Debug, Default)]
-pub struct ResponseBody(Body);
+pub struct ResponseBody(Inner);
+
+#[derive(Debug)]
+enum Inner {
+    Body(Body),
+    Observed(Observed),
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Inner::Body(Body::empty())
+    }
+}
 
 impl ResponseBody {
     /// Creates an empty `ResponseBody`.
@@ -43,21 +56,38 @@ impl ResponseBody {
         S::Error: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
         S::Item: IntoBuf,
     {
-        ResponseBody(Body::wrap_stream(
+        ResponseBody(Inner::Body(Body::wrap_stream(
             stream.map(|chunk| chunk.into_buf().collect::<Bytes>()),
-        ))
+        )))
+    }
+
+    /// Registers a callback that will be called with the number of sent bytes
+    /// when the transmission of this body is completed or aborted.
+    ///
+    /// The callback is called at most once, even if the body is never polled.
+    pub fn on_complete(self, f: impl FnMut(u64) + Send + 'static) -> Self {
+        let mut observed = match self.0 {
+            Inner::Body(body) => Observed {
+                body,
+                sent: 0,
+                callbacks: vec![],
+            },
+            Inner::Observed(observed) => observed,
+        };
+        observed.callbacks.push(Box::new(f));
+        ResponseBody(Inner::Observed(observed))
     }
 }
 
 impl From<()> for ResponseBody {
     fn from(_: ()) -> Self {
-        ResponseBody(Body::empty())
+        ResponseBody(Inner::Body(Body::empty()))
     }
 }
 
 impl From<RequestBody> for ResponseBody {
     fn from(body: RequestBody) -> Self {
-        ResponseBody(body.into_inner())
+        ResponseBody(Inner::Body(body.into_inner()))
     }
 }
 
@@ -65,7 +95,7 @@ macro_rules! impl_response_body {
     ($($t:ty,)*) => {$(
         impl From<$t> for ResponseBody {
             fn from(body: $t) -> Self {
-                ResponseBody(Body::from(body))
+                ResponseBody(Inner::Body(Body::from(body)))
             }
         }
     )*};
@@ -89,25 +119,85 @@ impl Payload for ResponseBody {
     #[inline]
     #[cfg_attr(tarpaulin, skip)]
     fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
-        self.0.poll_data()
+        match self.0 {
+            Inner::Body(ref mut body) => body.poll_data(),
+            Inner::Observed(ref mut observed) => observed.poll_data(),
+        }
     }
 
     #[inline]
     #[cfg_attr(tarpaulin, skip)]
     fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
-        self.0.poll_trailers()
+        match self.0 {
+            Inner::Body(ref mut body) => body.poll_trailers(),
+            Inner::Observed(ref mut observed) => observed.body.poll_trailers(),
+        }
     }
 
     #[inline]
     #[cfg_attr(tarpaulin, skip)]
     fn is_end_stream(&self) -> bool {
-        self.0.is_end_stream()
+        match self.0 {
+            Inner::Body(ref body) => body.is_end_stream(),
+            Inner::Observed(ref observed) => observed.body.is_end_stream(),
+        }
     }
 
     #[inline]
     #[cfg_attr(tarpaulin, skip)]
     fn content_length(&self) -> Option<u64> {
-        self.0.content_length()
+        match self.0 {
+            Inner::Body(ref body) => body.content_length(),
+            Inner::Observed(ref observed) => observed.body.content_length(),
+        }
+    }
+}
+
+/// A `Body` that counts the number of sent bytes.
+struct Observed {
+    body: Body,
+    sent: u64,
+    callbacks: Vec<Box<dyn FnMut(u64) + Send + 'static>>,
+}
+
+impl fmt::Debug for Observed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observed")
+            .field("body", &self.body)
+            .field("sent", &self.sent)
+            .finish()
+    }
+}
+
+impl Observed {
+    fn poll_data(&mut self) -> Poll<Option<hyper::Chunk>, hyper::Error> {
+        match self.body.poll_data() {
+            Ok(Async::Ready(Some(chunk))) => {
+                self.sent += chunk.len() as u64;
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Ok(Async::Ready(None)) => {
+                self.complete();
+                Ok(Async::Ready(None))
+            }
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(err) => {
+                self.complete();
+                Err(err)
+            }
+        }
+    }
+
+    fn complete(&mut self) {
+        for mut f in self.callbacks.drain(..) {
+            f(self.sent);
+        }
+    }
+}
+
+impl Drop for Observed {
+    fn drop(&mut self) {
+        self.complete();
     }
 }
 
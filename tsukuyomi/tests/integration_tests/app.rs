@@ -210,3 +210,50 @@ fn scoped_fallback() -> tsukuyomi_server::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn call_service() -> tsukuyomi_server::Result<()> {
+    use {
+        http::Response,
+        tsukuyomi::{
+            input::body::RequestBody,
+            vendor::futures::{Async, Future, Poll, Stream},
+        },
+        tsukuyomi_service::Service,
+    };
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<RequestBody>> for Echo {
+        type Response = Response<String>;
+        type Error = hyper::Error;
+        type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error> + Send>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, request: Request<RequestBody>) -> Self::Future {
+            let path = request.uri().path().to_owned();
+            Box::new(request.into_body().concat2().map(move |body| {
+                Response::new(format!("{}:{}", path, String::from_utf8_lossy(&body)))
+            }))
+        }
+    }
+
+    let app = App::create(
+        path!("/echo") //
+            .to(endpoint::post().call_service(Echo)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(Request::post("/echo").body("hello"))?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().to_utf8()?, "/echo:hello");
+
+    let response = server.perform("/echo")?;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    Ok(())
+}
@@ -426,3 +426,96 @@ fn either_or() -> tsukuyomi_server::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn auth_basic() -> tsukuyomi_server::Result<()> {
+    use {
+        tsukuyomi::extractor::auth::{self, BasicCredentials},
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get()
+                .extract(
+                    auth::basic(|credentials: BasicCredentials| {
+                        Ok::<_, tsukuyomi::Error>(if credentials.password() == "secret" {
+                            Some(credentials.username().to_owned())
+                        } else {
+                            None
+                        })
+                    })
+                    .realm("admin"),
+                )
+                .call(|username: String| username)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response =
+        server.perform(Request::get("/").header("authorization", "Basic YWxpY2U6c2VjcmV0"))?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "alice");
+
+    let response =
+        server.perform(Request::get("/").header("authorization", "basic YWxpY2U6c2VjcmV0"))?;
+    assert_eq!(response.status(), 200);
+
+    let response =
+        server.perform(Request::get("/").header("authorization", "Basic YWxpY2U6d3Jvbmc="))?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Basic realm="admin", charset="UTF-8""#
+    );
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 401);
+
+    Ok(())
+}
+
+#[test]
+fn auth_bearer() -> tsukuyomi_server::Result<()> {
+    use {
+        tsukuyomi::extractor::auth::{self, BearerToken},
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::get()
+                .extract(auth::bearer(|token: BearerToken| {
+                    Ok::<_, tsukuyomi::Error>(if token.as_str() == "mF_9.B5f-4.1JqM" {
+                        Some(42u32)
+                    } else {
+                        None
+                    })
+                }))
+                .call(|user_id: u32| format!("{}", user_id))),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response =
+        server.perform(Request::get("/").header("authorization", "Bearer mF_9.B5f-4.1JqM"))?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "42");
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Bearer realm="Restricted""#
+    );
+
+    let response = server.perform(Request::get("/").header("authorization", "Bearer invalid"))?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.header("www-authenticate")?,
+        r#"Bearer realm="Restricted", error="invalid_token", error_description="the access token is invalid""#
+    );
+
+    let response = server.perform(Request::get("/").header("authorization", "Bearer in valid"))?;
+    assert_eq!(response.status(), 400);
+
+    Ok(())
+}
@@ -175,3 +175,509 @@ fn nested_modifiers() -> tsukuyomi_server::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn timeout_modifier() -> tsukuyomi_server::Result<()> {
+    use {
+        http::StatusCode,
+        std::time::{Duration, Instant},
+        tokio_timer::Delay,
+        tsukuyomi::{
+            modifiers::timeout,
+            vendor::futures::{future, Future},
+        },
+    };
+
+    let app = App::create(
+        chain![
+            path!("/fast") //
+                .to(endpoint::reply("fast")),
+            path!("/slow") //
+                .to(endpoint::call_async(|| {
+                    future::empty::<&'static str, tsukuyomi::Error>()
+                })),
+            path!("/upstream")
+                .to(endpoint::call_async(|| {
+                    future::empty::<&'static str, tsukuyomi::Error>()
+                }))
+                .modify(timeout(Duration::from_millis(10)).status(StatusCode::GATEWAY_TIMEOUT)),
+            path!("/long")
+                .to(endpoint::call_async(|| {
+                    Delay::new(Instant::now() + Duration::from_millis(100))
+                        .map(|()| "long")
+                        .map_err(tsukuyomi::error::internal_server_error)
+                }))
+                .modify(timeout(Duration::from_millis(500))),
+        ]
+        .modify(timeout(Duration::from_millis(50))),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform("/fast")?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.perform("/slow")?;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = server.perform("/upstream")?;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+    // the timeout of the route overrides the one of the scope.
+    let response = server.perform("/long")?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[test]
+fn timeout_modifier_current_thread() -> tsukuyomi_server::Result<()> {
+    use {
+        http::StatusCode,
+        std::time::Duration,
+        tsukuyomi::{app::LocalApp, modifiers::timeout, vendor::futures::future},
+    };
+
+    let app = LocalApp::create(
+        path!("/")
+            .to(endpoint::call_async(|| {
+                future::empty::<&'static str, tsukuyomi::Error>()
+            }))
+            .modify(timeout(Duration::from_millis(10))),
+    )?;
+    let mut server = tsukuyomi_server::test::local_server(app)?;
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    Ok(())
+}
+
+/// A logger that captures the log lines, for inspecting the output of the modifiers.
+mod capture_log {
+    use {
+        log::{Log, Metadata, Record},
+        std::sync::{Mutex, Once},
+    };
+
+    lazy_static::lazy_static! {
+        static ref RECORDS: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
+    }
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            RECORDS
+                .lock()
+                .unwrap()
+                .push((record.target().into(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    pub fn init() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CaptureLogger).expect("another logger has already been set");
+            log::set_max_level(log::LevelFilter::Info);
+        });
+    }
+
+    /// Takes the captured log lines with the specified target.
+    pub fn take(target: &str) -> Vec<String> {
+        let mut records = RECORDS.lock().unwrap();
+        let (taken, rest) = records.drain(..).partition(|(t, _)| t == target);
+        *records = rest;
+        taken.into_iter().map(|(_, line)| line).collect()
+    }
+}
+
+#[test]
+fn access_log_modifier() -> tsukuyomi_server::Result<()> {
+    use {
+        http::{header::CONTENT_LENGTH, Request, StatusCode},
+        serde_json::Value,
+        tsukuyomi::modifiers::{access_log, LogFormat},
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    capture_log::init();
+
+    let app = App::create(chain![
+        path!("/") //
+            .to(endpoint::reply("hello")),
+        path!("/users/:id") //
+            .to(endpoint::call(|id: u32| format!("user {}", id))),
+        path!("/error") //
+            .to(endpoint::reply(Err::<(), _>(StatusCode::BAD_REQUEST))),
+    ])?;
+    let mut server = tsukuyomi_server::test::server(
+        app.with_modify_service(
+            access_log()
+                .target("test::access_log::json")
+                .format(LogFormat::Json),
+        ),
+    )?;
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header(CONTENT_LENGTH)?, "5");
+    assert_eq!(response.body().to_utf8()?, "hello");
+
+    let response = server.perform(
+        Request::get("/users/42?verbose=true") //
+            .header("user-agent", "test-client/1.0"),
+    )?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.perform("/error")?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // the request that does not match any route.
+    let response = server.perform("/noroute")?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let lines = capture_log::take("test::access_log::json");
+    assert_eq!(lines.len(), 4, "{:?}", lines);
+    let entries: Vec<Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).expect("the log line is not a JSON"))
+        .collect();
+
+    assert_eq!(entries[0]["method"], "GET");
+    assert_eq!(entries[0]["path"], "/");
+    assert_eq!(entries[0]["route"], "/");
+    assert_eq!(entries[0]["status"], 200);
+    assert_eq!(entries[0]["bytes_sent"], 5);
+    assert!(entries[0]["latency_ms"]
+        .as_f64()
+        .map_or(false, |l| l >= 0.0));
+    assert!(entries[0]["time"].is_string());
+    assert_eq!(entries[0]["user_agent"], Value::Null);
+
+    assert_eq!(entries[1]["path"], "/users/42?verbose=true");
+    assert_eq!(entries[1]["route"], "/users/:id");
+    assert_eq!(entries[1]["bytes_sent"], 7);
+    assert_eq!(entries[1]["user_agent"], "test-client/1.0");
+
+    assert_eq!(entries[2]["route"], "/error");
+    assert_eq!(entries[2]["status"], 400);
+
+    assert_eq!(entries[3]["path"], "/noroute");
+    assert_eq!(entries[3]["route"], Value::Null);
+    assert_eq!(entries[3]["status"], 404);
+
+    Ok(())
+}
+
+#[test]
+fn access_log_common_format() -> tsukuyomi_server::Result<()> {
+    use {
+        http::{Request, StatusCode},
+        std::net::SocketAddr,
+        tsukuyomi::modifiers::{access_log, LogFormat},
+    };
+
+    capture_log::init();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::reply("hello")),
+    )?;
+    let mut server = tsukuyomi_server::test::server(
+        app.with_modify_service(
+            access_log()
+                .target("test::access_log::combined")
+                .format(LogFormat::Combined),
+        ),
+    )?;
+
+    let peer_addr: SocketAddr = "192.0.2.1:54321".parse().unwrap();
+    let response = server.perform(
+        Request::get("/")
+            .header("referer", "http://example.com/")
+            .header("user-agent", "test-client/1.0 \"evil\" \\")
+            .extension(peer_addr),
+    )?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.perform("/noroute")?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let lines = capture_log::take("test::access_log::combined");
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+
+    // the peer address is recorded without the port number, as in the JSON format.
+    assert!(lines[0].starts_with("192.0.2.1 - - ["), "{}", lines[0]);
+    assert!(
+        lines[0].ends_with(
+            "] \"GET / HTTP/1.1\" 200 5 \"http://example.com/\" \
+                 \"test-client/1.0 \\\"evil\\\" \\\\\""
+        ),
+        "{}",
+        lines[0]
+    );
+
+    assert!(lines[1].starts_with("- - - ["), "{}", lines[1]);
+    assert!(
+        lines[1].contains("] \"GET /noroute HTTP/1.1\" 404 "),
+        "{}",
+        lines[1]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn access_log_records_peer_addr() -> tsukuyomi_server::Result<()> {
+    use {
+        futures01::{sync::oneshot, Future},
+        std::{
+            io::{Read, Write},
+            net::{TcpListener, TcpStream},
+            thread,
+        },
+        tsukuyomi::modifiers::{access_log, LogFormat},
+        tsukuyomi_server::{peer::with_peer_addr, Server},
+    };
+
+    capture_log::init();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::reply("hello")),
+    )?;
+
+    // the stock server with the peer address inserted outside of the logger.
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = Server::new(app.with_modify_service(chain![
+        access_log()
+            .target("test::access_log::peer")
+            .format(LogFormat::Common),
+        with_peer_addr(),
+    ]))
+    .bind(listener)
+    .shutdown_signal(rx.map_err(|_| ()));
+    let handle = thread::spawn(move || server.run());
+
+    let mut stream = TcpStream::connect(addr)?;
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+    tx.send(()).expect("the server has already stopped");
+    handle.join().expect("the server thread has panicked")?;
+
+    let lines = capture_log::take("test::access_log::peer");
+    assert_eq!(lines.len(), 1, "{:?}", lines);
+    assert!(lines[0].starts_with("127.0.0.1 - - ["), "{}", lines[0]);
+    assert!(
+        lines[0].ends_with("] \"GET / HTTP/1.1\" 200 5"),
+        "{}",
+        lines[0]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn request_id_modifier() -> tsukuyomi_server::Result<()> {
+    use {
+        http::{Request, StatusCode},
+        tsukuyomi::{
+            extractor,
+            input::localmap::LocalData,
+            modifiers::{request_id, RequestId},
+        },
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    let app = App::create(
+        chain![
+            path!("/") //
+                .to(endpoint::any()
+                    .extract(extractor::local::clone(&RequestId::KEY))
+                    .call(|id: RequestId| id.to_string())),
+            path!("/error") //
+                .to(endpoint::reply(Err::<(), _>(StatusCode::BAD_REQUEST))),
+        ]
+        .modify(request_id()),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform(Request::get("/").header("x-request-id", "foo-bar"))?;
+    assert_eq!(response.header("x-request-id")?, "foo-bar");
+    assert_eq!(response.body().to_utf8()?, "foo-bar");
+
+    let response = server.perform("/")?;
+    let generated = response.header("x-request-id")?.to_str()?.to_owned();
+    assert_eq!(generated.len(), 36);
+    assert_eq!(response.body().to_utf8()?, generated);
+
+    let response = server.perform(Request::get("/error").header("x-request-id", "baz"))?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.header("x-request-id")?, "baz");
+
+    Ok(())
+}
+
+#[test]
+fn request_id_service() -> tsukuyomi_server::Result<()> {
+    use {
+        http::{header::HeaderName, Request, StatusCode},
+        serde_json::Value,
+        tsukuyomi::{
+            extractor,
+            input::localmap::LocalData,
+            modifiers::{access_log, request_id, LogField, LogFormat, RequestId},
+        },
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    capture_log::init();
+
+    let app = App::create(
+        path!("/") //
+            .to(endpoint::any()
+                .extract(extractor::local::clone(&RequestId::KEY))
+                .call(|id: RequestId| id.to_string())),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app.with_modify_service(chain![
+            request_id().header_name(HeaderName::from_static("x-correlation-id")),
+            access_log()
+                .target("test::request_id")
+                .format(LogFormat::Json)
+                .fields(vec![LogField::Path, LogField::RequestId]),
+        ]))?;
+
+    let response = server.perform(Request::get("/").header("x-correlation-id", "foo-bar"))?;
+    assert_eq!(response.header("x-correlation-id")?, "foo-bar");
+    assert_eq!(response.body().to_utf8()?, "foo-bar");
+
+    // the ID is also assigned to the request that does not match any route.
+    let response = server.perform(
+        Request::get("/noroute")
+            .header("x-correlation-id", "baz")
+            .header("x-request-id", "ignored"),
+    )?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("x-correlation-id")?, "baz");
+
+    let lines = capture_log::take("test::request_id");
+    let entries: Vec<Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).expect("the log line is not a JSON"))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            serde_json::json!({ "path": "/", "request_id": "foo-bar" }),
+            serde_json::json!({ "path": "/noroute", "request_id": "baz" }),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn security_headers_modifier() -> tsukuyomi_server::Result<()> {
+    use {
+        http::StatusCode,
+        tsukuyomi::{
+            extractor,
+            input::localmap::LocalData,
+            modifiers::{security_headers, CspNonce, FrameOptions},
+        },
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    let app = App::create(
+        chain![
+            path!("/") //
+                .to(endpoint::any()
+                    .extract(extractor::local::clone(&CspNonce::KEY))
+                    .call(|nonce: CspNonce| nonce.to_string())),
+            path!("/error") //
+                .to(endpoint::reply(Err::<(), _>(StatusCode::BAD_REQUEST))),
+        ]
+        .modify(
+            security_headers()
+                .content_security_policy("default-src 'self'; script-src {nonce}")
+                .frame_options(Some(FrameOptions::SameOrigin)),
+        ),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response = server.perform("/")?;
+    assert_eq!(response.status(), 200);
+    let nonce = response.body().to_utf8()?.into_owned();
+    assert_eq!(
+        response.header("content-security-policy")?,
+        &*format!(
+            "default-src 'self'; script-src 'nonce-{}'; frame-ancestors 'self'",
+            nonce
+        )
+    );
+    assert_eq!(
+        response.header("strict-transport-security")?,
+        "max-age=31536000"
+    );
+    assert_eq!(response.header("x-content-type-options")?, "nosniff");
+    assert_eq!(response.header("x-frame-options")?, "SAMEORIGIN");
+    assert_eq!(
+        response.header("referrer-policy")?,
+        "strict-origin-when-cross-origin"
+    );
+
+    // the nonce is generated for each request.
+    let response = server.perform("/")?;
+    assert_ne!(response.body().to_utf8()?, nonce);
+
+    let response = server.perform("/error")?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.header("x-content-type-options")?, "nosniff");
+
+    Ok(())
+}
+
+#[test]
+fn https_redirect_modifier() -> tsukuyomi_server::Result<()> {
+    use {
+        http::{Request, StatusCode},
+        tsukuyomi::modifiers::https_redirect,
+        tsukuyomi_server::test::ResponseExt,
+    };
+
+    let app = App::create(
+        path!("*") //
+            .to(endpoint::reply("secure"))
+            .modify(https_redirect().port(8443).trust_forwarded_proto(true)),
+    )?;
+    let mut server = tsukuyomi_server::test::server(app)?;
+
+    let response =
+        server.perform(Request::post("/path/to?q=1").header("host", "example.com:8080"))?;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.header("location")?,
+        "https://example.com:8443/path/to?q=1"
+    );
+
+    let response = server.perform(
+        Request::get("/")
+            .header("host", "example.com")
+            .header("x-forwarded-proto", "https"),
+    )?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body().to_utf8()?, "secure");
+
+    Ok(())
+}